@@ -261,7 +261,11 @@ impl<V: DeltaValue, Attr: DeltaAttr> DeltaRope<V, Attr> {
                 let other_op_peek = other_iter.peek().cloned();
                 let _ = this_iter.next_with(length);
                 let _ = other_iter.next_with(length);
-                if other_op_peek.map(|x| x.is_delete()).unwrap_or(false) {
+                if other_op_peek
+                    .as_ref()
+                    .map(|x| x.is_delete())
+                    .unwrap_or(false)
+                {
                     // It makes our deletes or retains redundant
                     continue;
                 } else if this_op_peek
@@ -271,13 +275,16 @@ impl<V: DeltaValue, Attr: DeltaAttr> DeltaRope<V, Attr> {
                 {
                     transformed_delta.push_delete(length);
                 } else {
-                    transformed_delta.push_retain(
-                        length,
-                        this_op_peek
-                            .map(|x| x.into_retain().unwrap().1)
-                            .unwrap_or_default(),
-                    );
-                    // FIXME: transform the attributes
+                    let mut attr: Attr = this_op_peek
+                        .map(|x| x.into_retain().unwrap().1)
+                        .unwrap_or_default();
+                    if let Some(DeltaItem::Retain {
+                        attr: other_attr, ..
+                    }) = &other_op_peek
+                    {
+                        attr.transform(other_attr, left_prior);
+                    }
+                    transformed_delta.push_retain(length, attr);
                 }
             }
         }
@@ -10,6 +10,11 @@ pub trait DeltaValue:
 pub trait DeltaAttr: Clone + PartialEq + Debug + Default {
     fn compose(&mut self, other: &Self);
     fn attr_is_empty(&self) -> bool;
+    /// Transform the attributes of a retain against the attributes of a concurrent retain
+    /// on the same range. `left_prior` is true if `self` wins the conflicts.
+    ///
+    /// The attributes are kept as they are by default.
+    fn transform(&mut self, _other: &Self, _left_prior: bool) {}
 }
 
 mod implementations {
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use loro::{Frontiers, ID};
+
+use crate::commands::Result;
+
+/// The options that take a value. `-o` is the short form of `--output`.
+const OPTIONS: &[&str] = &["limit", "at", "from", "to", "mode", "output"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut ans = Args::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = if arg == "-o" {
+                "output"
+            } else if let Some(name) = arg.strip_prefix("--") {
+                name
+            } else {
+                ans.positional.push(arg.clone());
+                continue;
+            };
+
+            if !OPTIONS.contains(&name) {
+                return Err(format!("unknown option `{}`", arg).into());
+            }
+
+            let Some(value) = iter.next() else {
+                return Err(format!("missing value for `{}`", arg).into());
+            };
+            ans.options.insert(name.to_string(), value.clone());
+        }
+
+        Ok(ans)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|x| x.as_str())
+    }
+
+    pub fn frontiers(&self, name: &str) -> Result<Option<Frontiers>> {
+        self.get(name).map(parse_frontiers).transpose()
+    }
+}
+
+/// Parse comma separated ids such as `10@1,3@2`
+pub fn parse_frontiers(s: &str) -> Result<Frontiers> {
+    let mut ids = Vec::new();
+    for id in s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        ids.push(ID::try_from(id).map_err(|_| format!("invalid id `{}`", id))?);
+    }
+
+    Ok(Frontiers::from(ids))
+}
+
+pub fn format_frontiers(f: &Frontiers) -> String {
+    let ids: Vec<_> = f.iter().map(|id| id.to_string()).collect();
+    format!("[{}]", ids.join(", "))
+}
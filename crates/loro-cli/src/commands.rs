@@ -0,0 +1,384 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::ControlFlow,
+};
+
+use loro::{ExportMode, LoroDoc, ToJson, VersionVector};
+
+use crate::args::{format_frontiers, Args};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The number of containers listed by `info`
+const LISTED_CONTAINERS: usize = 10;
+
+pub fn run(command: &str, args: &Args, out: &mut dyn Write) -> Result<()> {
+    match command {
+        "info" => info(single_input(args)?, out),
+        "log" => log(single_input(args)?, args, out),
+        "cat" => cat(single_input(args)?, args, out),
+        "diff" => diff(args, out),
+        "export" => export(args, out),
+        "merge" => merge(args, out),
+        _ => Err(format!("unknown command `{}`, see `loro --help`", command).into()),
+    }
+}
+
+fn single_input(args: &Args) -> Result<&str> {
+    match args.positional.as_slice() {
+        [path] => Ok(path),
+        [] => Err("missing input file".into()),
+        _ => Err("expected exactly one input file".into()),
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| format!("failed to read `{}`: {}", path, e).into())
+}
+
+/// Import a snapshot, updates, JSON updates or NDJSON updates
+fn import_bytes(doc: &LoroDoc, path: &str, bytes: &[u8]) -> Result<()> {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let body = &bytes[start..];
+    let status = if body.starts_with(b"{\"type\":") {
+        doc.import_json_updates_ndjson(body)?
+    } else if body.starts_with(b"{") {
+        doc.import_json_updates(std::str::from_utf8(body)?)?
+    } else {
+        doc.import(bytes)?
+    };
+
+    if status.pending.is_some() {
+        eprintln!(
+            "warning: some changes in `{}` are missing their dependencies",
+            path
+        );
+    }
+
+    Ok(())
+}
+
+fn load(paths: &[String]) -> Result<LoroDoc> {
+    if paths.is_empty() {
+        return Err("missing input file".into());
+    }
+
+    let doc = LoroDoc::new();
+    for path in paths {
+        import_bytes(&doc, path, &read(path)?)?;
+    }
+
+    Ok(doc)
+}
+
+fn format_vv(vv: &VersionVector) -> String {
+    let mut entries: Vec<_> = vv.iter().collect();
+    entries.sort_unstable();
+    let entries: Vec<_> = entries
+        .into_iter()
+        .map(|(peer, counter)| format!("{}: {}", peer, counter))
+        .collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+fn info(path: &str, out: &mut dyn Write) -> Result<()> {
+    let bytes = read(path)?;
+    writeln!(out, "file:       {} ({} bytes)", path, bytes.len())?;
+    match LoroDoc::decode_import_blob_meta(&bytes, true) {
+        Ok(meta) => {
+            writeln!(out, "mode:       {:?}", meta.mode)?;
+            writeln!(out, "changes:    {}", meta.change_num)?;
+            writeln!(
+                out,
+                "timestamps: {}..{}",
+                meta.start_timestamp, meta.end_timestamp
+            )?;
+        }
+        Err(_) => writeln!(out, "mode:       json")?,
+    }
+
+    let doc = LoroDoc::new();
+    import_bytes(&doc, path, &bytes)?;
+    writeln!(out, "version:    {}", format_vv(&doc.oplog_vv()))?;
+    writeln!(
+        out,
+        "frontiers:  {}",
+        format_frontiers(&doc.oplog_frontiers())
+    )?;
+    if doc.is_shallow() {
+        writeln!(
+            out,
+            "shallow:    since {}",
+            format_frontiers(&doc.shallow_since_frontiers())
+        )?;
+    }
+    writeln!(out, "ops:        {}", doc.len_ops())?;
+
+    let analysis = doc.analyze();
+    let state_size: u64 = analysis.containers.values().map(|c| c.size as u64).sum();
+    writeln!(
+        out,
+        "containers: {} ({} dropped, {} bytes of state)",
+        analysis.len(),
+        analysis.dropped_len(),
+        state_size
+    )?;
+    let mut containers: Vec<_> = analysis
+        .containers
+        .iter()
+        .map(|(id, c)| (id.to_string(), c))
+        .collect();
+    containers.sort_unstable_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(&b.0)));
+    for (id, c) in containers.iter().take(LISTED_CONTAINERS) {
+        writeln!(
+            out,
+            "  {}  size={} ops={} depth={}{}",
+            id,
+            c.size,
+            c.ops_num,
+            c.depth,
+            if c.dropped { " dropped" } else { "" }
+        )?;
+    }
+
+    Ok(())
+}
+
+fn log(path: &str, args: &Args, out: &mut dyn Write) -> Result<()> {
+    let limit = match args.get("limit") {
+        Some(x) => x.parse().map_err(|_| format!("invalid limit `{}`", x))?,
+        None => usize::MAX,
+    };
+    let doc = load(&[path.to_string()])?;
+    let mut changes = Vec::new();
+    let frontiers = doc.oplog_frontiers().to_vec();
+    if !frontiers.is_empty() {
+        doc.travel_change_ancestors(&frontiers, &mut |c| {
+            changes.push(c);
+            ControlFlow::Continue(())
+        })?;
+    }
+
+    changes.sort_unstable_by(|a, b| {
+        b.lamport
+            .cmp(&a.lamport)
+            .then_with(|| b.id.peer.cmp(&a.id.peer))
+    });
+    for c in changes.iter().take(limit) {
+        writeln!(
+            out,
+            "change {}  lamport={} ops={} timestamp={}",
+            c.id, c.lamport, c.len, c.timestamp
+        )?;
+        if !c.deps.is_empty() {
+            writeln!(out, "  deps: {}", format_frontiers(&c.deps))?;
+        }
+        if let Some(metadata) = &c.metadata {
+            let metadata = loro::LoroValue::Map(metadata.clone());
+            writeln!(out, "  metadata: {}", metadata.to_json())?;
+        }
+        if let Some(msg) = &c.message {
+            for line in msg.lines() {
+                writeln!(out, "    {}", line)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cat(path: &str, args: &Args, out: &mut dyn Write) -> Result<()> {
+    let doc = load(&[path.to_string()])?;
+    if let Some(f) = args.frontiers("at")? {
+        doc.checkout(&f)?;
+    }
+
+    writeln!(out, "{}", doc.get_deep_value().to_json_pretty())?;
+    Ok(())
+}
+
+fn diff(args: &Args, out: &mut dyn Write) -> Result<()> {
+    let (doc, from, to) = match args.positional.as_slice() {
+        [path] => {
+            let doc = load(&[path.clone()])?;
+            let from = args.frontiers("from")?.unwrap_or_default();
+            let to = args
+                .frontiers("to")?
+                .unwrap_or_else(|| doc.oplog_frontiers());
+            (doc, from, to)
+        }
+        [path, other] => {
+            // Merge the two files into one doc, so that both versions can be checked out
+            let doc = load(&[path.clone()])?;
+            let bytes = read(other)?;
+            let other_doc = LoroDoc::new();
+            import_bytes(&other_doc, other, &bytes)?;
+            let from = doc.oplog_frontiers();
+            import_bytes(&doc, other, &bytes)?;
+            (doc, from, other_doc.oplog_frontiers())
+        }
+        [] => return Err("missing input file".into()),
+        _ => return Err("expected one or two input files".into()),
+    };
+
+    let patch = doc.json_patch_between(&from, &to)?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&patch)?)?;
+    Ok(())
+}
+
+/// Write to the `--output` file if it's given, otherwise to `out`
+fn with_output(
+    args: &Args,
+    out: &mut dyn Write,
+    f: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    match args.get("output") {
+        Some(path) => {
+            let file =
+                File::create(path).map_err(|e| format!("failed to create `{}`: {}", path, e))?;
+            let mut writer = BufWriter::new(file);
+            f(&mut writer)?;
+            writer.flush()?;
+        }
+        None => {
+            f(out)?;
+            out.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn export(args: &Args, out: &mut dyn Write) -> Result<()> {
+    let doc = load(&args.positional)?;
+    let mode = args.get("mode").ok_or("missing `--mode`")?;
+    let at = args.frontiers("at")?;
+    let end_vv = match &at {
+        Some(f) => doc
+            .frontiers_to_vv(f)
+            .ok_or_else(|| format!("unknown frontiers {}", format_frontiers(f)))?,
+        None => doc.oplog_vv(),
+    };
+
+    with_output(args, out, |writer| {
+        match mode {
+            "snapshot" => {
+                let bytes = match &at {
+                    Some(f) => doc.export(ExportMode::snapshot_at(f))?,
+                    None => doc.export(ExportMode::Snapshot)?,
+                };
+                writer.write_all(&bytes)?;
+            }
+            "shallow-snapshot" => {
+                let f = at.clone().unwrap_or_else(|| doc.oplog_frontiers());
+                writer.write_all(&doc.export(ExportMode::shallow_snapshot(&f))?)?;
+            }
+            "updates" => {
+                writer.write_all(&doc.export(ExportMode::updates_till(&end_vv))?)?;
+            }
+            "json" => {
+                let json = doc.export_json_updates(&Default::default(), &end_vv);
+                serde_json::to_writer_pretty(&mut *writer, &json)?;
+                writeln!(writer)?;
+            }
+            "ndjson" => {
+                doc.export_json_updates_ndjson(&Default::default(), &end_vv, writer)?;
+            }
+            _ => return Err(format!("unknown export mode `{}`", mode).into()),
+        }
+
+        Ok(())
+    })
+}
+
+fn merge(args: &Args, out: &mut dyn Write) -> Result<()> {
+    let doc = load(&args.positional)?;
+    let bytes = doc.export(ExportMode::Snapshot)?;
+    with_output(args, out, |writer| {
+        writer.write_all(&bytes)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("loro-cli-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn run_to_string(args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+        let mut out = Vec::new();
+        run(&args[0], &Args::parse(&args[1..]).unwrap(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn inspect_and_convert() {
+        let doc = LoroDoc::new();
+        doc.set_peer_id(1).unwrap();
+        doc.get_map("map").insert("a", 1).unwrap();
+        doc.set_next_commit_message("first");
+        doc.commit();
+        doc.get_map("map").insert("a", 2).unwrap();
+        let metadata = loro::LoroMapValue::from(vec![("author".to_string(), "alice".into())]);
+        doc.commit_with(loro::CommitOptions::new().commit_metadata(metadata));
+        let snapshot = temp_path("snapshot.loro");
+        std::fs::write(&snapshot, doc.export(ExportMode::Snapshot).unwrap()).unwrap();
+
+        let info = run_to_string(&["info", &snapshot]);
+        assert!(info.contains("mode:       Snapshot"), "{}", info);
+        assert!(info.contains("version:    {1: 2}"), "{}", info);
+
+        let log = run_to_string(&["log", &snapshot]);
+        assert!(log.starts_with("change "), "{}", log);
+        assert!(log.contains("    first"), "{}", log);
+        assert!(
+            log.contains("  metadata: {\"author\":\"alice\"}"),
+            "{}",
+            log
+        );
+
+        let value: serde_json::Value =
+            serde_json::from_str(&run_to_string(&["cat", &snapshot, "--at", "0@1"])).unwrap();
+        assert_eq!(value, serde_json::json!({"map": {"a": 1}}));
+
+        let diff: serde_json::Value = serde_json::from_str(&run_to_string(&[
+            "diff", &snapshot, "--from", "0@1", "--to", "1@1",
+        ]))
+        .unwrap();
+        assert_eq!(
+            diff,
+            serde_json::json!([{"op": "add", "path": "/map/a", "value": 2}])
+        );
+
+        let json = temp_path("updates.json");
+        run_to_string(&["export", &snapshot, "--mode", "ndjson", "-o", &json]);
+        let merged = temp_path("merged.loro");
+        run_to_string(&["merge", &json, &snapshot, "-o", &merged]);
+        let new_doc = LoroDoc::new();
+        new_doc.import(&std::fs::read(&merged).unwrap()).unwrap();
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+
+        for path in [snapshot, json, merged] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reject_unknown_options() {
+        let args = vec!["--verbose".to_string()];
+        assert!(Args::parse(&args).is_err());
+        let mut out = Vec::new();
+        assert!(run("unknown", &Args::default(), &mut out).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! `loro`: inspect Loro snapshots and updates on the local file system.
+//!
+//! ```text
+//! loro info <FILE>
+//! loro log <FILE> [--limit <N>]
+//! loro cat <FILE> [--at <FRONTIERS>]
+//! loro diff <FILE> [<OTHER_FILE>] [--from <FRONTIERS>] [--to <FRONTIERS>]
+//! loro export <FILE>... --mode <MODE> [--at <FRONTIERS>] [-o <OUTPUT>]
+//! loro merge <FILE>... [-o <OUTPUT>]
+//! ```
+//!
+//! Frontiers are written as comma separated ids, e.g. `10@1,3@2`.
+use std::process::ExitCode;
+
+mod args;
+mod commands;
+
+use args::Args;
+
+const USAGE: &str = "\
+Inspect Loro snapshots and updates
+
+Usage: loro <COMMAND> [OPTIONS]
+
+Commands:
+  info <FILE>                       Print the mode, version, frontiers and sizes
+  log <FILE> [--limit <N>]          Print the changes, latest first
+  cat <FILE> [--at <FRONTIERS>]     Print the deep value as JSON
+  diff <FILE> [<OTHER_FILE>]        Print the JSON Patch between two versions
+       [--from <FRONTIERS>] [--to <FRONTIERS>]
+  export <FILE>... --mode <MODE>    Convert the input into another format
+       [--at <FRONTIERS>] [-o <OUTPUT>]
+       MODE: snapshot, shallow-snapshot, updates, json, ndjson
+  merge <FILE>... [-o <OUTPUT>]     Merge the inputs into one snapshot
+
+The inputs can be snapshots, updates, JSON updates or NDJSON updates.
+Frontiers are comma separated ids, e.g. `10@1,3@2`.
+Without `-o`, the output is written to stdout.
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|x| x == "-h" || x == "--help") {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let result = Args::parse(&args[1..]).and_then(|parsed| {
+        let stdout = std::io::stdout();
+        commands::run(&args[0], &parsed, &mut stdout.lock())
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
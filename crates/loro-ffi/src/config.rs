@@ -43,6 +43,14 @@ impl StyleConfigMap {
         self.0.write().unwrap().insert(key.into(), value);
     }
 
+    pub fn insert_block(&self, key: &str) {
+        self.0.write().unwrap().insert_block(key.into());
+    }
+
+    pub fn is_block(&self, key: &str) -> bool {
+        self.0.read().unwrap().is_block(&(key.into()))
+    }
+
     pub fn get(&self, key: &str) -> Option<StyleConfig> {
         let m = self.0.read().unwrap();
         m.get(&(key.into())).cloned()
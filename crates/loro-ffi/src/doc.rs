@@ -593,6 +593,8 @@ pub struct ChangeMeta {
     pub timestamp: Timestamp,
     /// The commit message of the change
     pub message: Option<String>,
+    /// The user defined metadata of the change
+    pub metadata: Option<HashMap<String, LoroValue>>,
     /// The dependencies of the first op of the change
     pub deps: Arc<Frontiers>,
     /// The total op num inside this change
@@ -606,6 +608,11 @@ impl From<loro::ChangeMeta> for ChangeMeta {
             id: value.id,
             timestamp: value.timestamp,
             message: value.message.map(|x| (*x).to_string()),
+            metadata: value.metadata.map(|m| {
+                m.iter()
+                    .map(|(k, v)| (k.to_string(), v.clone().into()))
+                    .collect()
+            }),
             deps: Arc::new(value.deps.into()),
             len: value.len as u32,
         }
@@ -651,6 +658,7 @@ pub struct CommitOptions {
     pub immediate_renew: bool,
     pub timestamp: Option<Timestamp>,
     pub commit_msg: Option<String>,
+    pub commit_metadata: Option<HashMap<String, LoroValue>>,
 }
 
 impl From<CommitOptions> for loro::CommitOptions {
@@ -660,6 +668,12 @@ impl From<CommitOptions> for loro::CommitOptions {
             immediate_renew: value.immediate_renew,
             timestamp: value.timestamp,
             commit_msg: value.commit_msg.map(|x| x.into()),
+            commit_metadata: value.commit_metadata.map(|m| {
+                m.into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect::<HashMap<String, loro::LoroValue>>()
+                    .into()
+            }),
         }
     }
 }
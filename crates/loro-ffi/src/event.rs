@@ -81,6 +81,10 @@ pub enum TextDelta {
     Delete {
         delete: u32,
     },
+    InsertEmbed {
+        insert: LoroValue,
+        attributes: Option<HashMap<String, LoroValue>>,
+    },
 }
 
 pub enum ListDiffItem {
@@ -240,6 +244,16 @@ impl<'a, 'b> From<&'b loro::event::Diff<'a>> for Diff {
                                 delete: *delete as u32,
                             });
                         }
+                        loro::TextDelta::InsertEmbed { insert, attributes } => {
+                            ans.push(TextDelta::InsertEmbed {
+                                insert: insert.clone().into(),
+                                attributes: attributes.as_ref().map(|a| {
+                                    a.iter()
+                                        .map(|(k, v)| (k.to_string(), v.clone().into()))
+                                        .collect()
+                                }),
+                            });
+                        }
                     }
                 }
 
@@ -12,7 +12,7 @@ use crate::{
     span::{HasId, HasLamport},
     version::Frontiers,
 };
-use loro_common::{HasCounter, HasCounterSpan, PeerID};
+use loro_common::{HasCounter, HasCounterSpan, LoroMapValue, PeerID};
 use num::traits::AsPrimitive;
 use rle::{HasIndex, HasLength, Mergable, RleVec, Sliceable};
 use smallvec::SmallVec;
@@ -35,6 +35,8 @@ pub struct Change<O = Op> {
     /// It is the number of seconds that have elapsed since 00:00:00 UTC on 1 January 1970.
     pub(crate) timestamp: Timestamp,
     pub(crate) commit_msg: Option<Arc<str>>,
+    /// The user defined metadata of the change, e.g. author id or client version
+    pub(crate) commit_metadata: Option<LoroMapValue>,
     pub(crate) ops: RleVec<[O; 1]>,
 }
 
@@ -53,6 +55,7 @@ impl<O> Change<O> {
             lamport,
             timestamp,
             commit_msg: None,
+            commit_metadata: None,
         }
     }
 
@@ -98,6 +101,10 @@ impl<O> Change<O> {
     pub fn message(&self) -> Option<&Arc<str>> {
         self.commit_msg.as_ref()
     }
+
+    pub fn metadata(&self) -> Option<&LoroMapValue> {
+        self.commit_metadata.as_ref()
+    }
 }
 
 impl<O: EstimatedSize> EstimatedSize for Change<O> {
@@ -232,6 +239,7 @@ impl<O: Mergable + HasLength + HasIndex + Sliceable + HasCounter + Debug> Slicea
             lamport: self.lamport + from as Lamport,
             timestamp: self.timestamp,
             commit_msg: self.commit_msg.clone(),
+            commit_metadata: self.commit_metadata.clone(),
         }
     }
 }
@@ -250,6 +258,7 @@ impl Change {
             && other.deps.as_single().unwrap().peer == self.id.peer
             && other.timestamp - self.timestamp < merge_interval
             && self.commit_msg == other.commit_msg
+            && self.commit_metadata == other.commit_metadata
         {
             debug_assert!(other.timestamp >= self.timestamp);
             debug_assert!(other.lamport == self.lamport + self.len() as Lamport);
@@ -1,6 +1,6 @@
 use std::{cmp::Ordering, sync::Arc};
 
-use loro_common::HasLamport;
+use loro_common::{HasLamport, LoroMapValue};
 use rle::HasLength;
 
 use crate::{
@@ -31,6 +31,8 @@ pub struct ChangeMeta {
     pub timestamp: Timestamp,
     /// The commit message of the change
     pub message: Option<Arc<str>>,
+    /// The user defined metadata of the change, e.g. author id, session id or client version
+    pub metadata: Option<LoroMapValue>,
     /// The dependencies of the first op of the change
     pub deps: Frontiers,
     /// The total op num inside this change
@@ -78,6 +80,7 @@ impl ChangeMeta {
             lamport: c.lamport(),
             timestamp: c.timestamp(),
             message: c.message().cloned(),
+            metadata: c.metadata().cloned(),
             deps: c.deps().clone(),
             len: c.len(),
         }
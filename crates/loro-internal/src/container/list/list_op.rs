@@ -109,8 +109,10 @@ impl InnerListOp {
     pub(crate) fn estimate_storage_size(&self, container_type: ContainerType) -> usize {
         match self {
             InnerListOp::Insert { slice, .. } => match container_type {
-                ContainerType::MovableList | ContainerType::List => 4 * slice.atom_len(),
-                ContainerType::Text => slice.atom_len(),
+                // Text containers use it for embeds
+                ContainerType::MovableList | ContainerType::List | ContainerType::Text => {
+                    4 * slice.atom_len()
+                }
                 _ => unreachable!(),
             },
             InnerListOp::InsertText { slice, .. } => slice.len(),
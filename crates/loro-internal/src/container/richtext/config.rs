@@ -1,4 +1,4 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use loro_common::InternalString;
 
 use super::{ExpandType, TextStyleInfoFlag};
@@ -6,12 +6,15 @@ use super::{ExpandType, TextStyleInfoFlag};
 #[derive(Debug, Default, Clone)]
 pub struct StyleConfigMap {
     map: FxHashMap<InternalString, StyleConfig>,
+    /// The keys that are block attributes
+    blocks: FxHashSet<InternalString>,
 }
 
 impl StyleConfigMap {
     pub fn new() -> Self {
         Self {
             map: FxHashMap::default(),
+            blocks: FxHashSet::default(),
         }
     }
 
@@ -20,9 +23,29 @@ impl StyleConfigMap {
             panic!("style key should not contain ':'");
         }
 
+        self.blocks.remove(&key);
         self.map.insert(key, value);
     }
 
+    /// Register `key` as a block attribute, such as a heading level, a list type or an
+    /// alignment.
+    ///
+    /// A block attribute belongs to a paragraph. It is stored on the newline that ends the
+    /// paragraph, so marking any range with it styles the newlines of the paragraphs the range
+    /// touches. It never expands to the inserted text.
+    pub fn insert_block(&mut self, key: InternalString) {
+        if key.contains(':') {
+            panic!("style key should not contain ':'");
+        }
+
+        self.map.insert(key.clone(), StyleConfig::new());
+        self.blocks.insert(key);
+    }
+
+    pub fn is_block(&self, key: &InternalString) -> bool {
+        self.blocks.contains(key)
+    }
+
     pub fn get(&self, key: &InternalString) -> Option<&StyleConfig> {
         self.map.get(key)
     }
@@ -48,9 +71,7 @@ impl StyleConfigMap {
     }
 
     pub fn default_rich_text_config() -> Self {
-        let mut map = Self {
-            map: FxHashMap::default(),
-        };
+        let mut map = Self::new();
 
         map.map.insert(
             "bold".into(),
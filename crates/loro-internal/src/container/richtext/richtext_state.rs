@@ -23,9 +23,14 @@ use tracing::instrument;
 use crate::{
     container::richtext::style_range_map::EMPTY_STYLES,
     delta::{DeltaValue, StyleMeta},
-    utils::query_by_len::{EntityIndexQueryWithEventIndex, IndexQueryWithEntityIndex, QueryByLen},
+    utils::{
+        query_by_len::{EntityIndexQueryWithEventIndex, IndexQueryWithEntityIndex, QueryByLen},
+        string_slice::StringSlice,
+    },
 };
 
+#[cfg(feature = "line-index")]
+use self::query::LineStartQuery;
 use self::query::{
     EntityQuery, EntityQueryT, EventIndexQuery, EventIndexQueryT, UnicodeQuery, UnicodeQueryT,
     Utf16Query, Utf16QueryT,
@@ -49,7 +54,7 @@ impl Display for RichtextState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for span in self.tree.iter() {
             match span {
-                RichtextStateChunk::Style { .. } => {}
+                RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => {}
                 RichtextStateChunk::Text(s) => {
                     f.write_str(s.as_str())?;
                 }
@@ -275,6 +280,8 @@ mod text_chunk {
         bytes: BytesSlice,
         unicode_len: i32,
         utf16_len: i32,
+        #[cfg(feature = "line-index")]
+        line_breaks: i32,
         id: IdFull,
     }
 
@@ -293,15 +300,23 @@ mod text_chunk {
         pub fn new(bytes: BytesSlice, id: IdFull) -> Self {
             let mut utf16_len = 0;
             let mut unicode_len = 0;
+            #[cfg(feature = "line-index")]
+            let mut line_breaks = 0;
             for c in std::str::from_utf8(&bytes).unwrap().chars() {
                 utf16_len += c.len_utf16();
                 unicode_len += 1;
+                #[cfg(feature = "line-index")]
+                if c == '\n' {
+                    line_breaks += 1;
+                }
             }
 
             Self {
                 unicode_len,
                 bytes,
                 utf16_len: utf16_len as i32,
+                #[cfg(feature = "line-index")]
+                line_breaks,
                 id,
             }
         }
@@ -353,6 +368,12 @@ mod text_chunk {
             self.utf16_len
         }
 
+        #[cfg(feature = "line-index")]
+        #[inline]
+        pub fn line_breaks(&self) -> i32 {
+            self.line_breaks
+        }
+
         #[inline]
         pub fn event_len(&self) -> i32 {
             if cfg!(feature = "wasm") {
@@ -383,6 +404,8 @@ mod text_chunk {
                 unicode_len: 0,
                 bytes: BytesSlice::empty(),
                 utf16_len: 0,
+                #[cfg(feature = "line-index")]
+                line_breaks: 0,
                 // This is a dummy value.
                 // It's fine because the length is 0. We never actually use this value.
                 id: IdFull::NONE_ID,
@@ -402,10 +425,16 @@ mod text_chunk {
             let mut start_utf16_index = 0;
             let mut current_utf16_index = 0;
             let mut current_utf8_index = 0;
+            #[cfg(feature = "line-index")]
+            let mut start_line_breaks = 0;
+            #[cfg(feature = "line-index")]
+            let mut current_line_breaks = 0;
             for (current_unicode_index, c) in s.chars().enumerate() {
                 if current_unicode_index == start_unicode_index {
                     start_utf16_index = current_utf16_index;
                     start_byte = current_utf8_index;
+                    #[cfg(feature = "line-index")]
+                    start_line_breaks = current_line_breaks;
                 }
 
                 if current_unicode_index == end_unicode_index {
@@ -415,9 +444,15 @@ mod text_chunk {
 
                 current_utf16_index += c.len_utf16();
                 current_utf8_index += c.len_utf8();
+                #[cfg(feature = "line-index")]
+                if c == '\n' {
+                    current_line_breaks += 1;
+                }
             }
 
             self.utf16_len -= (current_utf16_index - start_utf16_index) as i32;
+            #[cfg(feature = "line-index")]
+            self.line_breaks -= current_line_breaks - start_line_breaks;
 
             let event_len = if cfg!(feature = "wasm") {
                 current_utf16_index - start_utf16_index
@@ -445,6 +480,8 @@ mod text_chunk {
                     let next = Self::new(next, self.id.inc(end_unicode_index as i32));
                     self.unicode_len -= next.unicode_len;
                     self.utf16_len -= next.utf16_len;
+                    #[cfg(feature = "line-index")]
+                    self.line_breaks -= next.line_breaks;
                     self.bytes.slice_(..start_byte);
                     Some(next)
                 }
@@ -464,6 +501,8 @@ mod text_chunk {
                     self.utf16_len,
                     self.as_str().chars().map(|c| c.len_utf16()).sum::<usize>() as i32
                 );
+                #[cfg(feature = "line-index")]
+                assert_eq!(self.line_breaks, self.as_str().matches('\n').count() as i32);
             }
         }
 
@@ -510,6 +549,8 @@ mod text_chunk {
         fn _slice(&self, range: Range<usize>) -> Self {
             assert!(range.start < range.end);
             let mut utf16_len = 0;
+            #[cfg(feature = "line-index")]
+            let mut line_breaks = 0;
             let mut start = 0;
             let mut end = 0;
             let mut started = false;
@@ -526,6 +567,10 @@ mod text_chunk {
                 }
                 if started {
                     utf16_len += c.len_utf16();
+                    #[cfg(feature = "line-index")]
+                    if c == '\n' {
+                        line_breaks += 1;
+                    }
                 }
 
                 last_unicode_index = unicode_index;
@@ -541,6 +586,8 @@ mod text_chunk {
                 unicode_len: range.len() as i32,
                 bytes: self.bytes.slice_clone(start..end),
                 utf16_len: utf16_len as i32,
+                #[cfg(feature = "line-index")]
+                line_breaks,
                 id: self.id.inc(range.start as i32),
             };
             ans.check();
@@ -549,6 +596,8 @@ mod text_chunk {
 
         fn split(&mut self, pos: usize) -> Self {
             let mut utf16_len = 0;
+            #[cfg(feature = "line-index")]
+            let mut line_breaks = 0;
             let mut byte_offset = 0;
             for (unicode_index, (i, c)) in self.as_str().char_indices().enumerate() {
                 if unicode_index == pos {
@@ -557,16 +606,24 @@ mod text_chunk {
                 }
 
                 utf16_len += c.len_utf16();
+                #[cfg(feature = "line-index")]
+                if c == '\n' {
+                    line_breaks += 1;
+                }
             }
             let right = Self {
                 unicode_len: self.unicode_len - pos as i32,
                 bytes: self.bytes.slice_clone(byte_offset..),
                 utf16_len: self.utf16_len - utf16_len as i32,
+                #[cfg(feature = "line-index")]
+                line_breaks: self.line_breaks - line_breaks,
                 id: self.id.inc(pos as i32),
             };
 
             self.unicode_len = pos as i32;
             self.utf16_len = utf16_len as i32;
+            #[cfg(feature = "line-index")]
+            self.line_breaks = line_breaks;
             self.bytes.slice_(..byte_offset);
             right.check();
             self.check();
@@ -583,6 +640,8 @@ mod text_chunk {
             self.bytes.try_merge(&rhs.bytes).unwrap();
             self.utf16_len += rhs.utf16_len;
             self.unicode_len += rhs.unicode_len;
+            #[cfg(feature = "line-index")]
+            self.line_breaks += rhs.line_breaks;
             self.check();
         }
 
@@ -592,6 +651,8 @@ mod text_chunk {
             self.bytes = new;
             self.utf16_len += left.utf16_len;
             self.unicode_len += left.unicode_len;
+            #[cfg(feature = "line-index")]
+            self.line_breaks += left.line_breaks;
             self.id = left.id;
             self.check();
         }
@@ -606,6 +667,12 @@ pub(crate) enum RichtextStateChunk {
         style: Arc<StyleOp>,
         anchor_type: AnchorType,
     },
+    /// An inline embed. It holds a value or a child container and its length is 1 in every
+    /// position type.
+    Embed {
+        value: LoroValue,
+        id: IdFull,
+    },
 }
 
 impl Default for RichtextStateChunk {
@@ -627,6 +694,10 @@ impl RichtextStateChunk {
         Self::Style { style, anchor_type }
     }
 
+    pub fn new_embed(value: LoroValue, id: IdFull) -> Self {
+        Self::Embed { value, id }
+    }
+
     pub(crate) fn get_id_lp_span(&self) -> IdLpSpan {
         match self {
             RichtextStateChunk::Text(t) => {
@@ -640,6 +711,7 @@ impl RichtextStateChunk {
                     IdLpSpan::new(id.peer, id.lamport + 1, id.lamport + 2)
                 }
             },
+            RichtextStateChunk::Embed { id, .. } => id.idlp().into(),
         }
     }
 
@@ -656,6 +728,7 @@ impl RichtextStateChunk {
                     id.to_span(1)
                 }
             },
+            RichtextStateChunk::Embed { id, .. } => id.id().into(),
         }
     }
 
@@ -669,6 +742,7 @@ impl RichtextStateChunk {
                     id.counter + 1
                 }
             },
+            RichtextStateChunk::Embed { id, .. } => id.counter,
         }
     }
 
@@ -680,6 +754,7 @@ impl RichtextStateChunk {
                 assert_eq!(range.end, 1);
                 0..1
             }
+            RichtextStateChunk::Embed { .. } => range,
         }
     }
 
@@ -699,6 +774,7 @@ impl RichtextStateChunk {
                     0
                 }
             }
+            RichtextStateChunk::Embed { .. } => 1,
         }
     }
 }
@@ -741,6 +817,12 @@ impl Serialize for RichtextStateChunk {
                 state.serialize_field("anchor_type", anchor_type)?;
                 state.end()
             }
+            RichtextStateChunk::Embed { value, .. } => {
+                let mut state = serializer.serialize_struct("RichtextStateChunk", 2)?;
+                state.serialize_field("type", "Embed")?;
+                state.serialize_field("value", value)?;
+                state.end()
+            }
         }
     }
 }
@@ -761,13 +843,20 @@ impl RichtextStateChunk {
             _ => None,
         }
     }
+
+    pub fn as_embed(&self) -> Option<&LoroValue> {
+        match self {
+            RichtextStateChunk::Embed { value, .. } => Some(value),
+            _ => None,
+        }
+    }
 }
 
 impl HasLength for RichtextStateChunk {
     fn rle_len(&self) -> usize {
         match self {
             RichtextStateChunk::Text(s) => s.rle_len(),
-            RichtextStateChunk::Style { .. } => 1,
+            RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => 1,
         }
     }
 }
@@ -809,13 +898,18 @@ impl Sliceable for RichtextStateChunk {
                     anchor_type: *anchor_type,
                 }
             }
+            RichtextStateChunk::Embed { .. } => {
+                assert_eq!(range.start, 0);
+                assert_eq!(range.end, 1);
+                self.clone()
+            }
         }
     }
 
     fn split(&mut self, pos: usize) -> Self {
         match self {
             RichtextStateChunk::Text(s) => RichtextStateChunk::Text(s.split(pos)),
-            RichtextStateChunk::Style { .. } => {
+            RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => {
                 unreachable!()
             }
         }
@@ -966,6 +1060,9 @@ pub(crate) struct PosCache {
     pub(super) bytes: i32,
     pub(super) utf16_len: i32,
     pub(crate) entity_len: i32,
+    /// The number of `\n` in the text, used to index the text by lines
+    #[cfg(feature = "line-index")]
+    pub(super) line_breaks: i32,
 }
 
 impl PosCache {
@@ -995,6 +1092,8 @@ impl AddAssign for PosCache {
         self.bytes += rhs.bytes;
         self.utf16_len += rhs.utf16_len;
         self.entity_len += rhs.entity_len;
+        #[cfg(feature = "line-index")]
+        self.line_breaks += rhs.line_breaks;
     }
 }
 
@@ -1007,6 +1106,8 @@ impl Add for PosCache {
             unicode_len: self.unicode_len + rhs.unicode_len,
             utf16_len: self.utf16_len + rhs.utf16_len,
             entity_len: self.entity_len + rhs.entity_len,
+            #[cfg(feature = "line-index")]
+            line_breaks: self.line_breaks + rhs.line_breaks,
         }
     }
 }
@@ -1020,6 +1121,8 @@ impl Sub for PosCache {
             unicode_len: self.unicode_len - rhs.unicode_len,
             utf16_len: self.utf16_len - rhs.utf16_len,
             entity_len: self.entity_len - rhs.entity_len,
+            #[cfg(feature = "line-index")]
+            line_breaks: self.line_breaks - rhs.line_breaks,
         }
     }
 }
@@ -1085,12 +1188,24 @@ impl BTreeTrait for RichtextTreeTrait {
                 unicode_len: s.unicode_len(),
                 utf16_len: s.utf16_len(),
                 entity_len: s.unicode_len(),
+                #[cfg(feature = "line-index")]
+                line_breaks: s.line_breaks(),
             },
             RichtextStateChunk::Style { .. } => PosCache {
                 bytes: 0,
                 unicode_len: 0,
                 utf16_len: 0,
                 entity_len: 1,
+                #[cfg(feature = "line-index")]
+                line_breaks: 0,
+            },
+            RichtextStateChunk::Embed { .. } => PosCache {
+                bytes: 1,
+                unicode_len: 1,
+                utf16_len: 1,
+                entity_len: 1,
+                #[cfg(feature = "line-index")]
+                line_breaks: 0,
             },
         }
     }
@@ -1107,12 +1222,17 @@ impl BTreeTrait for RichtextTreeTrait {
             unicode_len: cache_lhs.unicode_len - cache_rhs.unicode_len,
             utf16_len: cache_lhs.utf16_len - cache_rhs.utf16_len,
             entity_len: cache_lhs.entity_len - cache_rhs.entity_len,
+            #[cfg(feature = "line-index")]
+            line_breaks: cache_lhs.line_breaks - cache_rhs.line_breaks,
         }
     }
 }
 
 // This query implementation will prefer right element when both left element and right element are valid.
 mod query {
+    #[cfg(feature = "line-index")]
+    use generic_btree::{FindResult, Query};
+
     use crate::utils::query_by_len::{IndexQuery, QueryByLen};
 
     use super::*;
@@ -1152,6 +1272,7 @@ mod query {
             match elem {
                 RichtextStateChunk::Text(s) => s.rle_len(),
                 RichtextStateChunk::Style { .. } => 0,
+                RichtextStateChunk::Embed { .. } => 1,
             }
         }
 
@@ -1168,6 +1289,7 @@ mod query {
                     (left, false)
                 }
                 RichtextStateChunk::Style { .. } => (1, false),
+                RichtextStateChunk::Embed { .. } => (left, left <= 1),
             }
         }
 
@@ -1188,6 +1310,7 @@ mod query {
             match elem {
                 RichtextStateChunk::Text(s) => s.utf16_len() as usize,
                 RichtextStateChunk::Style { .. } => 0,
+                RichtextStateChunk::Embed { .. } => 1,
             }
         }
 
@@ -1208,6 +1331,7 @@ mod query {
                     (offset, true)
                 }
                 RichtextStateChunk::Style { .. } => (1, false),
+                RichtextStateChunk::Embed { .. } => (left.min(1), true),
             }
         }
 
@@ -1227,7 +1351,7 @@ mod query {
         fn get_elem_len(elem: &<RichtextTreeTrait as BTreeTrait>::Elem) -> usize {
             match elem {
                 RichtextStateChunk::Text(s) => s.rle_len(),
-                RichtextStateChunk::Style { .. } => 1,
+                RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => 1,
             }
         }
 
@@ -1243,6 +1367,7 @@ mod query {
 
                     (left, false)
                 }
+                RichtextStateChunk::Embed { .. } => (left, left <= 1),
                 RichtextStateChunk::Style { .. } => {
                     if left == 0 {
                         return (0, true);
@@ -1268,6 +1393,7 @@ mod query {
             match elem {
                 RichtextStateChunk::Text(s) => s.utf8_len() as usize,
                 RichtextStateChunk::Style { .. } => 0,
+                RichtextStateChunk::Embed { .. } => 1,
             }
         }
 
@@ -1288,6 +1414,7 @@ mod query {
                     (offset, true)
                 }
                 RichtextStateChunk::Style { .. } => (1, false),
+                RichtextStateChunk::Embed { .. } => (left.min(1), true),
             }
         }
 
@@ -1295,6 +1422,67 @@ mod query {
             cache.entity_len as usize
         }
     }
+
+    #[cfg(feature = "line-index")]
+    /// Find the start of a line, i.e. the position right after its preceding `\n`.
+    ///
+    /// The query arg is the line index. Line 0 starts at the beginning of the text.
+    pub(super) struct LineStartQuery {
+        left: usize,
+    }
+
+    #[cfg(feature = "line-index")]
+    impl Query<RichtextTreeTrait> for LineStartQuery {
+        type QueryArg = usize;
+
+        fn init(target: &Self::QueryArg) -> Self {
+            Self { left: *target }
+        }
+
+        fn find_node(
+            &mut self,
+            _: &Self::QueryArg,
+            child_caches: &[generic_btree::Child<RichtextTreeTrait>],
+        ) -> FindResult {
+            for (i, child) in child_caches.iter().enumerate() {
+                let line_breaks = child.cache.line_breaks as usize;
+                if self.left > line_breaks {
+                    self.left -= line_breaks;
+                } else {
+                    return FindResult::new_found(i, self.left);
+                }
+            }
+
+            FindResult::new_missing(child_caches.len() - 1, self.left)
+        }
+
+        fn confirm_elem(
+            &mut self,
+            _: &Self::QueryArg,
+            elem: &<RichtextTreeTrait as BTreeTrait>::Elem,
+        ) -> (usize, bool) {
+            if self.left == 0 {
+                return (0, true);
+            }
+
+            match elem {
+                RichtextStateChunk::Text(s) => {
+                    let mut left = self.left;
+                    for (i, c) in s.as_str().chars().enumerate() {
+                        if c == '\n' {
+                            left -= 1;
+                            if left == 0 {
+                                return (i + 1, true);
+                            }
+                        }
+                    }
+
+                    (s.rle_len(), false)
+                }
+                RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => (0, false),
+            }
+        }
+    }
 }
 
 impl RichtextState {
@@ -1592,14 +1780,16 @@ impl RichtextState {
             let Some(str) = &self.tree.get_elem(cursor.leaf) else {
                 return Err(());
             };
+            let Some(s) = str.as_str() else {
+                return Err(());
+            };
             if cfg!(not(feature = "wasm")) {
-                let mut char_iter = str.as_str().unwrap().chars();
+                let mut char_iter = s.chars();
                 match &mut char_iter.nth(cursor.offset) {
                     Some(c) => Ok(*c),
                     None => Err(()),
                 }
             } else {
-                let s = str.as_str().unwrap();
                 let utf16offset = unicode_to_utf16_index(s, cursor.offset).unwrap();
                 match s.encode_utf16().nth(utf16offset) {
                     Some(c) => Ok(std::char::from_u32(c as u32).unwrap()),
@@ -1641,7 +1831,7 @@ impl RichtextState {
             let mut right = left;
             let mut elem = self.tree.get_elem(right.leaf).unwrap();
             let entity_index = 0;
-            if matches!(elem, RichtextStateChunk::Text { .. }) {
+            if !matches!(elem, RichtextStateChunk::Style { .. }) {
                 return (Some(right), 0);
             } else {
                 while Q::get_elem_len(elem) == 0 {
@@ -1682,7 +1872,7 @@ impl RichtextState {
                     assert_eq!(right.offset, elem.rle_len());
                     right = x;
                     let mut elem = self.tree.get_elem(right.leaf).unwrap();
-                    if matches!(elem, RichtextStateChunk::Text { .. }) {
+                    if !matches!(elem, RichtextStateChunk::Style { .. }) {
                         return (Some(right), entity_index);
                     }
 
@@ -1716,7 +1906,9 @@ impl RichtextState {
             };
 
             let (style, anchor_type) = match elem {
-                RichtextStateChunk::Text { .. } => unreachable!(),
+                RichtextStateChunk::Text { .. } | RichtextStateChunk::Embed { .. } => {
+                    unreachable!()
+                }
                 RichtextStateChunk::Style { style, anchor_type } => (style, *anchor_type),
             };
 
@@ -1842,9 +2034,9 @@ impl RichtextState {
 
                 let len = end - start;
                 match span.elem {
-                    RichtextStateChunk::Text(s) => {
-                        let event_len = s.entity_range_to_event_range(start..end).len();
-                        let id = s.id().inc(start as i32);
+                    RichtextStateChunk::Text(_) | RichtextStateChunk::Embed { .. } => {
+                        let event_len = span.elem.entity_range_to_event_range(start..end).len();
+                        let id = span.elem.get_id_span().norm_id_start().inc(start as i32);
                         match ans.last_mut() {
                             Some(last)
                                 if last.entity_end == entity_index
@@ -1973,6 +2165,9 @@ impl RichtextState {
                         RichtextStateChunk::Text(t) => {
                             self.current_index += t.unicode_len() as usize;
                         }
+                        RichtextStateChunk::Embed { .. } => {
+                            self.current_index += 1;
+                        }
                         RichtextStateChunk::Style { style, anchor_type } => {
                             if matches!(anchor_type, AnchorType::End) {
                                 self.end = self.end.max(self.current_index);
@@ -2033,7 +2228,11 @@ impl RichtextState {
                             event_len = event_len_;
                             (true, next.map(RichtextStateChunk::Text), None)
                         }
-                        RichtextStateChunk::Style { .. } => {
+                        RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => {
+                            if matches!(elem, RichtextStateChunk::Embed { .. }) {
+                                event_len = 1;
+                            }
+
                             if let Some(f) = f {
                                 let v = std::mem::replace(
                                     elem,
@@ -2118,6 +2317,19 @@ impl RichtextState {
         self.cursor_to_event_index(cursor.cursor)
     }
 
+    /// Convert an event index into an index of `pos_type`.
+    pub fn event_index_to_index(&self, index: usize, pos_type: PosType) -> usize {
+        if pos_type == PosType::Event {
+            return index;
+        }
+
+        let Some(cursor) = self.tree.query::<EventIndexQuery>(&index) else {
+            return 0;
+        };
+
+        self.get_index_from_cursor(cursor.cursor, pos_type).unwrap()
+    }
+
     pub fn event_index_to_unicode_index(&self, index: usize) -> usize {
         if !cfg!(feature = "wasm") {
             return index;
@@ -2176,7 +2388,7 @@ impl RichtextState {
             cur_style_range.as_ref().map(|x| x.1.clone().into());
 
         self.tree.iter().filter_map(move |x| match x {
-            RichtextStateChunk::Text(s) => {
+            RichtextStateChunk::Text(_) | RichtextStateChunk::Embed { .. } => {
                 let mut styles = Default::default();
                 while let Some((inner_cur_range, _)) = cur_style_range.as_ref() {
                     if entity_index < inner_cur_range.start {
@@ -2192,9 +2404,16 @@ impl RichtextState {
                     }
                 }
 
-                entity_index += s.rle_len();
+                entity_index += x.rle_len();
+                let text = match x {
+                    RichtextStateChunk::Text(s) => s.bytes().clone().into(),
+                    RichtextStateChunk::Embed { value, .. } => {
+                        StringSlice::new_embed(value.clone())
+                    }
+                    RichtextStateChunk::Style { .. } => unreachable!(),
+                };
                 Some(RichtextSpan {
-                    text: s.bytes().clone().into(),
+                    text,
                     attributes: styles,
                 })
             }
@@ -2217,6 +2436,19 @@ impl RichtextState {
             let mut last_attributes: Option<LoroValue> = None;
             for span in self.iter() {
                 let attributes: LoroValue = span.attributes.to_value();
+                if let Some(embed) = span.text.as_embed() {
+                    // Embeds are never merged with their neighbors
+                    let mut value = FxHashMap::default();
+                    value.insert("insert".into(), embed.clone());
+                    if !attributes.as_map().unwrap().is_empty() {
+                        value.insert("attributes".into(), attributes);
+                    }
+
+                    ans.push(LoroValue::Map(value.into()));
+                    last_attributes = None;
+                    continue;
+                }
+
                 if let Some(last) = last_attributes.as_ref() {
                     if &attributes == last {
                         let hash_map = ans.last_mut().unwrap().as_map_mut().unwrap();
@@ -2336,7 +2568,7 @@ impl RichtextState {
                     });
 
                 match c {
-                    RichtextStateChunk::Text(_) => {
+                    RichtextStateChunk::Text(_) | RichtextStateChunk::Embed { .. } => {
                         unreachable!()
                     }
                     RichtextStateChunk::Style { style, anchor_type } => {
@@ -2363,7 +2595,7 @@ impl RichtextState {
         let mut start_ops: FxHashSet<&Arc<StyleOp>> = Default::default();
         for item in self.iter_chunk() {
             match item {
-                RichtextStateChunk::Text(_) => {}
+                RichtextStateChunk::Text(_) | RichtextStateChunk::Embed { .. } => {}
                 RichtextStateChunk::Style { style, anchor_type } => match anchor_type {
                     AnchorType::Start => {
                         start_ops.insert(style);
@@ -2524,7 +2756,7 @@ impl RichtextState {
         result
     }
 
-    fn len(&self, pos_type: PosType) -> usize {
+    pub(crate) fn len(&self, pos_type: PosType) -> usize {
         self.check_cache();
         let result = {
             match pos_type {
@@ -2538,6 +2770,168 @@ impl RichtextState {
         self.check_cache();
         result
     }
+
+    /// Get the styles of the element at `pos`, or `None` if `pos` is out of bound.
+    ///
+    /// The removed styles are included with null values.
+    pub(crate) fn get_styles_at(&self, pos: usize, pos_type: PosType) -> Option<StyleMeta> {
+        let range = self
+            .get_text_entity_ranges(pos, 1, pos_type)
+            .ok()?
+            .into_iter()
+            .next()?;
+        if !self.has_styles() {
+            return Some(StyleMeta::default());
+        }
+
+        let styles = self
+            .style_ranges
+            .as_ref()
+            .unwrap()
+            .get_styles_of_range(range.entity_start..range.entity_start + 1);
+        Some(styles.map(|x| x.into()).unwrap_or_default())
+    }
+
+    /// Get the event index of the first `\n` at or after the event index `pos`.
+    ///
+    /// Only the chunks between `pos` and the newline are visited.
+    pub(crate) fn next_newline(&self, pos: usize) -> Option<usize> {
+        if pos >= self.len_event() {
+            return None;
+        }
+
+        let start = self.tree.query::<EventIndexQuery>(&pos)?.cursor;
+        let mut index = pos;
+        for span in self.tree.iter_range(start..) {
+            let start = span.start.unwrap_or(0);
+            let end = span.end.unwrap_or(span.elem.rle_len());
+            match span.elem {
+                RichtextStateChunk::Text(t) => {
+                    for c in t.as_str().chars().take(end).skip(start) {
+                        if c == '\n' {
+                            return Some(index);
+                        }
+
+                        index += if cfg!(feature = "wasm") {
+                            c.len_utf16()
+                        } else {
+                            1
+                        };
+                    }
+                }
+                RichtextStateChunk::Embed { .. } => index += end.saturating_sub(start),
+                RichtextStateChunk::Style { .. } => {}
+            }
+        }
+
+        None
+    }
+
+    /// Get the styled ranges in order, read from the style range map.
+    ///
+    /// Ranges that don't cover any text, such as the styles of deleted text, are skipped.
+    pub(crate) fn get_style_ranges(&self, pos_type: PosType) -> Vec<(Range<usize>, StyleMeta)> {
+        if !self.has_styles() {
+            return Vec::new();
+        }
+
+        let mut ans = Vec::new();
+        for (range, styles) in self.style_ranges.as_ref().unwrap().iter() {
+            let start = self.entity_index_to_pos(range.start, pos_type);
+            let end = self.entity_index_to_pos(range.end, pos_type);
+            if start < end {
+                ans.push((start..end, styles.into()));
+            }
+        }
+
+        ans
+    }
+
+    fn entity_index_to_pos(&self, index: usize, pos_type: PosType) -> usize {
+        if index >= self.len_entity() {
+            return self.len(pos_type);
+        }
+
+        match self.tree.query::<EntityQuery>(&index) {
+            Some(result) => self.get_index_from_cursor(result.cursor, pos_type).unwrap(),
+            None => 0,
+        }
+    }
+
+    #[cfg(feature = "line-index")]
+    /// The number of lines, which is the number of `\n` plus one.
+    pub(crate) fn line_count(&self) -> usize {
+        self.tree.root_cache().line_breaks as usize + 1
+    }
+
+    #[cfg(feature = "line-index")]
+    /// Get the position where the line starts.
+    ///
+    /// Returns `None` if the line doesn't exist.
+    pub(crate) fn line_start(&self, line: usize, pos_type: PosType) -> Option<usize> {
+        if line >= self.line_count() {
+            return None;
+        }
+
+        if line == 0 {
+            return Some(0);
+        }
+
+        let result = self.tree.query::<LineStartQuery>(&line)?;
+        self.get_index_from_cursor(result.cursor, pos_type)
+    }
+
+    #[cfg(feature = "line-index")]
+    /// Get the line that contains `pos`.
+    ///
+    /// Returns `None` if `pos` is out of bound.
+    pub(crate) fn line_of(&self, pos: usize, pos_type: PosType) -> Option<usize> {
+        let len = self.len(pos_type);
+        if pos > len {
+            return None;
+        }
+
+        if pos == 0 {
+            return Some(0);
+        }
+
+        if pos == len {
+            return Some(self.line_count() - 1);
+        }
+
+        let result = match pos_type {
+            PosType::Bytes => self.tree.query::<ByteQuery>(&pos),
+            PosType::Unicode => self.tree.query::<UnicodeQuery>(&pos),
+            PosType::Utf16 => self.tree.query::<Utf16Query>(&pos),
+            PosType::Entity => self.tree.query::<EntityQuery>(&pos),
+            PosType::Event => self.tree.query::<EventIndexQuery>(&pos),
+        }?;
+
+        let mut line = 0;
+        self.tree
+            .visit_previous_caches(result.cursor, |cache| match cache {
+                generic_btree::PreviousCache::NodeCache(c) => {
+                    line += c.line_breaks as usize;
+                }
+                generic_btree::PreviousCache::PrevSiblingElem(c) => {
+                    if let RichtextStateChunk::Text(t) = c {
+                        line += t.line_breaks() as usize;
+                    }
+                }
+                generic_btree::PreviousCache::ThisElemAndOffset { elem, offset } => {
+                    if let RichtextStateChunk::Text(t) = elem {
+                        line += t
+                            .as_str()
+                            .chars()
+                            .take(offset)
+                            .filter(|&c| c == '\n')
+                            .count();
+                    }
+                }
+            });
+
+        Some(line)
+    }
 }
 
 fn entity_offset_to_pos_type_offset(
@@ -2549,11 +2943,13 @@ fn entity_offset_to_pos_type_offset(
         PosType::Bytes => match elem {
             RichtextStateChunk::Text(t) => unicode_to_utf8_index(t.as_str(), offset).unwrap(),
             RichtextStateChunk::Style { .. } => 0,
+            RichtextStateChunk::Embed { .. } => offset,
         },
         PosType::Unicode => offset,
         PosType::Utf16 => match elem {
             RichtextStateChunk::Text(t) => unicode_to_utf16_index(t.as_str(), offset).unwrap(),
             RichtextStateChunk::Style { .. } => 0,
+            RichtextStateChunk::Embed { .. } => offset,
         },
         PosType::Entity => offset,
         PosType::Event => match elem {
@@ -2565,6 +2961,7 @@ fn entity_offset_to_pos_type_offset(
                 }
             }
             RichtextStateChunk::Style { .. } => 0,
+            RichtextStateChunk::Embed { .. } => offset,
         },
     }
 }
@@ -2578,6 +2975,7 @@ fn pos_type_offset_to_entity_offset(
     match pos_type {
         PosType::Bytes => match elem {
             RichtextStateChunk::Text(t) => utf8_to_unicode_index(t.as_str(), offset).ok(),
+            RichtextStateChunk::Embed { .. } => (offset <= 1).then_some(offset),
             RichtextStateChunk::Style { .. } => {
                 if offset > 0 {
                     None
@@ -2589,6 +2987,7 @@ fn pos_type_offset_to_entity_offset(
         PosType::Unicode => Some(offset),
         PosType::Utf16 => match elem {
             RichtextStateChunk::Text(t) => utf16_to_unicode_index(t.as_str(), offset).ok(),
+            RichtextStateChunk::Embed { .. } => (offset <= 1).then_some(offset),
             RichtextStateChunk::Style { .. } => {
                 if offset > 0 {
                     None
@@ -2614,6 +3013,7 @@ fn pos_type_offset_to_entity_offset(
                     None
                 }
             }
+            RichtextStateChunk::Embed { .. } => (offset < 1).then_some(offset),
             RichtextStateChunk::Style { .. } => {
                 if offset > 0 {
                     None
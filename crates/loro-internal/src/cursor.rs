@@ -1,4 +1,7 @@
-use loro_common::{ContainerID, ID};
+use std::ops::Range;
+
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, Counter, PeerID, ID};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -16,6 +19,17 @@ pub struct Cursor {
     pub(crate) origin_pos: usize,
 }
 
+/// A stable range in a sequence container, such as a selection, a comment anchor or a highlight.
+///
+/// `start` is at the left of the first element of the range and `end` is at the right of the
+/// last one, so the content inserted at the boundaries is not included. A collapsed range
+/// has the same cursor at both ends.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RangeCursor {
+    pub start: Cursor,
+    pub end: Cursor,
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Side {
     Left = -1,
@@ -45,6 +59,21 @@ pub struct PosQueryResult {
     pub current: AbsolutePosition,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeQueryResult {
+    Range {
+        /// The current range
+        range: Range<usize>,
+        /// A range cursor that should replace the queried one.
+        ///
+        /// It's set when an end of the range is deleted, so that the next query doesn't need
+        /// to trace back the history.
+        update: Option<RangeCursor>,
+    },
+    /// All the elements of the range are deleted
+    Deleted,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AbsolutePosition {
     pub pos: usize,
@@ -80,3 +109,81 @@ impl Cursor {
         postcard::from_bytes(data)
     }
 }
+
+impl RangeCursor {
+    pub fn new(start: Cursor, end: Cursor) -> Self {
+        Self { start, end }
+    }
+
+    /// Build the range cursor of `range` from the cursors that `get_cursor` returns.
+    pub(crate) fn from_range(
+        range: Range<usize>,
+        get_cursor: impl Fn(usize, Side) -> Option<Cursor>,
+    ) -> Option<Self> {
+        if range.end < range.start {
+            return None;
+        }
+
+        let start = get_cursor(range.start, Side::Left)?;
+        if range.is_empty() {
+            return Some(Self::new(start.clone(), start));
+        }
+
+        let end = get_cursor(range.end - 1, Side::Right)?;
+        Some(Self::new(start, end))
+    }
+
+    /// Whether the range was empty when it was created.
+    pub fn is_collapsed(&self) -> bool {
+        self.start.id == self.end.id && self.start.side == self.end.side
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, postcard::Error> {
+        postcard::from_bytes(data)
+    }
+}
+
+/// The ids to find in a single pass over the elements of a container.
+///
+/// They are grouped by peer and sorted by counter, so each element span is matched
+/// with a binary search.
+pub(crate) struct IdLookup {
+    ids: FxHashMap<PeerID, Vec<(Counter, usize)>>,
+}
+
+impl IdLookup {
+    pub(crate) fn new(ids: &[ID]) -> Self {
+        let mut map: FxHashMap<PeerID, Vec<(Counter, usize)>> = FxHashMap::default();
+        for (slot, id) in ids.iter().enumerate() {
+            map.entry(id.peer).or_default().push((id.counter, slot));
+        }
+
+        for list in map.values_mut() {
+            list.sort_unstable();
+        }
+
+        Self { ids: map }
+    }
+
+    /// Call `f(slot, offset)` for each id inside the span, where `slot` is the index of the id
+    /// in the input and `offset` is the offset of the id in the span.
+    pub(crate) fn find_in(
+        &self,
+        peer: PeerID,
+        counter: Range<Counter>,
+        mut f: impl FnMut(usize, usize),
+    ) {
+        let Some(list) = self.ids.get(&peer) else {
+            return;
+        };
+
+        let start = list.partition_point(|(c, _)| *c < counter.start);
+        for &(c, slot) in list[start..].iter().take_while(|(c, _)| *c < counter.end) {
+            f(slot, (c - counter.start) as usize);
+        }
+    }
+}
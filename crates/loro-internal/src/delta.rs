@@ -6,6 +6,8 @@ mod map;
 pub use map::{MapDiff, ValuePair};
 mod map_delta;
 pub use map_delta::{MapDelta, MapValue, ResolvedMapDelta, ResolvedMapValue};
+mod quill;
+pub use quill::{QuillAttributes, QuillDelta, QuillValue};
 mod text;
 pub use text::{StyleMeta, StyleMetaItem};
 mod tree;
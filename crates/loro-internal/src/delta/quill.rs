@@ -0,0 +1,516 @@
+//! [Quill Delta](https://quilljs.com/docs/delta/) interop.
+//!
+//! A [QuillDelta] can be converted to and from the canonical Quill Delta JSON, i.e.
+//! `{ "ops": [...] }`. It supports embeds and `null` attributes, and can be composed and
+//! transformed like a Quill Delta.
+//!
+//! The lengths are [Event Index]es:
+//!
+//! - if feature="wasm", they are UTF-16 lengths, the same as Quill
+//! - if feature!="wasm", they are Unicode lengths
+use std::ops::{Deref, DerefMut};
+
+use fxhash::FxHashMap;
+use generic_btree::rle::{HasLength, Mergeable, Sliceable, TryInsert};
+use loro_common::LoroValue;
+use loro_delta::{
+    delta_trait::{DeltaAttr, DeltaValue},
+    DeltaItem, DeltaRope,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    container::richtext::richtext_state::{unicode_to_utf8_index, utf16_to_utf8_index},
+    utils::utf16::count_utf16_len,
+    value::ToJson,
+};
+
+/// The inserted content of a [QuillDelta]. An embed has the length of 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuillValue {
+    Text(String),
+    Embed(LoroValue),
+}
+
+/// The attributes of an op. A `null` value removes the attribute.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuillAttributes(FxHashMap<String, LoroValue>);
+
+/// A [Quill Delta](https://quilljs.com/docs/delta/) that can be composed and transformed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuillDelta(DeltaRope<QuillValue, QuillAttributes>);
+
+impl QuillDelta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, text: &str, attributes: QuillAttributes) -> &mut Self {
+        if !text.is_empty() {
+            self.0
+                .push_insert(QuillValue::Text(text.to_string()), attributes);
+        }
+        self
+    }
+
+    pub fn insert_embed(&mut self, value: LoroValue, attributes: QuillAttributes) -> &mut Self {
+        self.0.push_insert(QuillValue::Embed(value), attributes);
+        self
+    }
+
+    pub fn retain(&mut self, len: usize, attributes: QuillAttributes) -> &mut Self {
+        self.0.push_retain(len, attributes);
+        self
+    }
+
+    pub fn delete(&mut self, len: usize) -> &mut Self {
+        self.0.push_delete(len);
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DeltaItem<QuillValue, QuillAttributes>> {
+        self.0.iter()
+    }
+
+    /// The length of the document after applying this delta, if it's applied to a document
+    /// of [QuillDelta::base_len].
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The length of the document this delta can be applied to, excluding the trailing
+    /// part that is implicitly retained.
+    pub fn base_len(&self) -> usize {
+        self.0.old_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Compose `other` into `self`, so that applying `self` is the same as applying the old
+    /// `self` and then `other`.
+    pub fn compose(&mut self, other: &Self) {
+        self.0.compose(&other.0);
+        self.0.chop();
+        let has_null_on_insert = self.0.iter().any(|item| match item {
+            DeltaItem::Replace { value, attr, .. } => {
+                value.rle_len() > 0 && attr.values().any(|v| v.is_null())
+            }
+            DeltaItem::Retain { .. } => false,
+        });
+        if has_null_on_insert {
+            // `null` only means removal on retains. Inserts drop it, as Quill does.
+            let mut ans = DeltaRope::new();
+            for item in self.0.iter() {
+                match item.clone() {
+                    DeltaItem::Replace {
+                        value,
+                        mut attr,
+                        delete,
+                    } => {
+                        attr.retain(|_, v| !v.is_null());
+                        ans.push_replace(value, attr, delete);
+                    }
+                    item => {
+                        ans.push(item);
+                    }
+                }
+            }
+            self.0 = ans;
+        }
+    }
+
+    /// Transform `other` against `self`, which happened concurrently. It's the same as
+    /// Quill's `delta.transform(other, priority)`.
+    ///
+    /// If `priority` is true, `self` is considered to happen first: its inserts are placed
+    /// before the inserts of `other` at the same position, and its attributes win.
+    pub fn transform(&self, other: &Self, priority: bool) -> Self {
+        Self(other.0.transform(&self.0, !priority))
+    }
+
+    /// Transform the index against `self`. It's the same as Quill's
+    /// `delta.transformPosition(index, priority)`.
+    pub fn transform_position(&self, index: usize, priority: bool) -> usize {
+        self.0.transform_pos(index, !priority)
+    }
+}
+
+impl QuillAttributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_inner(self) -> FxHashMap<String, LoroValue> {
+        self.0
+    }
+}
+
+impl Deref for QuillAttributes {
+    type Target = FxHashMap<String, LoroValue>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for QuillAttributes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<FxHashMap<String, LoroValue>> for QuillAttributes {
+    fn from(map: FxHashMap<String, LoroValue>) -> Self {
+        Self(map)
+    }
+}
+
+impl<K: Into<String>, V: Into<LoroValue>> FromIterator<(K, V)> for QuillAttributes {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl DeltaAttr for QuillAttributes {
+    fn compose(&mut self, other: &Self) {
+        for (key, value) in other.0.iter() {
+            self.0.insert(key.clone(), value.clone());
+        }
+    }
+
+    fn attr_is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn transform(&mut self, other: &Self, left_prior: bool) {
+        if !left_prior {
+            self.0.retain(|key, _| !other.0.contains_key(key));
+        }
+    }
+}
+
+impl Default for QuillValue {
+    fn default() -> Self {
+        QuillValue::Text(String::new())
+    }
+}
+
+fn event_len(s: &str) -> usize {
+    if cfg!(feature = "wasm") {
+        count_utf16_len(s.as_bytes())
+    } else {
+        s.chars().count()
+    }
+}
+
+fn utf8_index(s: &str, event_index: usize) -> usize {
+    if cfg!(feature = "wasm") {
+        utf16_to_utf8_index(s, event_index).unwrap()
+    } else {
+        unicode_to_utf8_index(s, event_index).unwrap()
+    }
+}
+
+impl HasLength for QuillValue {
+    fn rle_len(&self) -> usize {
+        match self {
+            QuillValue::Text(s) => event_len(s),
+            QuillValue::Embed(_) => 1,
+        }
+    }
+}
+
+impl Mergeable for QuillValue {
+    fn can_merge(&self, rhs: &Self) -> bool {
+        matches!((self, rhs), (QuillValue::Text(_), QuillValue::Text(_)))
+    }
+
+    fn merge_right(&mut self, rhs: &Self) {
+        if let (QuillValue::Text(a), QuillValue::Text(b)) = (self, rhs) {
+            a.push_str(b);
+        }
+    }
+
+    fn merge_left(&mut self, left: &Self) {
+        if let (QuillValue::Text(a), QuillValue::Text(b)) = (self, left) {
+            a.insert_str(0, b);
+        }
+    }
+}
+
+impl Sliceable for QuillValue {
+    fn _slice(&self, range: std::ops::Range<usize>) -> Self {
+        match self {
+            QuillValue::Text(s) => QuillValue::Text(
+                s[utf8_index(s, range.start)..utf8_index(s, range.end)].to_string(),
+            ),
+            QuillValue::Embed(_) if range.is_empty() => Default::default(),
+            QuillValue::Embed(_) => self.clone(),
+        }
+    }
+
+    fn split(&mut self, pos: usize) -> Self {
+        match self {
+            QuillValue::Text(s) => {
+                let pos = utf8_index(s, pos);
+                QuillValue::Text(s.split_off(pos))
+            }
+            QuillValue::Embed(_) if pos == 0 => std::mem::take(self),
+            QuillValue::Embed(_) => Default::default(),
+        }
+    }
+}
+
+impl TryInsert for QuillValue {
+    fn try_insert(&mut self, pos: usize, elem: Self) -> Result<(), Self>
+    where
+        Self: Sized,
+    {
+        match (self, &elem) {
+            (QuillValue::Text(s), QuillValue::Text(e)) => {
+                let pos = utf8_index(s, pos);
+                s.insert_str(pos, e);
+                Ok(())
+            }
+            _ => Err(elem),
+        }
+    }
+}
+
+impl DeltaValue for QuillValue {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum QuillInsert {
+    Text(String),
+    Embed(LoroValue),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum QuillOp {
+    Insert {
+        insert: QuillInsert,
+        #[serde(default, skip_serializing_if = "FxHashMap::is_empty")]
+        attributes: FxHashMap<String, LoroValue>,
+    },
+    /// A string under `insert` is always text, so a container embed, whose JSON form is a
+    /// prefixed container id, is tagged with `embed` instead
+    Embed {
+        embed: LoroValue,
+        #[serde(default, skip_serializing_if = "FxHashMap::is_empty")]
+        attributes: FxHashMap<String, LoroValue>,
+    },
+    Retain {
+        retain: usize,
+        #[serde(default, skip_serializing_if = "FxHashMap::is_empty")]
+        attributes: FxHashMap<String, LoroValue>,
+    },
+    Delete {
+        delete: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum QuillOps {
+    Ops { ops: Vec<QuillOp> },
+    List(Vec<QuillOp>),
+}
+
+impl Serialize for QuillDelta {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut ops = Vec::new();
+        for item in self.0.iter() {
+            match item {
+                DeltaItem::Retain { len, attr } => ops.push(QuillOp::Retain {
+                    retain: *len,
+                    attributes: attr.0.clone(),
+                }),
+                DeltaItem::Replace {
+                    value,
+                    attr,
+                    delete,
+                } => {
+                    if value.rle_len() > 0 {
+                        let mut attributes = attr.0.clone();
+                        attributes.retain(|_, v| !v.is_null());
+                        ops.push(match value {
+                            QuillValue::Text(s) => QuillOp::Insert {
+                                insert: QuillInsert::Text(s.clone()),
+                                attributes,
+                            },
+                            QuillValue::Embed(v @ LoroValue::Container(_)) => QuillOp::Embed {
+                                embed: v.clone(),
+                                attributes,
+                            },
+                            QuillValue::Embed(v) => QuillOp::Insert {
+                                insert: QuillInsert::Embed(v.clone()),
+                                attributes,
+                            },
+                        });
+                    }
+                    if *delete > 0 {
+                        ops.push(QuillOp::Delete { delete: *delete });
+                    }
+                }
+            }
+        }
+
+        QuillOps::Ops { ops }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for QuillDelta {
+    /// Accepts both `{ "ops": [...] }` and a bare list of ops.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ops = match QuillOps::deserialize(deserializer)? {
+            QuillOps::Ops { ops } | QuillOps::List(ops) => ops,
+        };
+        let mut ans = QuillDelta::new();
+        for op in ops {
+            match op {
+                QuillOp::Insert {
+                    insert: QuillInsert::Text(s),
+                    attributes,
+                } => ans.insert(&s, attributes.into()),
+                QuillOp::Insert {
+                    insert: QuillInsert::Embed(v),
+                    attributes,
+                }
+                | QuillOp::Embed {
+                    embed: v,
+                    attributes,
+                } => ans.insert_embed(v, attributes.into()),
+                QuillOp::Retain { retain, attributes } => ans.retain(retain, attributes.into()),
+                QuillOp::Delete { delete } => ans.delete(delete),
+            };
+        }
+
+        Ok(ans)
+    }
+}
+
+impl ToJson for QuillDelta {
+    fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    fn from_json(s: &str) -> Self {
+        serde_json::from_str(s).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn delta(value: serde_json::Value) -> QuillDelta {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let value = json!({
+            "ops": [
+                { "insert": "Hello", "attributes": { "bold": true } },
+                { "insert": { "image": "https://loro.dev/logo.png" } },
+                { "retain": 3, "attributes": { "bold": null } },
+                { "delete": 2 },
+            ]
+        });
+        let d = delta(value.clone());
+        assert_eq!(d.len(), 9);
+        assert_eq!(d.base_len(), 5);
+        assert_eq!(d.to_json_value(), value);
+        assert_eq!(delta(value["ops"].clone()), d);
+    }
+
+    #[test]
+    fn only_tagged_strings_are_embeds() {
+        let text = delta(json!([{ "insert": "🦜:cid:root-map:Map" }]));
+        assert_eq!(text.len(), "🦜:cid:root-map:Map".chars().count());
+
+        let value = json!({ "ops": [{ "embed": "🦜:cid:root-map:Map" }] });
+        let embed = delta(value.clone());
+        assert_eq!(embed.len(), 1);
+        assert_eq!(embed.to_json_value(), value);
+    }
+
+    #[test]
+    fn compose_drops_null_on_insert() {
+        let mut a = delta(json!([
+            { "insert": "Hello", "attributes": { "bold": true } },
+            { "insert": { "image": "a.png" } },
+        ]));
+        a.compose(&delta(json!([
+            { "retain": 2, "attributes": { "bold": null, "italic": true } },
+            { "delete": 3 },
+            { "retain": 1, "attributes": { "alt": "logo" } },
+        ])));
+        assert_eq!(
+            a.to_json_value(),
+            json!({
+                "ops": [
+                    { "insert": "He", "attributes": { "italic": true } },
+                    { "insert": { "image": "a.png" }, "attributes": { "alt": "logo" } },
+                ]
+            })
+        );
+
+        let mut b = delta(json!([{ "retain": 2, "attributes": { "bold": true } }]));
+        b.compose(&delta(
+            json!([{ "retain": 1, "attributes": { "bold": null } }]),
+        ));
+        assert_eq!(
+            b.to_json_value(),
+            json!({
+                "ops": [
+                    { "retain": 1, "attributes": { "bold": null } },
+                    { "retain": 1, "attributes": { "bold": true } },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn transform_like_quill() {
+        let a = delta(json!([{ "insert": "A" }]));
+        let b = delta(json!([{ "insert": "B" }]));
+        assert_eq!(
+            a.transform(&b, true).to_json_value(),
+            json!({ "ops": [{ "retain": 1 }, { "insert": "B" }] })
+        );
+        assert_eq!(
+            a.transform(&b, false).to_json_value(),
+            json!({ "ops": [{ "insert": "B" }] })
+        );
+
+        let a = delta(json!([{ "retain": 2, "attributes": { "bold": true, "color": "red" } }]));
+        let b = delta(json!([{ "retain": 2, "attributes": { "bold": null, "italic": true } }]));
+        assert_eq!(
+            a.transform(&b, true).to_json_value(),
+            json!({ "ops": [{ "retain": 2, "attributes": { "italic": true } }] })
+        );
+        assert_eq!(a.transform(&b, false), b);
+
+        let insert = delta(json!([{ "retain": 2 }, { "insert": "abc" }]));
+        assert_eq!(insert.transform_position(2, true), 2);
+        assert_eq!(insert.transform_position(2, false), 5);
+        assert_eq!(insert.transform_position(3, true), 6);
+    }
+}
@@ -1,4 +1,3 @@
-
 use fxhash::FxHashMap;
 use loro_common::{InternalString, LoroValue, PeerID};
 use loro_delta::delta_trait::DeltaAttr;
@@ -109,7 +108,7 @@ impl StyleMeta {
         LoroValue::Map(self.to_map_without_null_value().into())
     }
 
-    fn to_map_without_null_value(&self) -> FxHashMap<String, LoroValue> {
+    pub(crate) fn to_map_without_null_value(&self) -> FxHashMap<String, LoroValue> {
         self.map
             .iter()
             .filter_map(|(key, value)| {
@@ -25,10 +25,22 @@ use std::ops::{Index, IndexMut};
 ///
 /// - `timeout_ms`: Optional timeout in milliseconds for the diff computation
 /// - `use_refined_diff`: Whether to use a more refined but slower diff algorithm. Defaults to true.
+/// - `granularity`: The unit that the texts are compared by. Defaults to grapheme clusters.
+///   It's ignored by `update_by_line`.
+/// - `ignore_whitespace`: Whether to treat the tokens that differ only in whitespace as equal.
+///   Their whitespace is still updated. Defaults to false.
+/// - `semantic_cleanup`: Whether to merge the edits separated by short equalities into larger
+///   edits, which are easier to read but may touch more characters. Defaults to false.
+///
+/// New options may be added, so build it from [`UpdateOptions::default`] with the setters.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct UpdateOptions {
     pub timeout_ms: Option<f64>,
     pub use_refined_diff: bool,
+    pub granularity: DiffGranularity,
+    pub ignore_whitespace: bool,
+    pub semantic_cleanup: bool,
 }
 
 impl Default for UpdateOptions {
@@ -36,10 +48,52 @@ impl Default for UpdateOptions {
         Self {
             timeout_ms: None,
             use_refined_diff: true,
+            granularity: DiffGranularity::Char,
+            ignore_whitespace: false,
+            semantic_cleanup: false,
         }
     }
 }
 
+impl UpdateOptions {
+    pub fn timeout_ms(mut self, timeout_ms: f64) -> Self {
+        self.timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    pub fn use_refined_diff(mut self, use_refined_diff: bool) -> Self {
+        self.use_refined_diff = use_refined_diff;
+        self
+    }
+
+    pub fn granularity(mut self, granularity: DiffGranularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    pub fn ignore_whitespace(mut self, ignore_whitespace: bool) -> Self {
+        self.ignore_whitespace = ignore_whitespace;
+        self
+    }
+
+    pub fn semantic_cleanup(mut self, semantic_cleanup: bool) -> Self {
+        self.semantic_cleanup = semantic_cleanup;
+        self
+    }
+}
+
+/// The unit that a text update compares the old and the new text by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffGranularity {
+    /// Grapheme clusters, so a user-perceived character is never split
+    #[default]
+    Char,
+    /// Words, whitespace runs and punctuation.
+    ///
+    /// The cursors inside the unchanged words stay where they are.
+    Word,
+}
+
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum UpdateTimeoutError {
     #[error("Timeout")]
@@ -109,8 +163,7 @@ impl<D: DiffHandler> OperateProxy<D> {
         self.handler.insert(old_index, new_index, new_len);
     }
 
-    #[allow(unused)]
-    fn unwrap(self) -> D {
+    pub fn unwrap(self) -> D {
         self.handler
     }
 }
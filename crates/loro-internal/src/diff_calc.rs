@@ -850,11 +850,23 @@ impl DiffCalculatorTrait for RichtextDiffCalculator {
                 last_style_start,
             } => match &op.raw_op().content {
                 crate::op::InnerContent::List(l) => match l {
-                    InnerListOp::Insert { .. }
-                    | InnerListOp::Move { .. }
-                    | InnerListOp::Set { .. } => {
+                    InnerListOp::Move { .. } | InnerListOp::Set { .. } => {
                         unreachable!()
                     }
+                    InnerListOp::Insert { slice, pos } => {
+                        for (i, value) in oplog
+                            .arena
+                            .get_values(slice.to_range())
+                            .into_iter()
+                            .enumerate()
+                        {
+                            diff.insert_value(
+                                *pos + i,
+                                RichtextStateChunk::new_embed(value, op.id_full().inc(i as i32)),
+                                (),
+                            );
+                        }
+                    }
                     InnerListOp::InsertText {
                         slice: _,
                         unicode_start,
@@ -949,11 +961,17 @@ impl DiffCalculatorTrait for RichtextDiffCalculator {
                 }
                 match &op.raw_op().content {
                     crate::op::InnerContent::List(l) => match l {
-                        InnerListOp::Insert { .. }
-                        | InnerListOp::Move { .. }
-                        | InnerListOp::Set { .. } => {
+                        InnerListOp::Move { .. } | InnerListOp::Set { .. } => {
                             unreachable!()
                         }
+                        InnerListOp::Insert { slice, pos } => {
+                            // The values of embeds are looked up in the oplog when calculating the diff
+                            tracker.insert(
+                                op.id_full(),
+                                *pos,
+                                RichtextChunk::new_unknown(slice.atom_len() as u32),
+                            );
+                        }
                         InnerListOp::InsertText {
                             slice: _,
                             unicode_start,
@@ -1071,13 +1089,23 @@ impl DiffCalculatorTrait for RichtextDiffCalculator {
         idx: ContainerIdx,
         oplog: &OpLog,
         info: DiffCalcVersionInfo,
-        _: impl FnMut(&ContainerID),
+        mut on_new_container: impl FnMut(&ContainerID),
     ) -> (InternalDiff, DiffMode) {
         match &mut *self.mode {
-            RichtextCalcMode::Linear { diff, .. } => (
-                InternalDiff::RichtextRaw(std::mem::take(diff)),
-                DiffMode::Linear,
-            ),
+            RichtextCalcMode::Linear { diff, .. } => {
+                for item in diff.iter() {
+                    if let loro_delta::DeltaItem::Replace { value, .. } = item {
+                        if let Some(LoroValue::Container(c)) = value.as_embed() {
+                            on_new_container(c);
+                        }
+                    }
+                }
+
+                (
+                    InternalDiff::RichtextRaw(std::mem::take(diff)),
+                    DiffMode::Linear,
+                )
+            }
             RichtextCalcMode::Crdt {
                 tracker, styles, ..
             } => {
@@ -1157,6 +1185,30 @@ impl DiffCalculatorTrait for RichtextDiffCalculator {
                                                     (),
                                                 );
                                             }
+                                            InnerListOp::Insert { slice, .. } => {
+                                                for (i, value) in oplog
+                                                    .arena
+                                                    .get_values(slice.to_range())
+                                                    .into_iter()
+                                                    .enumerate()
+                                                {
+                                                    if let LoroValue::Container(c) = &value {
+                                                        on_new_container(c);
+                                                    }
+
+                                                    delta.push_insert(
+                                                        RichtextStateChunk::new_embed(
+                                                            value,
+                                                            IdFull::new(
+                                                                id.peer,
+                                                                op.counter + i as Counter,
+                                                                lamport + i as Lamport,
+                                                            ),
+                                                        ),
+                                                        (),
+                                                    );
+                                                }
+                                            }
                                             _ => unreachable!("{:?}", content),
                                         }
                                     }
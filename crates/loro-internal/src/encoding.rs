@@ -2,7 +2,7 @@ pub(crate) mod arena;
 pub(crate) mod fast_snapshot;
 pub(crate) mod json_schema;
 mod outdated_encode_reordered;
-mod shallow_snapshot;
+pub(crate) mod shallow_snapshot;
 pub(crate) mod value;
 pub(crate) mod value_register;
 pub(crate) use outdated_encode_reordered::{
@@ -11,6 +11,7 @@ pub(crate) use outdated_encode_reordered::{
 use outdated_encode_reordered::{import_changes_to_oplog, ImportChangesResult};
 pub(crate) use value::OwnedValue;
 
+use crate::change::{get_sys_timestamp, Timestamp};
 use crate::op::OpWithId;
 use crate::version::{Frontiers, VersionRange};
 use crate::LoroDoc;
@@ -18,7 +19,7 @@ use crate::{oplog::OpLog, LoroError, VersionVector};
 use loro_common::{HasIdSpan, IdLpSpan, IdSpan, LoroEncodeError, LoroResult, PeerID, ID};
 use num_traits::{FromPrimitive, ToPrimitive};
 use rle::{HasLength, Sliceable};
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
 /// The mode of the export.
 ///
@@ -69,6 +70,22 @@ pub enum ExportMode<'a> {
     /// The snapshot at the specified frontiers. It contains the full history
     /// till the target frontiers and the state at the target frontiers.
     SnapshotAt { version: Cow<'a, Frontiers> },
+    /// It contains the full history and the current state of the document, but the
+    /// history is rewritten to be more compact.
+    ///
+    /// The consecutive changes of the same peer are merged into one change if they
+    /// have the same commit message and metadata and the timestamp of the later one
+    /// is within `merge_interval` seconds of the first one. The merged change keeps
+    /// the timestamp of the first change. All the op ids are kept, so the cursors and
+    /// version vectors stay valid.
+    SquashedSnapshot { merge_interval: i64 },
+    /// The shallow snapshot that only contains the history within the last
+    /// `older_than` duration.
+    ///
+    /// The start version is computed from the timestamps of the changes, like
+    /// [`crate::LoroDoc::frontiers_at_timestamp`], so the cut is always causally consistent.
+    /// Changes recorded without a timestamp are treated as old history.
+    ShallowSince { older_than: Duration },
 }
 
 impl<'a> ExportMode<'a> {
@@ -138,6 +155,17 @@ impl<'a> ExportMode<'a> {
         }
     }
 
+    /// It contains the full history and the current state of the document, where the
+    /// consecutive changes of the same peer within `merge_interval` seconds are merged.
+    pub fn squashed_snapshot(merge_interval: i64) -> Self {
+        ExportMode::SquashedSnapshot { merge_interval }
+    }
+
+    /// The shallow snapshot that only contains the history within the last `older_than` duration.
+    pub fn shallow_since(older_than: Duration) -> Self {
+        ExportMode::ShallowSince { older_than }
+    }
+
     /// This mode exports the history within the specified version vector.
     pub fn updates_till(vv: &VersionVector) -> ExportMode<'static> {
         let mut spans = Vec::with_capacity(vv.len());
@@ -436,6 +464,14 @@ pub(crate) fn export_fast_snapshot(doc: &LoroDoc) -> Vec<u8> {
     .unwrap()
 }
 
+pub(crate) fn export_squashed_snapshot(doc: &LoroDoc, merge_interval: i64) -> Vec<u8> {
+    encode_with(EncodeMode::FastSnapshot, &mut |ans| {
+        fast_snapshot::encode_squashed_snapshot(doc, merge_interval, ans);
+        Ok(())
+    })
+    .unwrap()
+}
+
 pub(crate) fn export_snapshot_at(
     doc: &LoroDoc,
     frontiers: &Frontiers,
@@ -473,6 +509,17 @@ pub(crate) fn export_shallow_snapshot(
     })
 }
 
+pub(crate) fn export_shallow_snapshot_since(
+    doc: &LoroDoc,
+    older_than: Duration,
+) -> Result<Vec<u8>, LoroEncodeError> {
+    let now = (get_sys_timestamp() as Timestamp + 500) / 1000;
+    let cut =
+        now.saturating_sub(Timestamp::try_from(older_than.as_secs()).unwrap_or(Timestamp::MAX));
+    let f = doc.oplog().try_lock().unwrap().frontiers_at_timestamp(cut);
+    export_shallow_snapshot(doc, &f)
+}
+
 fn check_target_version_reachable(doc: &LoroDoc, f: &Frontiers) -> Result<(), LoroEncodeError> {
     let oplog = doc.oplog.try_lock().unwrap();
     if !oplog.dag.can_export_shallow_snapshot_on(f) {
@@ -184,6 +184,24 @@ pub(crate) fn encode_snapshot<W: std::io::Write>(doc: &LoroDoc, w: &mut W) {
     _encode_snapshot(snapshot, w);
 }
 
+/// Encode a snapshot whose history is squashed.
+///
+/// The consecutive changes of the same peer within `merge_interval` seconds are merged
+/// into one change. The state and the op ids are the same as the normal snapshot.
+pub(crate) fn encode_squashed_snapshot<W: std::io::Write>(
+    doc: &LoroDoc,
+    merge_interval: i64,
+    w: &mut W,
+) {
+    let mut snapshot = encode_snapshot_inner(doc);
+    snapshot.oplog_bytes = doc
+        .oplog()
+        .try_lock()
+        .unwrap()
+        .export_squashed_change_store(merge_interval);
+    _encode_snapshot(snapshot, w);
+}
+
 pub(crate) fn encode_snapshot_inner(doc: &LoroDoc) -> Snapshot {
     assert!(doc.drop_pending_events().is_empty());
     let old_state_frontiers = doc.state_frontiers();
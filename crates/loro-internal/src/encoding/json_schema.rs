@@ -17,13 +17,17 @@ use crate::{
     OpLog, VersionVector,
 };
 use either::Either;
+use itertools::Itertools;
 use json::{JsonOpContent, JsonSchema};
 use loro_common::{
-    ContainerID, ContainerType, HasCounterSpan, HasIdSpan, IdLp, LoroError, LoroResult, LoroValue,
-    PeerID, TreeID, ID,
+    ContainerID, ContainerType, HasCounterSpan, HasIdSpan, IdLp, IdSpan, Lamport, LoroError,
+    LoroResult, LoroValue, PeerID, TreeID, ID,
+};
+use rle::{RleVec, Sliceable};
+use std::{
+    io::{self, BufRead, Write},
+    sync::Arc,
 };
-use rle::{HasLength, RleVec, Sliceable};
-use std::sync::Arc;
 
 const SCHEMA_VERSION: u8 = 1;
 
@@ -54,7 +58,7 @@ pub(crate) fn export_json<'a, 'c: 'a>(
     let frontiers = oplog.dag.vv_to_frontiers(&actual_start_vv);
 
     let mut peer_register = ValueRegister::<PeerID>::new();
-    let diff_changes = init_encode(oplog, &actual_start_vv, &actual_end_vv);
+    let diff_changes: Vec<_> = init_encode(oplog, &actual_start_vv, &actual_end_vv).collect();
     let changes = encode_changes(&diff_changes, &oplog.arena, &mut peer_register);
     JsonSchema {
         changes,
@@ -64,8 +68,111 @@ pub(crate) fn export_json<'a, 'c: 'a>(
     }
 }
 
+/// Export the json updates as NDJSON, one [`json::JsonUpdateLine`] per line.
+///
+/// The changes are streamed in causal order and written one by one, so the encoded updates are
+/// never held in memory as a whole. The peers are written right before the first change that
+/// refers to them.
+pub(crate) fn export_json_ndjson<W: Write>(
+    oplog: &OpLog,
+    start_vv: &VersionVector,
+    end_vv: &VersionVector,
+    mut writer: W,
+) -> io::Result<()> {
+    let actual_start_vv = refine_vv(start_vv, oplog);
+    let actual_end_vv = refine_vv(end_vv, oplog);
+
+    write_ndjson_line(
+        &mut writer,
+        &json::JsonUpdateLine::Header {
+            schema_version: SCHEMA_VERSION,
+            start_version: oplog.dag.vv_to_frontiers(&actual_start_vv),
+        },
+    )?;
+    let mut peer_register = ValueRegister::<PeerID>::new();
+    for change in init_encode(oplog, &actual_start_vv, &actual_end_vv) {
+        let registered = peer_register.vec().len();
+        let mut changes = encode_changes(
+            std::slice::from_ref(&change),
+            &oplog.arena,
+            &mut peer_register,
+        );
+        if peer_register.vec().len() > registered {
+            write_ndjson_line(
+                &mut writer,
+                &json::JsonUpdateLine::Peers {
+                    peers: peer_register.vec()[registered..].to_vec(),
+                },
+            )?;
+        }
+
+        write_ndjson_line(
+            &mut writer,
+            &json::JsonUpdateLine::Change(changes.pop().unwrap()),
+        )?;
+    }
+
+    writer.flush()
+}
+
+fn write_ndjson_line<W: Write>(writer: &mut W, line: &json::JsonUpdateLine) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, line)?;
+    writer.write_all(b"\n")
+}
+
+/// Read the NDJSON json updates exported by [`export_json_ndjson`] batch by batch.
+pub(crate) struct JsonUpdatesReader<R> {
+    lines: io::Lines<R>,
+    peers: Vec<PeerID>,
+}
+
+impl<R: BufRead> JsonUpdatesReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            peers: Vec::new(),
+        }
+    }
+
+    /// The peer table of the lines read so far
+    pub(crate) fn peers(&self) -> &[PeerID] {
+        &self.peers
+    }
+
+    /// Read at most `max` changes. Return an empty vec when the input is exhausted.
+    pub(crate) fn next_batch(&mut self, max: usize) -> LoroResult<Vec<json::JsonChange>> {
+        let mut changes = Vec::new();
+        while changes.len() < max {
+            let Some(line) = self.lines.next() else {
+                break;
+            };
+            let line = line.map_err(|e| LoroError::DecodeError(e.to_string().into_boxed_str()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line).map_err(|_| LoroError::InvalidJsonSchema)? {
+                json::JsonUpdateLine::Header { .. } => {}
+                json::JsonUpdateLine::Peers { peers } => self.peers.extend(peers),
+                json::JsonUpdateLine::Change(change) => changes.push(change),
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
 pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<ImportStatus> {
-    let changes = decode_changes(json, &oplog.arena)?;
+    let JsonSchema { peers, changes, .. } = json;
+    import_json_changes(oplog, changes, &peers)
+}
+
+pub(crate) fn import_json_changes(
+    oplog: &mut OpLog,
+    changes: Vec<json::JsonChange>,
+    peers: &[PeerID],
+) -> LoroResult<ImportStatus> {
+    let changes = decode_changes(changes, peers, &oplog.arena)?;
     let ImportChangesResult {
         latest_ids,
         pending_changes,
@@ -91,37 +198,49 @@ pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<Imp
     })
 }
 
-fn init_encode<'s, 'a: 's>(
+/// Iterate the changes between the versions in causal order.
+///
+/// The changes of each peer are sliced to the range and merged lazily by lamport, so the
+/// changes are never collected or sorted as a whole.
+fn init_encode<'a>(
     oplog: &'a OpLog,
     start_vv: &VersionVector,
     end_vv: &VersionVector,
-) -> Vec<Either<BlockChangeRef, Change>> {
-    let mut diff_changes: Vec<Either<BlockChangeRef, Change>> = Vec::new();
-    for change in oplog.iter_changes_peer_by_peer(start_vv, end_vv) {
-        let start_cnt = start_vv.get(&change.id.peer).copied().unwrap_or(0);
-        let end_cnt = end_vv.get(&change.id.peer).copied().unwrap_or(0);
-        if change.id.counter < start_cnt {
-            if change.ctr_end() <= start_cnt {
-                continue;
-            }
+) -> impl Iterator<Item = Either<BlockChangeRef, Change>> + 'a {
+    let spans: Vec<IdSpan> = start_vv.diff_iter(end_vv).1.collect();
+    spans
+        .into_iter()
+        .map(move |span| {
+            oplog
+                .change_store()
+                .iter_changes(span)
+                .filter_map(move |change| slice_change(change, span))
+        })
+        .kmerge_by(|a, b| change_lamport(a) < change_lamport(b))
+}
 
-            let offset = start_cnt - change.id.counter;
-            let to = change
-                .atom_len()
-                .min((end_cnt - change.id.counter) as usize);
-            diff_changes.push(Either::Right(change.slice(offset as usize, to)));
-        } else if change.id.counter + change.atom_len() as i32 > end_cnt {
-            let len = end_cnt - change.id.counter;
-            diff_changes.push(Either::Right(change.slice(0, len as usize)));
-        } else {
-            diff_changes.push(Either::Left(change));
-        }
+fn slice_change(change: BlockChangeRef, span: IdSpan) -> Option<Either<BlockChangeRef, Change>> {
+    let start = span.counter.min().max(change.id.counter);
+    let end = span.counter.norm_end().min(change.ctr_end());
+    if start >= end {
+        return None;
+    }
+
+    if start == change.id.counter && end == change.ctr_end() {
+        Some(Either::Left(change))
+    } else {
+        Some(Either::Right(change.slice(
+            (start - change.id.counter) as usize,
+            (end - change.id.counter) as usize,
+        )))
     }
-    diff_changes.sort_by_key(|x| match x {
+}
+
+fn change_lamport(change: &Either<BlockChangeRef, Change>) -> Lamport {
+    match change {
         Either::Left(c) => c.lamport,
         Either::Right(c) => c.lamport,
-    });
-    diff_changes
+    }
 }
 
 fn register_id(id: &ID, peer_register: &mut ValueRegister<PeerID>) -> ID {
@@ -316,6 +435,21 @@ fn encode_changes(
                             let text = String::from_utf8(slice.as_bytes().to_vec()).unwrap();
                             json::TextOp::Insert { pos: *pos, text }
                         }
+                        InnerListOp::Insert { slice, pos } => {
+                            let mut values =
+                                arena.get_values(slice.0.start as usize..slice.0.end as usize);
+                            values.iter_mut().for_each(|x| {
+                                if let LoroValue::Container(id) = x {
+                                    if id.is_normal() {
+                                        *id = register_container_id(id.clone(), peer_register);
+                                    }
+                                }
+                            });
+                            json::TextOp::InsertEmbed {
+                                pos: *pos as u32,
+                                value: values,
+                            }
+                        }
                         InnerListOp::Delete(DeleteSpanWithId {
                             id_start,
                             span: DeleteSpan { pos, signed_len },
@@ -440,6 +574,7 @@ fn encode_changes(
             lamport: change.lamport,
             timestamp: change.timestamp,
             msg: change.message().map(|x| x.to_string()),
+            metadata: change.metadata().map(|x| (**x).clone()),
         };
 
         changes.push(c);
@@ -447,8 +582,11 @@ fn encode_changes(
     changes
 }
 
-fn decode_changes(json: JsonSchema, arena: &SharedArena) -> LoroResult<Vec<Change>> {
-    let JsonSchema { peers, changes, .. } = json;
+fn decode_changes(
+    changes: Vec<json::JsonChange>,
+    peers: &[PeerID],
+    arena: &SharedArena,
+) -> LoroResult<Vec<Change>> {
     let mut ans = Vec::with_capacity(changes.len());
     for json::JsonChange {
         id,
@@ -456,22 +594,24 @@ fn decode_changes(json: JsonSchema, arena: &SharedArena) -> LoroResult<Vec<Chang
         deps,
         lamport,
         msg,
+        metadata,
         ops: json_ops,
     } in changes
     {
-        let id = convert_id(&id, &peers);
+        let id = convert_id(&id, peers);
         let mut ops: RleVec<[Op; 1]> = RleVec::new();
         for op in json_ops {
-            ops.push(decode_op(op, arena, &peers)?);
+            ops.push(decode_op(op, arena, peers)?);
         }
 
         let change = Change {
             id,
             timestamp,
-            deps: Frontiers::from_iter(deps.into_iter().map(|id| convert_id(&id, &peers))),
+            deps: Frontiers::from_iter(deps.into_iter().map(|id| convert_id(&id, peers))),
             lamport,
             ops,
             commit_msg: msg.map(|x| x.into()),
+            commit_metadata: metadata.map(|x| x.into()),
         };
         ans.push(change);
     }
@@ -498,6 +638,23 @@ fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &[PeerID]) -> LoroRes
                         pos,
                     })
                 }
+                json::TextOp::InsertEmbed {
+                    pos,
+                    value: mut values,
+                } => {
+                    values.iter_mut().for_each(|v| {
+                        if let LoroValue::Container(id) = v {
+                            if id.is_normal() {
+                                *id = convert_container_id(id.clone(), peers);
+                            }
+                        }
+                    });
+                    let range = arena.alloc_values(values.iter().cloned());
+                    InnerContent::List(InnerListOp::Insert {
+                        slice: SliceRange::new(range.start as u32..range.end as u32),
+                        pos: pos as usize,
+                    })
+                }
                 json::TextOp::Delete {
                     pos,
                     len,
@@ -720,6 +877,7 @@ pub mod json {
         version::{Frontiers, VersionRange},
     };
     use fractional_index::FractionalIndex;
+    use fxhash::FxHashMap;
     use loro_common::{ContainerID, Counter, IdLp, Lamport, LoroValue, PeerID, TreeID, ID};
     use serde::{Deserialize, Serialize};
     use std::ops::Range;
@@ -736,6 +894,25 @@ pub mod json {
         pub changes: Vec<JsonChange>,
     }
 
+    /// A line of the NDJSON encoding of the json updates.
+    ///
+    /// The stream starts with a header. The peer indexes in a change refer to the peer table,
+    /// which is the concatenation of all the `peers` lines before it.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum JsonUpdateLine {
+        Header {
+            schema_version: u8,
+            #[serde(with = "self::serde_impl::frontiers")]
+            start_version: Frontiers,
+        },
+        Peers {
+            #[serde(with = "self::serde_impl::peer_id")]
+            peers: Vec<PeerID>,
+        },
+        Change(JsonChange),
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct JsonChange {
         #[serde(with = "self::serde_impl::id")]
@@ -745,6 +922,9 @@ pub mod json {
         pub deps: Vec<ID>,
         pub lamport: Lamport,
         pub msg: Option<String>,
+        /// The user defined metadata of the change
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub metadata: Option<FxHashMap<String, LoroValue>>,
         pub ops: Vec<JsonOp>,
     }
 
@@ -869,6 +1049,11 @@ pub mod json {
             pos: u32,
             text: String,
         },
+        /// Insert inline embeds. Each value takes one position in the text.
+        InsertEmbed {
+            pos: u32,
+            value: Vec<LoroValue>,
+        },
         Delete {
             pos: i32,
             len: i32,
@@ -889,6 +1074,7 @@ pub mod json {
         fn op_len(&self) -> usize {
             match self {
                 TextOp::Insert { text, .. } => text.chars().count(),
+                TextOp::InsertEmbed { value, .. } => value.len(),
                 TextOp::Delete { len, .. } => len.unsigned_abs() as usize,
                 TextOp::Mark { .. } => 1,
                 TextOp::MarkEnd => 1,
@@ -1348,6 +1534,11 @@ pub mod json {
                         }
                         *text = chars.into_iter().collect();
                     }
+                    TextOp::InsertEmbed { value: values, .. } => {
+                        for i in range {
+                            redact_value(&mut values[i as usize]);
+                        }
+                    }
                     TextOp::Delete { .. } => {
                         // Delete op won't be changed
                     }
@@ -1378,7 +1569,7 @@ pub mod json {
     }
 }
 
-fn redact_value(v: &mut LoroValue) {
+pub(crate) fn redact_value(v: &mut LoroValue) {
     match v {
         LoroValue::Container(_) => {}
         _ => *v = LoroValue::Null,
@@ -1387,6 +1578,7 @@ fn redact_value(v: &mut LoroValue) {
 
 #[cfg(test)]
 mod tests {
+    use super::json;
     use crate::{LoroDoc, VersionVector};
 
     #[test]
@@ -1408,4 +1600,52 @@ mod tests {
         );
         assert_eq!(json.changes[0].ops.len(), 2);
     }
+
+    #[test]
+    fn json_ndjson_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        doc.get_text("text").insert(0, "hello").unwrap();
+        doc.commit_then_renew();
+        let other = LoroDoc::new_auto_commit();
+        other.set_peer_id(2).unwrap();
+        other.import(&doc.export_snapshot().unwrap()).unwrap();
+        other.get_list("list").insert(0, "a").unwrap();
+        other.get_text("text").insert(5, " world").unwrap();
+        other.commit_then_renew();
+        doc.import(&other.export_snapshot().unwrap()).unwrap();
+
+        let mut ndjson = Vec::new();
+        doc.export_json_updates_ndjson(&Default::default(), &doc.oplog_vv(), &mut ndjson)
+            .unwrap();
+        let text = String::from_utf8(ndjson.clone()).unwrap();
+        let lines: Vec<json::JsonUpdateLine> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert!(matches!(lines[0], json::JsonUpdateLine::Header { .. }));
+        let peer_lines = lines
+            .iter()
+            .filter(|l| matches!(l, json::JsonUpdateLine::Peers { .. }))
+            .count();
+        assert_eq!(peer_lines, 2);
+        // The changes of the peers are merged in causal order
+        let lamports: Vec<_> = lines
+            .iter()
+            .filter_map(|l| match l {
+                json::JsonUpdateLine::Change(c) => Some(c.lamport),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lamports.len(), 2);
+        assert!(lamports.windows(2).all(|w| w[0] <= w[1]));
+
+        let new_doc = LoroDoc::new_auto_commit();
+        let status = new_doc
+            .import_json_updates_ndjson(ndjson.as_slice())
+            .unwrap();
+        assert!(status.pending.is_none());
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+        assert_eq!(new_doc.oplog_vv(), doc.oplog_vv());
+    }
 }
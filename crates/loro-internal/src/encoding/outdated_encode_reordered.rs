@@ -318,6 +318,7 @@ fn decode_changes<'a>(
                 let s = key.to_string();
                 Some(Arc::from(s))
             },
+            commit_metadata: None,
             timestamp,
         };
 
@@ -1224,7 +1225,7 @@ mod encode {
                 crate::container::list::list_op::InnerListOp::Insert { slice, .. } => {
                     assert!(matches!(
                         op.container.get_type(),
-                        ContainerType::List | ContainerType::MovableList
+                        ContainerType::List | ContainerType::MovableList | ContainerType::Text
                     ));
                     let value = arena.get_values(slice.0.start as usize..slice.0.end as usize);
                     Value::LoroValue(value.into())
@@ -1354,6 +1355,16 @@ pub(crate) fn decode_op(
             Value::Null => crate::op::InnerContent::List(
                 crate::container::list::list_op::InnerListOp::StyleEnd,
             ),
+            Value::LoroValue(arr) => {
+                // Embeds
+                let range = shared_arena.alloc_values(arr.into_list().unwrap().iter().cloned());
+                crate::op::InnerContent::List(
+                    crate::container::list::list_op::InnerListOp::Insert {
+                        slice: SliceRange::new(range.start as u32..range.end as u32),
+                        pos: prop as usize,
+                    },
+                )
+            }
             _ => unreachable!(),
         },
         ContainerType::Map => {
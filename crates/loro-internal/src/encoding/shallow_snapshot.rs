@@ -10,7 +10,7 @@ use crate::{
     dag::{Dag, DagUtils},
     encoding::fast_snapshot::{Snapshot, _encode_snapshot},
     state::container_store::FRONTIERS_KEY,
-    version::Frontiers,
+    version::{Frontiers, VersionVector},
     LoroDoc,
 };
 
@@ -35,12 +35,7 @@ pub(crate) fn export_shallow_snapshot_inner(
     start_from: &Frontiers,
 ) -> Result<(Snapshot, Frontiers), LoroEncodeError> {
     let oplog = doc.oplog().try_lock().unwrap();
-    let start_from = calc_shallow_doc_start(&oplog, start_from);
-    let mut start_vv = oplog.dag().frontiers_to_vv(&start_from).unwrap();
-    for id in start_from.iter() {
-        // we need to include the ops in start_from, this can make things easier
-        start_vv.insert(id.peer, id.counter);
-    }
+    let (start_from, start_vv) = calc_shallow_doc_start_version(&oplog, start_from);
 
     #[cfg(debug_assertions)]
     {
@@ -143,12 +138,7 @@ pub(crate) fn export_state_only_snapshot<W: std::io::Write>(
     w: &mut W,
 ) -> Result<Frontiers, LoroEncodeError> {
     let oplog = doc.oplog().try_lock().unwrap();
-    let start_from = calc_shallow_doc_start(&oplog, start_from);
-    let mut start_vv = oplog.dag().frontiers_to_vv(&start_from).unwrap();
-    for id in start_from.iter() {
-        // we need to include the ops in start_from, this can make things easier
-        start_vv.insert(id.peer, id.counter);
-    }
+    let (start_from, start_vv) = calc_shallow_doc_start_version(&oplog, start_from);
 
     debug!(
         "start version vv={:?} frontiers={:?}",
@@ -207,6 +197,24 @@ fn cids_to_bytes(
     alive_c_bytes
 }
 
+/// Calculate the real start frontiers of the shallow snapshot exported from `frontiers`,
+/// and the version vector of the history before it.
+///
+/// The ops in the start frontiers are not included in the version vector.
+pub(crate) fn calc_shallow_doc_start_version(
+    oplog: &crate::OpLog,
+    frontiers: &Frontiers,
+) -> (Frontiers, VersionVector) {
+    let start_from = calc_shallow_doc_start(oplog, frontiers);
+    let mut start_vv = oplog.dag().frontiers_to_vv(&start_from).unwrap();
+    for id in start_from.iter() {
+        // we need to include the ops in start_from, this can make things easier
+        start_vv.insert(id.peer, id.counter);
+    }
+
+    (start_from, start_vv)
+}
+
 /// Calculates optimal starting version for the shallow doc
 ///
 /// It should be the LCA of the user given version and the latest version.
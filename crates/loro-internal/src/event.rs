@@ -23,6 +23,8 @@ use loro_common::{ContainerID, TreeID};
 
 use crate::{container::idx::ContainerIdx, version::Frontiers};
 
+pub mod encoded;
+
 #[derive(Debug, Clone)]
 pub struct ContainerDiff {
     pub id: ContainerID,
@@ -33,7 +35,7 @@ pub struct ContainerDiff {
 }
 
 /// The kind of the event trigger.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EventTriggerKind {
     /// The event is triggered by a local transaction.
     Local,
@@ -0,0 +1,675 @@
+//! Stable JSON and binary encodings of [`DiffBatch`] and [`DiffEvent`].
+//!
+//! The child containers in the diffs are encoded as [`LoroValue::Container`], so a decoded
+//! [`DiffBatch`] can be applied to another doc with [`LoroDoc::apply_diff`].
+//!
+//! The text and list lengths are in the same unit as the diffs they are encoded from.
+//!
+//! The binary encoding is postcard prefixed with a format version byte.
+use std::sync::Arc;
+
+use fractional_index::FractionalIndex;
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, IdLp, LoroError, LoroResult, LoroValue, TreeID, ID};
+use loro_delta::{array_vec::ArrayVec, DeltaItem, DeltaRope};
+use serde::{Deserialize, Serialize};
+
+use super::{ContainerDiff, Diff, DiffEvent, DocDiff, EventTriggerKind, Index, ListDeltaMeta};
+use crate::{
+    delta::{
+        ResolvedMapDelta, ResolvedMapValue, StyleMeta, StyleMetaItem, TreeDiff, TreeDiffItem,
+        TreeExternalDiff,
+    },
+    handler::{Handler, ValueOrHandler},
+    state::TreeParentId,
+    undo::DiffBatch,
+    utils::string_slice::StringSlice,
+    LoroDoc,
+};
+
+/// The version of the binary encoding, stored as its first byte
+const FORMAT_VERSION: u8 = 0;
+
+fn to_versioned_bytes<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut ans = vec![FORMAT_VERSION];
+    postcard::to_io(value, &mut ans).unwrap();
+    ans
+}
+
+fn from_versioned_bytes<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> LoroResult<T> {
+    match bytes.split_first() {
+        Some((&FORMAT_VERSION, rest)) => {
+            postcard::from_bytes(rest).map_err(|e| LoroError::DecodeError(e.to_string().into()))
+        }
+        Some((version, _)) => Err(LoroError::DecodeError(
+            format!("unsupported diff encoding version {}", version).into(),
+        )),
+        None => Err(LoroError::DecodeError("empty diff encoding".into())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedDiffBatch {
+    /// The diffs are sorted by the container ids, so the encoding is deterministic
+    pub diffs: Vec<EncodedContainerDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedContainerDiff {
+    pub id: String,
+    pub diff: EncodedDiff,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodedDiff {
+    List(Vec<EncodedListItem>),
+    Text(Vec<EncodedTextItem>),
+    Map(Vec<EncodedMapValue>),
+    Tree(Vec<EncodedTreeItem>),
+    Counter(f64),
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodedListItem {
+    Retain(usize),
+    Insert {
+        values: Vec<LoroValue>,
+        is_move: bool,
+    },
+    Delete(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodedTextItem {
+    Retain {
+        len: usize,
+        attributes: Option<Vec<(String, LoroValue)>>,
+    },
+    Insert {
+        text: String,
+        attributes: Option<Vec<(String, LoroValue)>>,
+    },
+    Delete(usize),
+    InsertEmbed {
+        value: LoroValue,
+        attributes: Option<Vec<(String, LoroValue)>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedMapValue {
+    pub key: String,
+    /// `None` means the key is deleted
+    pub value: Option<LoroValue>,
+    pub idlp: IdLp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedTreeItem {
+    pub target: TreeID,
+    pub action: EncodedTreeAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodedTreeAction {
+    Create {
+        parent: EncodedTreeParent,
+        index: usize,
+        /// The hex string of the fractional index
+        fractional_index: String,
+    },
+    Move {
+        parent: EncodedTreeParent,
+        index: usize,
+        fractional_index: String,
+        old_parent: EncodedTreeParent,
+        old_index: usize,
+    },
+    Delete {
+        old_parent: EncodedTreeParent,
+        old_index: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodedTreeParent {
+    Node(TreeID),
+    Root,
+    Deleted,
+    Unexist,
+}
+
+/// The owned and serializable version of [`DiffEvent`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedDiffEvent {
+    pub from: Vec<ID>,
+    pub to: Vec<ID>,
+    pub origin: String,
+    pub triggered_by: EventTriggerKind,
+    pub current_target: Option<String>,
+    pub events: Vec<EncodedEventItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodedEventItem {
+    pub target: String,
+    pub path: Vec<(String, Index)>,
+    pub is_unknown: bool,
+    pub diff: EncodedDiff,
+}
+
+impl DiffBatch {
+    pub fn encode(&self) -> EncodedDiffBatch {
+        let mut diffs: Vec<_> = self
+            .0
+            .iter()
+            .map(|(id, diff)| EncodedContainerDiff {
+                id: id.to_string(),
+                diff: encode_diff(diff),
+            })
+            .collect();
+        diffs.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+        EncodedDiffBatch { diffs }
+    }
+
+    /// Decode the diff batch. The child containers in the diffs are bound to `doc`.
+    pub fn decode(encoded: &EncodedDiffBatch, doc: &LoroDoc) -> LoroResult<Self> {
+        let mut map = FxHashMap::default();
+        for d in encoded.diffs.iter() {
+            map.insert(parse_container_id(&d.id)?, decode_diff(&d.diff, doc)?);
+        }
+
+        Ok(Self(map))
+    }
+}
+
+impl EncodedDiffBatch {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> LoroResult<Self> {
+        serde_json::from_str(json).map_err(|e| LoroError::DecodeError(e.to_string().into()))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_versioned_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> LoroResult<Self> {
+        from_versioned_bytes(bytes)
+    }
+}
+
+impl DiffEvent<'_> {
+    pub fn encode(&self) -> EncodedDiffEvent {
+        EncodedDiffEvent {
+            from: self.event_meta.from.to_vec(),
+            to: self.event_meta.to.to_vec(),
+            origin: self.event_meta.origin.to_string(),
+            triggered_by: self.event_meta.by,
+            current_target: self.current_target.as_ref().map(|x| x.to_string()),
+            events: self
+                .events
+                .iter()
+                .map(|e| EncodedEventItem {
+                    target: e.id.to_string(),
+                    path: e
+                        .path
+                        .iter()
+                        .map(|(id, index)| (id.to_string(), index.clone()))
+                        .collect(),
+                    is_unknown: e.is_unknown,
+                    diff: encode_diff(&e.diff),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl EncodedDiffEvent {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> LoroResult<Self> {
+        serde_json::from_str(json).map_err(|e| LoroError::DecodeError(e.to_string().into()))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_versioned_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> LoroResult<Self> {
+        from_versioned_bytes(bytes)
+    }
+
+    /// Decode the event into a [`DocDiff`]. The child containers in the diffs are bound to `doc`.
+    ///
+    /// It can be turned into a [`DiffBatch`] with [`DiffBatch::new`] to be applied to `doc`.
+    pub fn decode(&self, doc: &LoroDoc) -> LoroResult<DocDiff> {
+        let mut diff = Vec::with_capacity(self.events.len());
+        for e in self.events.iter() {
+            let id = parse_container_id(&e.target)?;
+            let mut path = Vec::with_capacity(e.path.len());
+            for (id, index) in e.path.iter() {
+                path.push((parse_container_id(id)?, index.clone()));
+            }
+            diff.push(ContainerDiff {
+                idx: doc.arena.register_container(&id),
+                id,
+                path,
+                is_unknown: e.is_unknown,
+                diff: decode_diff(&e.diff, doc)?,
+            });
+        }
+
+        Ok(DocDiff {
+            from: self.from.iter().copied().collect(),
+            to: self.to.iter().copied().collect(),
+            origin: self.origin.as_str().into(),
+            by: self.triggered_by,
+            diff,
+        })
+    }
+}
+
+fn parse_container_id(id: &str) -> LoroResult<ContainerID> {
+    ContainerID::try_from(id)
+        .map_err(|_| LoroError::DecodeError(format!("Invalid container id {}", id).into()))
+}
+
+fn encode_diff(diff: &Diff) -> EncodedDiff {
+    match diff {
+        Diff::List(list) => EncodedDiff::List(
+            list.iter()
+                .flat_map(|item| match item {
+                    DeltaItem::Retain { len, .. } => vec![EncodedListItem::Retain(*len)],
+                    DeltaItem::Replace {
+                        value,
+                        attr,
+                        delete,
+                    } => {
+                        let mut ans = Vec::with_capacity(2);
+                        if !value.is_empty() {
+                            ans.push(EncodedListItem::Insert {
+                                values: value.iter().map(|v| v.to_value()).collect(),
+                                is_move: attr.from_move,
+                            });
+                        }
+                        if *delete > 0 {
+                            ans.push(EncodedListItem::Delete(*delete));
+                        }
+                        ans
+                    }
+                })
+                .collect(),
+        ),
+        Diff::Text(text) => EncodedDiff::Text(
+            text.iter()
+                .flat_map(|item| match item {
+                    DeltaItem::Retain { len, attr } => vec![EncodedTextItem::Retain {
+                        len: *len,
+                        attributes: encode_attributes(attr),
+                    }],
+                    DeltaItem::Replace {
+                        value,
+                        attr,
+                        delete,
+                    } => {
+                        let mut ans = Vec::with_capacity(2);
+                        if let Some(embed) = value.as_embed() {
+                            ans.push(EncodedTextItem::InsertEmbed {
+                                value: embed.clone(),
+                                attributes: encode_attributes(attr),
+                            });
+                        } else if !value.is_empty() {
+                            ans.push(EncodedTextItem::Insert {
+                                text: value.to_string(),
+                                attributes: encode_attributes(attr),
+                            });
+                        }
+                        if *delete > 0 {
+                            ans.push(EncodedTextItem::Delete(*delete));
+                        }
+                        ans
+                    }
+                })
+                .collect(),
+        ),
+        Diff::Map(map) => {
+            let mut updated: Vec<_> = map
+                .updated
+                .iter()
+                .map(|(key, v)| EncodedMapValue {
+                    key: key.to_string(),
+                    value: v.value.as_ref().map(|v| v.to_value()),
+                    idlp: v.idlp,
+                })
+                .collect();
+            updated.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+            EncodedDiff::Map(updated)
+        }
+        Diff::Tree(tree) => EncodedDiff::Tree(
+            tree.diff
+                .iter()
+                .map(|item| EncodedTreeItem {
+                    target: item.target,
+                    action: match &item.action {
+                        TreeExternalDiff::Create {
+                            parent,
+                            index,
+                            position,
+                        } => EncodedTreeAction::Create {
+                            parent: (*parent).into(),
+                            index: *index,
+                            fractional_index: position.to_string(),
+                        },
+                        TreeExternalDiff::Move {
+                            parent,
+                            index,
+                            position,
+                            old_parent,
+                            old_index,
+                        } => EncodedTreeAction::Move {
+                            parent: (*parent).into(),
+                            index: *index,
+                            fractional_index: position.to_string(),
+                            old_parent: (*old_parent).into(),
+                            old_index: *old_index,
+                        },
+                        TreeExternalDiff::Delete {
+                            old_parent,
+                            old_index,
+                        } => EncodedTreeAction::Delete {
+                            old_parent: (*old_parent).into(),
+                            old_index: *old_index,
+                        },
+                    },
+                })
+                .collect(),
+        ),
+        #[cfg(feature = "counter")]
+        Diff::Counter(x) => EncodedDiff::Counter(*x),
+        Diff::Unknown => EncodedDiff::Unknown,
+    }
+}
+
+fn decode_diff(diff: &EncodedDiff, doc: &LoroDoc) -> LoroResult<Diff> {
+    Ok(match diff {
+        EncodedDiff::List(items) => {
+            let mut ans = DeltaRope::new();
+            for item in items.iter() {
+                match item {
+                    EncodedListItem::Retain(len) => {
+                        ans.push_retain(*len, Default::default());
+                    }
+                    EncodedListItem::Insert { values, is_move } => {
+                        let values = values.iter().map(|v| decode_value(v, doc));
+                        for chunk in ArrayVec::from_many(values) {
+                            ans.push_insert(
+                                chunk,
+                                ListDeltaMeta {
+                                    from_move: *is_move,
+                                },
+                            );
+                        }
+                    }
+                    EncodedListItem::Delete(len) => {
+                        ans.push_delete(*len);
+                    }
+                }
+            }
+            Diff::List(ans)
+        }
+        EncodedDiff::Text(items) => {
+            let mut ans = DeltaRope::new();
+            for item in items.iter() {
+                match item {
+                    EncodedTextItem::Retain { len, attributes } => {
+                        ans.push_retain(*len, decode_attributes(attributes));
+                    }
+                    EncodedTextItem::Insert { text, attributes } => {
+                        ans.push_insert(text.as_str().into(), decode_attributes(attributes));
+                    }
+                    EncodedTextItem::Delete(len) => {
+                        ans.push_delete(*len);
+                    }
+                    EncodedTextItem::InsertEmbed { value, attributes } => {
+                        ans.push_insert(
+                            StringSlice::new_embed(value.clone()),
+                            decode_attributes(attributes),
+                        );
+                    }
+                }
+            }
+            Diff::Text(ans)
+        }
+        EncodedDiff::Map(updated) => Diff::Map(ResolvedMapDelta {
+            updated: updated
+                .iter()
+                .map(|v| {
+                    (
+                        v.key.as_str().into(),
+                        ResolvedMapValue {
+                            value: v.value.as_ref().map(|v| decode_value(v, doc)),
+                            idlp: v.idlp,
+                        },
+                    )
+                })
+                .collect(),
+        }),
+        EncodedDiff::Tree(items) => Diff::Tree(TreeDiff {
+            diff: items
+                .iter()
+                .map(|item| TreeDiffItem {
+                    target: item.target,
+                    action: match &item.action {
+                        EncodedTreeAction::Create {
+                            parent,
+                            index,
+                            fractional_index,
+                        } => TreeExternalDiff::Create {
+                            parent: (*parent).into(),
+                            index: *index,
+                            position: FractionalIndex::from_hex_string(fractional_index),
+                        },
+                        EncodedTreeAction::Move {
+                            parent,
+                            index,
+                            fractional_index,
+                            old_parent,
+                            old_index,
+                        } => TreeExternalDiff::Move {
+                            parent: (*parent).into(),
+                            index: *index,
+                            position: FractionalIndex::from_hex_string(fractional_index),
+                            old_parent: (*old_parent).into(),
+                            old_index: *old_index,
+                        },
+                        EncodedTreeAction::Delete {
+                            old_parent,
+                            old_index,
+                        } => TreeExternalDiff::Delete {
+                            old_parent: (*old_parent).into(),
+                            old_index: *old_index,
+                        },
+                    },
+                })
+                .collect(),
+        }),
+        #[cfg(feature = "counter")]
+        EncodedDiff::Counter(x) => Diff::Counter(*x),
+        #[cfg(not(feature = "counter"))]
+        EncodedDiff::Counter(_) => {
+            return Err(LoroError::NotImplemented(
+                "Counter diff requires the counter feature",
+            ))
+        }
+        EncodedDiff::Unknown => Diff::Unknown,
+    })
+}
+
+fn decode_value(value: &LoroValue, doc: &LoroDoc) -> ValueOrHandler {
+    match value {
+        LoroValue::Container(id) => ValueOrHandler::Handler(Handler::new_attached(
+            id.clone(),
+            doc.arena.clone(),
+            doc.get_global_txn(),
+            Arc::downgrade(&doc.state),
+        )),
+        v => ValueOrHandler::Value(v.clone()),
+    }
+}
+
+fn encode_attributes(attr: &StyleMeta) -> Option<Vec<(String, LoroValue)>> {
+    attr.to_option_map().map(|map| {
+        let mut ans: Vec<_> = map.into_iter().collect();
+        ans.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        ans
+    })
+}
+
+fn decode_attributes(attributes: &Option<Vec<(String, LoroValue)>>) -> StyleMeta {
+    let mut ans = StyleMeta::default();
+    for (key, value) in attributes.iter().flatten() {
+        ans.insert(
+            key.as_str().into(),
+            StyleMetaItem {
+                // The lamport and peer are only used to compose the events
+                lamport: 0,
+                peer: 0,
+                value: value.clone(),
+            },
+        );
+    }
+    ans
+}
+
+impl From<TreeParentId> for EncodedTreeParent {
+    fn from(value: TreeParentId) -> Self {
+        match value {
+            TreeParentId::Node(id) => Self::Node(id),
+            TreeParentId::Root => Self::Root,
+            TreeParentId::Deleted => Self::Deleted,
+            TreeParentId::Unexist => Self::Unexist,
+        }
+    }
+}
+
+impl From<EncodedTreeParent> for TreeParentId {
+    fn from(value: EncodedTreeParent) -> Self {
+        match value {
+            EncodedTreeParent::Node(id) => Self::Node(id),
+            EncodedTreeParent::Root => Self::Root,
+            EncodedTreeParent::Deleted => Self::Deleted,
+            EncodedTreeParent::Unexist => Self::Unexist,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HandlerTrait, ListHandler, TextHandler};
+
+    #[test]
+    fn diff_batch_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "hello world").unwrap();
+        text.mark(0, 5, "bold", true.into()).unwrap();
+        let list = doc.get_list("list");
+        list.insert(0, 1).unwrap();
+        let sub = list
+            .insert_container(1, TextHandler::new_detached())
+            .unwrap();
+        sub.insert(0, "sub").unwrap();
+        doc.get_map("map").insert("key", "value").unwrap();
+        let tree = doc.get_tree("tree");
+        let root = tree.create(TreeParentId::Root).unwrap();
+        tree.get_meta(root).unwrap().insert("a", 1).unwrap();
+        tree.get_meta(root)
+            .unwrap()
+            .insert_container("list", ListHandler::new_detached())
+            .unwrap()
+            .insert(0, "x")
+            .unwrap();
+        doc.commit_then_renew();
+
+        let diff = doc
+            .diff(&Default::default(), &doc.oplog_frontiers())
+            .unwrap();
+        let encoded = diff.encode();
+        let json = encoded.to_json();
+        assert_eq!(EncodedDiffBatch::from_json(&json).unwrap(), encoded);
+        let bytes = encoded.to_bytes();
+        assert_eq!(EncodedDiffBatch::from_bytes(&bytes).unwrap(), encoded);
+
+        let new_doc = LoroDoc::new_auto_commit();
+        let decoded = DiffBatch::decode(&encoded, &new_doc).unwrap();
+        new_doc
+            .apply_diff(decoded, &mut Default::default(), true)
+            .unwrap();
+        for name in ["list", "map"] {
+            let a = new_doc
+                .get_deep_value()
+                .as_map()
+                .unwrap()
+                .get(name)
+                .cloned();
+            let b = doc.get_deep_value().as_map().unwrap().get(name).cloned();
+            assert_eq!(a, b);
+        }
+        // The tree nodes are recreated with new ids
+        let new_tree = new_doc.get_tree("tree");
+        let new_root = new_tree.roots()[0];
+        assert_eq!(
+            new_tree.get_meta(new_root).unwrap().get_deep_value(),
+            tree.get_meta(root).unwrap().get_deep_value()
+        );
+        assert_eq!(
+            new_doc.get_text("text").get_richtext_value(),
+            text.get_richtext_value()
+        );
+    }
+
+    #[test]
+    fn diff_event_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let _sub = doc.subscribe_root(Arc::new(move |e| {
+            events_clone.try_lock().unwrap().push(e.encode().to_bytes());
+        }));
+        doc.get_map("map").insert("key", 1).unwrap();
+        doc.get_text("text").insert(0, "abc").unwrap();
+        doc.commit_then_renew();
+
+        let events = events.try_lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0][0], FORMAT_VERSION);
+        let event = EncodedDiffEvent::from_bytes(&events[0]).unwrap();
+        assert_eq!(event.triggered_by, EventTriggerKind::Local);
+        let mut unknown_version = events[0].clone();
+        unknown_version[0] = FORMAT_VERSION + 1;
+        assert!(EncodedDiffEvent::from_bytes(&unknown_version).is_err());
+        assert_eq!(
+            EncodedDiffEvent::from_json(&event.to_json()).unwrap(),
+            event
+        );
+
+        let new_doc = LoroDoc::new_auto_commit();
+        let diff = DiffBatch::new(vec![event.decode(&new_doc).unwrap()]);
+        new_doc
+            .apply_diff(diff, &mut Default::default(), true)
+            .unwrap();
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    }
+}
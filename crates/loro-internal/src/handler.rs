@@ -4,11 +4,14 @@ use crate::{
     container::{
         idx::ContainerIdx,
         list::list_op::{DeleteSpan, DeleteSpanWithId, ListOp},
-        richtext::{richtext_state::PosType, RichtextState, StyleOp, TextStyleInfoFlag},
+        richtext::{
+            richtext_state::{PosType, RichtextStateChunk},
+            RichtextState, StyleOp, TextStyleInfoFlag,
+        },
     },
     cursor::{Cursor, Side},
     delta::{DeltaItem, Meta, StyleMeta, TreeExternalDiff},
-    diff::{diff, diff_impl::UpdateTimeoutError, OperateProxy},
+    diff::diff_impl::UpdateTimeoutError,
     event::{Diff, TextDiffItem},
     op::ListSlice,
     state::{IndexType, State, TreeParentId},
@@ -34,15 +37,37 @@ use std::{
 };
 use tracing::{error, info, instrument, trace};
 
-pub use crate::diff::diff_impl::UpdateOptions;
+pub use crate::diff::diff_impl::{DiffGranularity, UpdateOptions};
 pub use tree::TreeHandler;
 mod movable_list_apply_delta;
 mod tree;
 
 const INSERT_CONTAINER_VALUE_ARG_ERROR: &str =
     "Cannot insert a LoroValue::Container directly. To create child container, use insert_container";
-
+const INSERT_EMBED_VALUE_ARG_ERROR: &str =
+    "An inline embed cannot be a string or null, because it would be indistinguishable from text in the delta";
+
+mod reconcile;
+mod text_grapheme;
+mod text_haystack;
+#[cfg(feature = "line-index")]
+mod text_lines;
+mod text_markdown;
+mod text_quill;
+mod text_range;
+mod text_render;
+#[cfg(feature = "text-search")]
+mod text_search;
+mod text_styles;
 mod text_update;
+pub(crate) use reconcile::{
+    insert_list_value, insert_movable_list_value, replace_movable_list_value, set_map_value,
+};
+#[cfg(feature = "line-index")]
+pub use text_lines::LineChange;
+pub use text_render::{CustomMarkRender, MarkRender, TextFormat, TextRenderOptions};
+#[cfg(feature = "text-search")]
+pub use text_search::{Regex, TextMatch};
 
 pub trait HandlerTrait: Clone + Sized {
     fn is_attached(&self) -> bool;
@@ -289,10 +314,16 @@ impl HandlerTrait for TextHandler {
                 let text = inner.into_text().unwrap();
                 let mut delta: Vec<TextDelta> = Vec::new();
                 for span in t.value.iter() {
-                    delta.push(TextDelta::Insert {
-                        insert: span.text.to_string(),
-                        attributes: span.attributes.to_option_map(),
-                    });
+                    match span.text.as_embed() {
+                        Some(embed) => delta.push(TextDelta::InsertEmbed {
+                            insert: embed.clone(),
+                            attributes: span.attributes.to_option_map(),
+                        }),
+                        None => delta.push(TextDelta::Insert {
+                            insert: span.text.to_string(),
+                            attributes: span.attributes.to_option_map(),
+                        }),
+                    }
                 }
 
                 text.apply_delta_with_txn(txn, &delta)?;
@@ -366,6 +397,28 @@ impl std::fmt::Debug for TextHandler {
     }
 }
 
+/// The unit of the positions passed to and returned by a text container.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextPosUnit {
+    /// Unicode scalar values
+    #[default]
+    Unicode,
+    /// UTF-16 code units, as used by JavaScript strings
+    Utf16,
+    /// UTF-8 bytes
+    Utf8,
+}
+
+impl TextPosUnit {
+    pub(crate) fn to_pos_type(self) -> PosType {
+        match self {
+            TextPosUnit::Unicode => PosType::Unicode,
+            TextPosUnit::Utf16 => PosType::Utf16,
+            TextPosUnit::Utf8 => PosType::Bytes,
+        }
+    }
+}
+
 #[derive(Debug, Clone, EnumAsInner, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum TextDelta {
@@ -380,6 +433,44 @@ pub enum TextDelta {
     Delete {
         delete: usize,
     },
+    /// An inline embed. It occupies a single position in the text.
+    ///
+    /// It is serialized as `{ "embed": value }`. A non-string value is also accepted under
+    /// `insert`, as Quill does, but a string under `insert` is always text, so a container
+    /// embed, whose JSON form is a prefixed container id, has to be tagged with `embed`.
+    InsertEmbed {
+        #[serde(rename = "embed", alias = "insert")]
+        insert: LoroValue,
+        attributes: Option<FxHashMap<String, LoroValue>>,
+    },
+}
+
+/// The error of setting a block attribute on a paragraph without a trailing newline
+fn no_paragraph_end(pos: usize) -> LoroError {
+    LoroError::ArgErr(
+        format!(
+            "the paragraph at {} doesn't end with a newline, so it can't have block attributes",
+            pos
+        )
+        .into_boxed_str(),
+    )
+}
+
+/// Check that the value can be an inline embed of a text
+fn check_embed_value(v: &LoroValue) -> LoroResult<()> {
+    let msg = match v {
+        LoroValue::Container(_) => INSERT_CONTAINER_VALUE_ARG_ERROR,
+        LoroValue::String(_) | LoroValue::Null => INSERT_EMBED_VALUE_ARG_ERROR,
+        _ => return Ok(()),
+    };
+
+    Err(LoroError::ArgErr(msg.to_string().into_boxed_str()))
+}
+
+/// The content of a single local insertion into a text container.
+enum TextInsertContent<'a> {
+    Str(&'a str),
+    Embed(LoroValue),
 }
 
 impl TextDelta {
@@ -398,7 +489,12 @@ impl TextDelta {
                     attr,
                     delete,
                 } => {
-                    if value.rle_len() > 0 {
+                    if let Some(embed) = value.as_embed() {
+                        ans.push(TextDelta::InsertEmbed {
+                            insert: embed.clone(),
+                            attributes: attr.to_option_map(),
+                        });
+                    } else if value.rle_len() > 0 {
                         ans.push(TextDelta::Insert {
                             insert: value.to_string(),
                             attributes: attr.to_option_map(),
@@ -422,9 +518,15 @@ impl From<&DeltaItem<StringSlice, StyleMeta>> for TextDelta {
                 retain: *retain,
                 attributes: attributes.to_option_map(),
             },
-            crate::delta::DeltaItem::Insert { insert, attributes } => TextDelta::Insert {
-                insert: insert.to_string(),
-                attributes: attributes.to_option_map(),
+            crate::delta::DeltaItem::Insert { insert, attributes } => match insert.as_embed() {
+                Some(embed) => TextDelta::InsertEmbed {
+                    insert: embed.clone(),
+                    attributes: attributes.to_option_map(),
+                },
+                None => TextDelta::Insert {
+                    insert: insert.to_string(),
+                    attributes: attributes.to_option_map(),
+                },
             },
             crate::delta::DeltaItem::Delete {
                 delete,
@@ -1379,6 +1481,10 @@ impl TextHandler {
             MaybeDetached::Detached(t) => {
                 let t = t.try_lock().unwrap();
                 for span in t.value.iter() {
+                    if span.text.is_embed() {
+                        continue;
+                    }
+
                     if !callback(span.text.as_str()) {
                         return;
                     }
@@ -1558,6 +1664,142 @@ impl TextHandler {
         Ok(())
     }
 
+    /// Insert `s` at the event index `pos` with exactly the styles in `attrs`.
+    ///
+    /// The styles that `s` would inherit from its neighbors by their expand behavior are
+    /// removed unless they are in `attrs`, and the other styles in `attrs` are applied to `s`.
+    /// It's done in the same transaction as the insertion, so no peer can see `s` with the
+    /// inherited styles. A `null` value removes the style.
+    pub fn insert_with_marks(
+        &self,
+        pos: usize,
+        s: &str,
+        attrs: &FxHashMap<String, LoroValue>,
+    ) -> LoroResult<()> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => {
+                self.insert(pos, s)?;
+                if s.is_empty() {
+                    return Ok(());
+                }
+
+                let end = pos + event_len(s);
+                for (key, value) in attrs {
+                    if !value.is_null() {
+                        self.mark(pos, end, key.as_str(), value.clone())?;
+                    }
+                }
+
+                Ok(())
+            }
+            MaybeDetached::Attached(a) => {
+                a.with_txn(|txn| self.insert_with_marks_with_txn(txn, pos, s, attrs))
+            }
+        }
+    }
+
+    /// `pos` is a Event Index, see [`TextHandler::insert_with_marks`].
+    pub fn insert_with_marks_with_txn(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        s: &str,
+        attrs: &FxHashMap<String, LoroValue>,
+    ) -> LoroResult<()> {
+        let end = pos + event_len(s);
+        let override_styles =
+            self.insert_with_txn_and_attr(txn, pos, s, Some(attrs), PosType::Event)?;
+        for (key, value) in override_styles {
+            let is_delete = value.is_null();
+            self.mark_with_txn(txn, pos, end, key, value, is_delete)?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert an inline embed at the given event index.
+    ///
+    /// An embed occupies a single position in the text and is skipped by `to_string`.
+    /// Use [`TextHandler::insert_embed_container`] to embed a container.
+    pub fn insert_embed(&self, pos: usize, v: impl Into<LoroValue>) -> LoroResult<()> {
+        let v = v.into();
+        check_embed_value(&v)?;
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let mut t = t.try_lock().unwrap();
+                let len = t.value.len_event();
+                if pos > len {
+                    return Err(LoroError::OutOfBound {
+                        pos,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len,
+                    });
+                }
+                let (index, _) = t
+                    .value
+                    .get_entity_index_for_text_insert(pos, PosType::Event)
+                    .unwrap();
+                t.value.insert_elem_at_entity_index(
+                    index,
+                    RichtextStateChunk::new_embed(v, IdFull::NONE_ID),
+                );
+                Ok(())
+            }
+            MaybeDetached::Attached(a) => a.with_txn(|txn| self.insert_embed_with_txn(txn, pos, v)),
+        }
+    }
+
+    pub fn insert_embed_with_txn(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        v: LoroValue,
+    ) -> LoroResult<()> {
+        check_embed_value(&v)?;
+        self.insert_content_with_txn_and_attr(
+            txn,
+            pos,
+            TextInsertContent::Embed(v),
+            None,
+            PosType::Event,
+        )?;
+        Ok(())
+    }
+
+    /// Insert a child container as an inline embed at the given event index.
+    ///
+    /// Detached text containers cannot hold child containers.
+    pub fn insert_embed_container<H: HandlerTrait>(&self, pos: usize, child: H) -> LoroResult<H> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => Err(LoroError::MisuseDetachedContainer {
+                method: "insert_embed_container",
+            }),
+            MaybeDetached::Attached(a) => {
+                a.with_txn(|txn| self.insert_embed_container_with_txn(txn, pos, child))
+            }
+        }
+    }
+
+    pub fn insert_embed_container_with_txn<H: HandlerTrait>(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        child: H,
+    ) -> LoroResult<H> {
+        let inner = self.inner.try_attached_state()?;
+        let id = txn.next_id();
+        let container_id = ContainerID::new_normal(id, child.kind());
+        self.insert_content_with_txn_and_attr(
+            txn,
+            pos,
+            TextInsertContent::Embed(LoroValue::Container(container_id.clone())),
+            None,
+            PosType::Event,
+        )?;
+        let ans = child.attach(txn, inner, container_id)?;
+        Ok(ans)
+    }
+
     /// `pos` is a Event Index:
     ///
     /// - if feature="wasm", pos is a UTF-16 index
@@ -1636,6 +1878,17 @@ impl TextHandler {
             return Ok(Vec::new());
         }
 
+        self.insert_content_with_txn_and_attr(txn, pos, TextInsertContent::Str(s), attr, pos_type)
+    }
+
+    fn insert_content_with_txn_and_attr(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        content: TextInsertContent<'_>,
+        attr: Option<&FxHashMap<String, LoroValue>>,
+        pos_type: PosType,
+    ) -> Result<Vec<(InternalString, LoroValue)>, LoroError> {
         match pos_type {
             PosType::Event => {
                 if pos > self.len_event() {
@@ -1750,20 +2003,30 @@ impl TextHandler {
             }
         }
 
-        let unicode_len = s.chars().count();
-        let event_len = if cfg!(feature = "wasm") {
-            count_utf16_len(s.as_bytes())
-        } else {
-            unicode_len
+        let (slice, unicode_len, event_len) = match content {
+            TextInsertContent::Str(s) => {
+                let unicode_len = s.chars().count();
+                let event_len = if cfg!(feature = "wasm") {
+                    count_utf16_len(s.as_bytes())
+                } else {
+                    unicode_len
+                };
+                (
+                    ListSlice::RawStr {
+                        str: Cow::Borrowed(s),
+                        unicode_len,
+                    },
+                    unicode_len,
+                    event_len,
+                )
+            }
+            TextInsertContent::Embed(value) => (ListSlice::RawData(Cow::Owned(vec![value])), 1, 1),
         };
 
         txn.apply_local_op(
             inner.container_idx,
             crate::op::RawOpContent::List(crate::container::list::list_op::ListOp::Insert {
-                slice: ListSlice::RawStr {
-                    str: Cow::Borrowed(s),
-                    unicode_len,
-                },
+                slice,
                 pos: entity_index,
             }),
             EventHint::InsertText {
@@ -1868,6 +2131,10 @@ impl TextHandler {
     /// - if feature="wasm", pos is a UTF-16 index
     /// - if feature!="wasm", pos is a Unicode index
     ///
+    /// If `key` is a block attribute, the newlines ending the paragraphs in the range are
+    /// marked instead. It fails if the range reaches a last paragraph that doesn't end with a
+    /// newline, because peers appending the missing newline concurrently would each add one.
+    ///
     /// This method requires auto_commit to be enabled.
     pub fn mark(
         &self,
@@ -1995,9 +2262,37 @@ impl TextHandler {
             });
         }
 
-        let inner = self.inner.try_attached_state()?;
         let key: InternalString = key.into();
+        if self.is_block_key(&key) {
+            let newlines: Vec<usize> = self
+                .paragraph_ends(start, end)
+                .into_iter()
+                .map(|(pos, _)| pos)
+                .collect();
+            if !is_delete && !matches!(newlines.last(), Some(&pos) if pos + 1 >= end) {
+                return Err(no_paragraph_end(end - 1));
+            }
+
+            for pos in newlines {
+                self.mark_range_with_txn(txn, pos, pos + 1, key.clone(), value.clone(), is_delete)?;
+            }
+
+            return Ok(());
+        }
+
+        self.mark_range_with_txn(txn, start, end, key, value, is_delete)
+    }
 
+    fn mark_range_with_txn(
+        &self,
+        txn: &mut Transaction,
+        start: usize,
+        end: usize,
+        key: InternalString,
+        value: LoroValue,
+        is_delete: bool,
+    ) -> LoroResult<()> {
+        let inner = self.inner.try_attached_state()?;
         let mutex = &inner.state.upgrade().unwrap();
         let mut doc_state = mutex.try_lock().unwrap();
         let (entity_range, skip) = doc_state.with_state_mut(inner.container_idx, |state| {
@@ -2062,6 +2357,148 @@ impl TextHandler {
         Ok(())
     }
 
+    /// Set the block attribute `key` of the paragraph that contains `pos`, which is an event
+    /// index.
+    ///
+    /// `key` must be registered by [`StyleConfigMap::insert_block`]. The attribute is stored on
+    /// the newline that ends the paragraph, so it fails if the paragraph doesn't end with a
+    /// newline. Keep a newline at the end of the text, as Quill does, to style the last
+    /// paragraph.
+    ///
+    /// [`StyleConfigMap::insert_block`]: crate::configure::StyleConfigMap::insert_block
+    pub fn set_block_attr(
+        &self,
+        pos: usize,
+        key: impl Into<InternalString>,
+        value: LoroValue,
+    ) -> LoroResult<()> {
+        let inner = self.inner.try_attached_state()?;
+        inner.with_txn(|txn| self.set_block_attr_with_txn(txn, pos, key, value))
+    }
+
+    pub fn set_block_attr_with_txn(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        key: impl Into<InternalString>,
+        value: LoroValue,
+    ) -> LoroResult<()> {
+        let key: InternalString = key.into();
+        if !self.is_block_key(&key) {
+            return Err(LoroError::ArgErr(
+                format!("`{}` is not a block attribute", key).into_boxed_str(),
+            ));
+        }
+
+        let len = self.len_event();
+        if pos > len {
+            return Err(LoroError::OutOfBound {
+                pos,
+                len,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        let Some(&(newline, _)) = self.paragraph_ends(pos, pos + 1).first() else {
+            return Err(no_paragraph_end(pos));
+        };
+
+        self.mark_range_with_txn(txn, newline, newline + 1, key, value, false)
+    }
+
+    /// Remove the block attribute `key` from the paragraph that contains `pos`, which is an
+    /// event index.
+    pub fn remove_block_attr(&self, pos: usize, key: impl Into<InternalString>) -> LoroResult<()> {
+        let inner = self.inner.try_attached_state()?;
+        inner.with_txn(|txn| self.remove_block_attr_with_txn(txn, pos, key))
+    }
+
+    pub fn remove_block_attr_with_txn(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        key: impl Into<InternalString>,
+    ) -> LoroResult<()> {
+        let key: InternalString = key.into();
+        if !self.is_block_key(&key) {
+            return Err(LoroError::ArgErr(
+                format!("`{}` is not a block attribute", key).into_boxed_str(),
+            ));
+        }
+
+        match self.paragraph_ends(pos, pos + 1).first() {
+            Some((newline, _)) => {
+                self.mark_range_with_txn(txn, *newline, *newline + 1, key, LoroValue::Null, true)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Get the block attributes of the paragraph that contains `pos`, which is an event index.
+    pub fn get_block_attrs(&self, pos: usize) -> FxHashMap<String, LoroValue> {
+        if !self.is_attached() {
+            return FxHashMap::default();
+        }
+
+        let Some((_, Some(attributes))) = self.paragraph_ends(pos, pos + 1).into_iter().next()
+        else {
+            return FxHashMap::default();
+        };
+
+        attributes
+            .into_iter()
+            .filter(|(k, v)| !v.is_null() && self.is_block_key(&k.as_str().into()))
+            .collect()
+    }
+
+    fn is_block_key(&self, key: &InternalString) -> bool {
+        let Ok(inner) = self.inner.try_attached_state() else {
+            return false;
+        };
+
+        inner.with_doc_state(|state| {
+            state
+                .config
+                .text_style_config
+                .try_read()
+                .unwrap()
+                .is_block(key)
+        })
+    }
+
+    /// The newlines that end the paragraphs overlapping `start..end`, with their attributes.
+    ///
+    /// The positions are event indexes. A last paragraph without a trailing newline is skipped.
+    /// Only the chunks from `start` to the last returned newline are visited.
+    fn paragraph_ends(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Vec<(usize, Option<FxHashMap<String, LoroValue>>)> {
+        let Ok(inner) = self.inner.try_attached_state() else {
+            return Vec::new();
+        };
+
+        inner.with_state(|state| {
+            let state = state.as_richtext_state_mut().unwrap();
+            let mut ans = Vec::new();
+            let mut pos = start;
+            while let Some(newline) = state.next_newline(pos) {
+                let attributes = state
+                    .get_styles_at(newline, PosType::Event)
+                    .and_then(|styles| styles.to_option_map());
+                ans.push((newline, attributes));
+                if newline + 1 >= end {
+                    break;
+                }
+
+                pos = newline + 1;
+            }
+
+            ans
+        })
+    }
+
     pub fn check(&self) {
         match &self.inner {
             MaybeDetached::Detached(t) => {
@@ -2100,20 +2537,52 @@ impl TextHandler {
         for d in delta {
             match d {
                 TextDelta::Insert { insert, attributes } => {
-                    let end = index + event_len(insert.as_str());
-                    let override_styles = self.insert_with_txn_and_attr(
+                    self.insert_with_marks_with_txn(
                         txn,
                         index,
                         insert.as_str(),
-                        Some(attributes.as_ref().unwrap_or(&Default::default())),
-                        PosType::Event,
+                        attributes.as_ref().unwrap_or(&Default::default()),
                     )?;
+                    index += event_len(insert.as_str());
+                }
+                TextDelta::InsertEmbed { insert, attributes } => {
+                    let attr = Some(attributes.as_ref().unwrap_or(&Default::default()));
+                    let override_styles = match insert {
+                        LoroValue::Container(id) => {
+                            // The referenced container is copied into a new child,
+                            // the same way an attached list is attached to a new parent
+                            let inner = self.inner.try_attached_state()?;
+                            let child = create_handler(inner, id.clone());
+                            let container_id = ContainerID::new_normal(txn.next_id(), child.kind());
+                            let ans = self.insert_content_with_txn_and_attr(
+                                txn,
+                                index,
+                                TextInsertContent::Embed(LoroValue::Container(
+                                    container_id.clone(),
+                                )),
+                                attr,
+                                PosType::Event,
+                            )?;
+                            child.attach(txn, inner, container_id)?;
+                            ans
+                        }
+                        _ => {
+                            check_embed_value(insert)?;
+                            self.insert_content_with_txn_and_attr(
+                                txn,
+                                index,
+                                TextInsertContent::Embed(insert.clone()),
+                                attr,
+                                PosType::Event,
+                            )?
+                        }
+                    };
 
                     for (key, value) in override_styles {
-                        marks.push((index, end, key, value));
+                        marks.push((index, index + 1, key, value));
                     }
 
-                    index = end;
+                    index += 1;
                 }
                 TextDelta::Delete { delete } => {
                     self.delete_with_txn(txn, index, *delete)?;
@@ -2147,16 +2616,8 @@ impl TextHandler {
     }
 
     pub fn update(&self, text: &str, options: UpdateOptions) -> Result<(), UpdateTimeoutError> {
-        let old_str = self.to_string();
-        let new = text.chars().map(|x| x as u32).collect::<Vec<u32>>();
-        let old = old_str.chars().map(|x| x as u32).collect::<Vec<u32>>();
-        diff(
-            &mut OperateProxy::new(text_update::DiffHook::new(self, &new)),
-            options,
-            &old,
-            &new,
-        )?;
-        Ok(())
+        let tokenizer = text_update::Tokenizer::from_options(&options);
+        text_update::update_text(self, text, tokenizer, options)
     }
 
     pub fn update_by_line(
@@ -2164,15 +2625,7 @@ impl TextHandler {
         text: &str,
         options: UpdateOptions,
     ) -> Result<(), UpdateTimeoutError> {
-        let hook = text_update::DiffHookForLine::new(self, text);
-        let old_lines = hook.get_old_arr().to_vec();
-        let new_lines = hook.get_new_arr().to_vec();
-        diff(
-            &mut OperateProxy::new(hook),
-            options,
-            &old_lines,
-            &new_lines,
-        )
+        text_update::update_text(self, text, text_update::Tokenizer::Line, options)
     }
 
     #[allow(clippy::inherent_to_string)]
@@ -0,0 +1,288 @@
+use fxhash::FxHashMap;
+use loro_common::{LoroError, LoroResult, LoroValue};
+
+use super::{Handler, HandlerTrait, ListHandler, MapHandler, MovableListHandler, ValueOrHandler};
+use crate::diff::{diff, DiffHandler, OperateProxy};
+
+impl Handler {
+    /// Update the container to the target deep value with the smallest set of ops.
+    ///
+    /// - Maps are diffed key by key.
+    /// - Lists are diffed with the same algorithm as [`super::TextHandler::update`].
+    /// - Texts are updated with [`super::TextHandler::update`].
+    /// - Counters are incremented by the difference.
+    /// - Nested containers are reconciled recursively.
+    ///
+    /// New maps and lists in the target value are created as containers, while the
+    /// existing plain values are overwritten as plain values.
+    pub fn set_deep_value(&self, value: &LoroValue) -> LoroResult<()> {
+        if self.reconcile(value)? {
+            Ok(())
+        } else {
+            Err(LoroError::ArgErr(
+                format!("{:?} cannot be set to {:?}", self.c_type(), value).into_boxed_str(),
+            ))
+        }
+    }
+
+    /// Return false if the container cannot represent the value.
+    pub(crate) fn reconcile(&self, value: &LoroValue) -> LoroResult<bool> {
+        match (self, value) {
+            (Handler::Text(t), LoroValue::String(s)) => {
+                if t.to_string() != **s {
+                    // No timeout is set, so it never fails
+                    t.update(s, Default::default()).unwrap();
+                }
+            }
+            #[cfg(feature = "counter")]
+            (Handler::Counter(c), LoroValue::Double(_) | LoroValue::I64(_)) => {
+                let old = c.get_value().into_double().unwrap_or_default();
+                let new = match value {
+                    LoroValue::I64(i) => *i as f64,
+                    v => *v.as_double().unwrap(),
+                };
+                if old != new {
+                    c.increment(new - old)?;
+                }
+            }
+            (Handler::Map(m), LoroValue::Map(target)) => reconcile_map(m, target)?,
+            (Handler::List(l), LoroValue::List(target)) => {
+                reconcile_list(ListLike::List(l), target)?
+            }
+            (Handler::MovableList(l), LoroValue::List(target)) => {
+                reconcile_list(ListLike::MovableList(l), target)?
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+fn reconcile_map(map: &MapHandler, target: &FxHashMap<String, LoroValue>) -> LoroResult<()> {
+    let keys: Vec<_> = map.keys().collect();
+    for key in keys {
+        if !target.contains_key(key.as_str()) && map.get_(key.as_str()).is_some() {
+            map.delete(key.as_str())?;
+        }
+    }
+
+    let mut entries: Vec<_> = target.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in entries {
+        set_map_value(map, key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Set the value of the key, reusing the existing child container if possible.
+pub(crate) fn set_map_value(map: &MapHandler, key: &str, value: &LoroValue) -> LoroResult<()> {
+    match map.get_(key) {
+        Some(ValueOrHandler::Handler(h)) => {
+            if h.reconcile(value)? {
+                return Ok(());
+            }
+        }
+        Some(ValueOrHandler::Value(v)) => {
+            if &v != value {
+                map.insert(key, value.clone())?;
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    match new_detached_container(value) {
+        Some(h) => {
+            map.insert_container(key, h)?.reconcile(value)?;
+        }
+        None => map.insert(key, value.clone())?,
+    }
+
+    Ok(())
+}
+
+pub(crate) fn insert_list_value(
+    list: &ListHandler,
+    pos: usize,
+    value: &LoroValue,
+) -> LoroResult<()> {
+    ListLike::List(list).insert(pos, value)
+}
+
+pub(crate) fn insert_movable_list_value(
+    list: &MovableListHandler,
+    pos: usize,
+    value: &LoroValue,
+) -> LoroResult<()> {
+    ListLike::MovableList(list).insert(pos, value)
+}
+
+/// Replace the element at `pos` with `set`, so the element keeps its identity.
+pub(crate) fn replace_movable_list_value(
+    list: &MovableListHandler,
+    pos: usize,
+    value: &LoroValue,
+) -> LoroResult<()> {
+    ListLike::MovableList(list).replace(pos, value)
+}
+
+/// Maps and lists are stored as containers
+fn new_detached_container(value: &LoroValue) -> Option<Handler> {
+    match value {
+        LoroValue::Map(_) => Some(MapHandler::new_detached().to_handler()),
+        LoroValue::List(_) => Some(ListHandler::new_detached().to_handler()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ListLike<'a> {
+    List(&'a ListHandler),
+    MovableList(&'a MovableListHandler),
+}
+
+impl ListLike<'_> {
+    fn len(&self) -> usize {
+        match self {
+            ListLike::List(l) => l.len(),
+            ListLike::MovableList(l) => l.len(),
+        }
+    }
+
+    fn get_(&self, pos: usize) -> Option<ValueOrHandler> {
+        match self {
+            ListLike::List(l) => l.get_(pos),
+            ListLike::MovableList(l) => l.get_(pos),
+        }
+    }
+
+    fn delete(&self, pos: usize, len: usize) -> LoroResult<()> {
+        match self {
+            ListLike::List(l) => l.delete(pos, len),
+            ListLike::MovableList(l) => l.delete(pos, len),
+        }
+    }
+
+    fn insert(&self, pos: usize, value: &LoroValue) -> LoroResult<()> {
+        match (self, new_detached_container(value)) {
+            (ListLike::List(l), Some(h)) => {
+                l.insert_container(pos, h)?.reconcile(value)?;
+            }
+            (ListLike::List(l), None) => l.insert(pos, value.clone())?,
+            (ListLike::MovableList(l), Some(h)) => {
+                l.insert_container(pos, h)?.reconcile(value)?;
+            }
+            (ListLike::MovableList(l), None) => l.insert(pos, value.clone())?,
+        }
+
+        Ok(())
+    }
+
+    /// Replace the element at `pos`, reusing the existing child container if possible
+    fn replace(&self, pos: usize, value: &LoroValue) -> LoroResult<()> {
+        if let Some(ValueOrHandler::Handler(h)) = self.get_(pos) {
+            if h.reconcile(value)? {
+                return Ok(());
+            }
+        }
+
+        match (self, new_detached_container(value)) {
+            (ListLike::MovableList(l), Some(h)) => {
+                l.set_container(pos, h)?.reconcile(value)?;
+            }
+            (ListLike::MovableList(l), None) => l.set(pos, value.clone())?,
+            (ListLike::List(_), _) => {
+                self.delete(pos, 1)?;
+                self.insert(pos, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Record which old elements are deleted and which new elements are inserted
+struct ListDiffRecorder<'a> {
+    deleted: &'a mut [bool],
+    inserted: &'a mut [bool],
+}
+
+impl DiffHandler for ListDiffRecorder<'_> {
+    fn insert(&mut self, _old_index: usize, new_index: usize, new_len: usize) {
+        self.inserted[new_index..new_index + new_len].fill(true);
+    }
+
+    fn delete(&mut self, old_index: usize, old_len: usize) {
+        self.deleted[old_index..old_index + old_len].fill(true);
+    }
+}
+
+fn value_ids<'a>(ids: &mut FxHashMap<&'a LoroValue, u32>, values: &'a [LoroValue]) -> Vec<u32> {
+    values
+        .iter()
+        .map(|v| {
+            let next = ids.len() as u32;
+            *ids.entry(v).or_insert(next)
+        })
+        .collect()
+}
+
+fn reconcile_list(list: ListLike, target: &[LoroValue]) -> LoroResult<()> {
+    let old: Vec<LoroValue> = (0..list.len())
+        .map(|i| list.get_(i).unwrap().to_deep_value())
+        .collect();
+    // Map the values to ids so that equal values can be matched by the diff algorithm
+    let mut ids: FxHashMap<&LoroValue, u32> = FxHashMap::default();
+    let old_ids = value_ids(&mut ids, &old);
+    let new_ids = value_ids(&mut ids, target);
+
+    let mut deleted = vec![false; old.len()];
+    let mut inserted = vec![false; target.len()];
+    diff(
+        &mut OperateProxy::new(ListDiffRecorder {
+            deleted: &mut deleted,
+            inserted: &mut inserted,
+        }),
+        Default::default(),
+        &old_ids,
+        &new_ids,
+    )
+    .unwrap();
+
+    // Walk the old and new elements side by side. A run of deleted elements followed by
+    // a run of inserted elements is paired up, so that the child containers can be
+    // reconciled in place instead of being recreated.
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+    loop {
+        let del_end = (i..old.len()).find(|&x| !deleted[x]).unwrap_or(old.len());
+        let ins_end = (j..target.len())
+            .find(|&x| !inserted[x])
+            .unwrap_or(target.len());
+        let paired = (del_end - i).min(ins_end - j);
+        for value in &target[j..j + paired] {
+            list.replace(pos, value)?;
+            pos += 1;
+        }
+        if del_end - i > paired {
+            list.delete(pos, del_end - i - paired)?;
+        }
+        for value in &target[j + paired..ins_end] {
+            list.insert(pos, value)?;
+            pos += 1;
+        }
+
+        (i, j) = (del_end, ins_end);
+        if i == old.len() || j == target.len() {
+            break;
+        }
+
+        // Both are kept
+        i += 1;
+        j += 1;
+        pos += 1;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use loro_common::{LoroError, LoroResult};
+
+use super::TextHandler;
+use crate::cursor::{Cursor, Side};
+
+/// Grapheme cluster positions.
+///
+/// Clusters can span across the chunks of the text, so they are not indexed. These methods scan
+/// the text and cost O(n). An inline embed is a cluster of its own.
+impl TextHandler {
+    /// Get the length of the text in grapheme clusters.
+    pub fn len_grapheme(&self) -> usize {
+        self.haystack().graphemes().len()
+    }
+
+    /// Insert `s` at the grapheme cluster index `pos`.
+    pub fn insert_grapheme(&self, pos: usize, s: &str) -> LoroResult<()> {
+        let pos = self.grapheme_to_unicode_index(pos)?;
+        self.insert_unicode(pos, s)
+    }
+
+    /// Delete `len` grapheme clusters from the grapheme cluster index `pos`.
+    pub fn delete_grapheme(&self, pos: usize, len: usize) -> LoroResult<()> {
+        let start = self.grapheme_to_unicode_index(pos)?;
+        let end = self.grapheme_to_unicode_index(pos + len)?;
+        self.delete_unicode(start, end - start)
+    }
+
+    /// Get the text between the grapheme cluster indexes `start_index` and `end_index`.
+    ///
+    /// Inline embeds are skipped, like [`TextHandler::slice`].
+    pub fn slice_grapheme(&self, start_index: usize, end_index: usize) -> LoroResult<String> {
+        if end_index < start_index {
+            return Err(LoroError::EndIndexLessThanStartIndex {
+                start: start_index,
+                end: end_index,
+            });
+        }
+
+        let haystack = self.haystack();
+        let mut ans = String::new();
+        for (i, (byte, cluster)) in haystack.graphemes().into_iter().enumerate() {
+            if i >= end_index {
+                break;
+            }
+
+            if i >= start_index {
+                for (offset, c) in cluster.char_indices() {
+                    if !haystack.is_embed(byte + offset) {
+                        ans.push(c);
+                    }
+                }
+            }
+        }
+
+        self.grapheme_to_unicode_index(end_index)?;
+        Ok(ans)
+    }
+
+    /// Get the stable position at the grapheme cluster index `pos`.
+    pub fn get_cursor_grapheme(&self, pos: usize, side: Side) -> Option<Cursor> {
+        let pos = self.grapheme_to_unicode_index(pos).ok()?;
+        self.get_cursor_internal(pos, side, false)
+    }
+
+    /// Convert a grapheme cluster index into a unicode index.
+    fn grapheme_to_unicode_index(&self, pos: usize) -> LoroResult<usize> {
+        let haystack = self.haystack();
+        let mut unicode_index = 0;
+        let mut len = 0;
+        for (_, cluster) in haystack.graphemes() {
+            if len == pos {
+                return Ok(unicode_index);
+            }
+
+            unicode_index += cluster.chars().count();
+            len += 1;
+        }
+
+        if len == pos {
+            return Ok(unicode_index);
+        }
+
+        Err(LoroError::OutOfBound {
+            pos,
+            len,
+            info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{handler::UpdateOptions, LoroDoc};
+
+    #[test]
+    fn edit_by_grapheme() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        // A thumbs up with skin tone, a family ZWJ sequence and a combining accent
+        text.insert(0, "a👍🏽👨‍👩‍👧e\u{301}").unwrap();
+        assert_eq!(text.len_grapheme(), 4);
+        assert_eq!(text.slice_grapheme(1, 3).unwrap(), "👍🏽👨‍👩‍👧");
+        assert_eq!(text.slice_grapheme(3, 4).unwrap(), "e\u{301}");
+        assert!(text.slice_grapheme(3, 5).is_err());
+
+        text.delete_grapheme(2, 1).unwrap();
+        assert_eq!(text.to_string(), "a👍🏽e\u{301}");
+        text.insert_grapheme(2, "!").unwrap();
+        assert_eq!(text.to_string(), "a👍🏽!e\u{301}");
+        let image = loro_common::loro_value!({"image": "img"});
+        text.insert_embed(1, image).unwrap();
+        assert_eq!(text.len_grapheme(), 5);
+        text.delete_grapheme(2, 1).unwrap();
+        assert_eq!(text.to_string(), "a!e\u{301}");
+
+        let cursor = text.get_cursor_grapheme(2, Default::default()).unwrap();
+        text.insert(0, "xy").unwrap();
+        assert_eq!(doc.query_pos(&cursor).unwrap().current.pos, 4);
+    }
+
+    #[test]
+    fn embed_is_a_cluster_of_its_own() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "a\u{301}b").unwrap();
+        let image = loro_common::loro_value!({"image": "img"});
+        text.insert_embed(1, image).unwrap();
+        // The combining mark doesn't join the embed before it
+        assert_eq!(text.len_grapheme(), 4);
+        assert_eq!(text.slice_grapheme(2, 3).unwrap(), "\u{301}");
+        text.delete_grapheme(1, 1).unwrap();
+        assert_eq!(text.to_string(), "a\u{301}b");
+        assert_eq!(text.len_unicode(), 3);
+    }
+
+    #[test]
+    fn update_never_splits_clusters() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "👍🏽 ok").unwrap();
+        let cursor = text.get_cursor(0, Default::default()).unwrap();
+        text.update("👍🏿 ok", UpdateOptions::default()).unwrap();
+        assert_eq!(text.to_string(), "👍🏿 ok");
+        // The whole cluster is replaced, not only the skin tone modifier
+        assert!(doc.query_pos(&cursor).unwrap().update.is_some());
+    }
+}
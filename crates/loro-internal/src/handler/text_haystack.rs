@@ -0,0 +1,100 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{MaybeDetached, TextDelta, TextHandler};
+
+/// Embeds are represented as U+FFFC OBJECT REPLACEMENT CHARACTER
+const EMBED_PLACEHOLDER: char = '\u{FFFC}';
+
+/// The text content with embeds replaced by [`EMBED_PLACEHOLDER`], which is what the
+/// text searches and the grapheme queries run on.
+pub(super) struct Haystack {
+    pub(super) text: String,
+    /// Sorted byte offsets of the embeds in `text`
+    embeds: Vec<usize>,
+}
+
+impl Haystack {
+    pub(super) fn is_embed(&self, byte_offset: usize) -> bool {
+        self.embeds.binary_search(&byte_offset).is_ok()
+    }
+
+    /// The text between the embeds in order, with `None` for each embed.
+    pub(super) fn chunks(&self) -> Vec<Option<&str>> {
+        let mut ans = Vec::new();
+        let mut start = 0;
+        for &embed in self.embeds.iter() {
+            if start < embed {
+                ans.push(Some(&self.text[start..embed]));
+            }
+
+            ans.push(None);
+            start = embed + EMBED_PLACEHOLDER.len_utf8();
+        }
+
+        if start < self.text.len() {
+            ans.push(Some(&self.text[start..]));
+        }
+
+        ans
+    }
+
+    /// The grapheme clusters with their byte offsets.
+    ///
+    /// An embed is always a cluster of its own, so a combining mark after it isn't merged
+    /// into it as it would be into a real U+FFFC.
+    pub(super) fn graphemes(&self) -> Vec<(usize, &str)> {
+        let mut ans = Vec::new();
+        let mut start = 0;
+        for &embed in self.embeds.iter() {
+            let end = embed + EMBED_PLACEHOLDER.len_utf8();
+            ans.extend(
+                self.text[start..embed]
+                    .grapheme_indices(true)
+                    .map(|(i, g)| (start + i, g)),
+            );
+            ans.push((embed, &self.text[embed..end]));
+            start = end;
+        }
+
+        ans.extend(
+            self.text[start..]
+                .grapheme_indices(true)
+                .map(|(i, g)| (start + i, g)),
+        );
+        ans
+    }
+}
+
+impl TextHandler {
+    pub(super) fn haystack(&self) -> Haystack {
+        let mut text = String::new();
+        let mut embeds = Vec::new();
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                for span in t.value.iter() {
+                    if span.text.is_embed() {
+                        embeds.push(text.len());
+                        text.push(EMBED_PLACEHOLDER);
+                    } else {
+                        text.push_str(span.text.as_str());
+                    }
+                }
+            }
+            MaybeDetached::Attached(_) => {
+                for item in self.get_delta() {
+                    match item {
+                        TextDelta::Insert { insert, .. } => text.push_str(&insert),
+                        TextDelta::InsertEmbed { .. } => {
+                            embeds.push(text.len());
+                            text.push(EMBED_PLACEHOLDER);
+                        }
+                        TextDelta::Retain { .. } | TextDelta::Delete { .. } => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        Haystack { text, embeds }
+    }
+}
@@ -0,0 +1,272 @@
+use std::ops::Range;
+
+use loro_common::{LoroError, LoroResult};
+
+use super::{event_len, MaybeDetached, PosType, TextDelta, TextHandler, TextPosUnit};
+
+/// A text change expressed in lines, see [`TextHandler::line_change`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    /// The lines of the old text that are replaced
+    pub old_lines: Range<usize>,
+    /// The new content of the replaced lines, without the `\n`s.
+    ///
+    /// They start at line `old_lines.start` in the new text.
+    pub new_lines: Vec<String>,
+}
+
+impl TextHandler {
+    /// The number of lines, which is the number of `\n` plus one.
+    ///
+    /// It's read from the line index of the text, so it's O(1).
+    pub fn line_count(&self) -> usize {
+        match &self.inner {
+            MaybeDetached::Detached(t) => t.try_lock().unwrap().value.line_count(),
+            MaybeDetached::Attached(a) => {
+                a.with_state(|state| state.as_richtext_state_mut().unwrap().line_count())
+            }
+        }
+    }
+
+    /// Get the content of the line, without the `\n`.
+    ///
+    /// Inline embeds are skipped, like [`TextHandler::slice`].
+    pub fn line(&self, line: usize) -> LoroResult<String> {
+        let range = self.line_range(line, PosType::Event)?;
+        self.slice(range.start, range.end)
+    }
+
+    /// Convert a position in the given unit into a zero-based `(line, column)`.
+    ///
+    /// The column is in the same unit as `pos`.
+    pub fn pos_to_line_col(&self, pos: usize, unit: TextPosUnit) -> LoroResult<(usize, usize)> {
+        let pos_type = unit.to_pos_type();
+        let line_start = match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value
+                    .line_of(pos, pos_type)
+                    .map(|line| (line, t.value.line_start(line, pos_type).unwrap()))
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let state = state.as_richtext_state_mut().unwrap();
+                state
+                    .line_of(pos, pos_type)
+                    .map(|line| (line, state.line_start(line, pos_type).unwrap()))
+            }),
+        };
+
+        match line_start {
+            Some((line, start)) => Ok((line, pos - start)),
+            None => Err(LoroError::OutOfBound {
+                pos,
+                len: self.len_in(unit),
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            }),
+        }
+    }
+
+    /// Convert a zero-based `(line, column)` into a position in the given unit.
+    ///
+    /// The column can point to the end of the line, but not beyond.
+    pub fn line_col_to_pos(&self, line: usize, col: usize, unit: TextPosUnit) -> LoroResult<usize> {
+        let range = self.line_range(line, unit.to_pos_type())?;
+        if col > range.len() {
+            return Err(LoroError::OutOfBound {
+                pos: col,
+                len: range.len(),
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        Ok(range.start + col)
+    }
+
+    /// Express the text event `delta` as a change of lines.
+    ///
+    /// `old_line_count` is the [`TextHandler::line_count`] before the change. The text must
+    /// not have changed since the event, which holds inside the event callback. All the edits
+    /// in the delta are merged into one change. Style changes are ignored.
+    ///
+    /// Returns `None` if the content is unchanged.
+    pub fn line_change(&self, delta: &[TextDelta], old_line_count: usize) -> Option<LineChange> {
+        let mut index = 0;
+        let mut changed: Option<Range<usize>> = None;
+        for item in delta {
+            let range = match item {
+                TextDelta::Retain { retain, .. } => {
+                    index += retain;
+                    continue;
+                }
+                TextDelta::Insert { insert, .. } => index..index + event_len(insert),
+                TextDelta::InsertEmbed { .. } => index..index + 1,
+                TextDelta::Delete { .. } => index..index,
+            };
+
+            index = range.end;
+            changed = Some(match changed {
+                Some(changed) => changed.start..range.end,
+                None => range,
+            });
+        }
+
+        let changed = changed?;
+        let (start_line, end_line, line_count) = match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                (
+                    t.value.line_of(changed.start, PosType::Event)?,
+                    t.value.line_of(changed.end, PosType::Event)?,
+                    t.value.line_count(),
+                )
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let state = state.as_richtext_state_mut().unwrap();
+                Some((
+                    state.line_of(changed.start, PosType::Event)?,
+                    state.line_of(changed.end, PosType::Event)?,
+                    state.line_count(),
+                ))
+            })?,
+        };
+
+        // The lines after the change are the same in the old and the new text
+        let old_end = (end_line + 1 + old_line_count)
+            .saturating_sub(line_count)
+            .max(start_line);
+        let new_lines = (start_line..=end_line)
+            .map(|line| self.line(line).unwrap())
+            .collect();
+        Some(LineChange {
+            old_lines: start_line..old_end,
+            new_lines,
+        })
+    }
+
+    /// Get the range of the line without the `\n`.
+    fn line_range(&self, line: usize, pos_type: PosType) -> LoroResult<Range<usize>> {
+        let range = match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.line_start(line, pos_type).map(|start| {
+                    let end = match t.value.line_start(line + 1, pos_type) {
+                        Some(next) => next - 1,
+                        None => t.value.len(pos_type),
+                    };
+                    start..end
+                })
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let state = state.as_richtext_state_mut().unwrap();
+                state.line_start(line, pos_type).map(|start| {
+                    let end = match state.line_start(line + 1, pos_type) {
+                        Some(next) => next - 1,
+                        None => state.len(pos_type),
+                    };
+                    start..end
+                })
+            }),
+        };
+
+        range.ok_or_else(|| LoroError::OutOfBound {
+            pos: line,
+            len: self.line_count(),
+            info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoroDoc;
+
+    #[test]
+    fn line_index() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        assert_eq!(text.line_count(), 1);
+        text.insert(0, "fn main() {\n    😀\n}").unwrap();
+        assert_eq!(text.line_count(), 3);
+        assert_eq!(text.line(1).unwrap(), "    😀");
+        assert_eq!(text.line(2).unwrap(), "}");
+        assert!(text.line(3).is_err());
+
+        assert_eq!(
+            text.pos_to_line_col(0, TextPosUnit::Unicode).unwrap(),
+            (0, 0)
+        );
+        assert_eq!(
+            text.pos_to_line_col(11, TextPosUnit::Unicode).unwrap(),
+            (0, 11)
+        );
+        assert_eq!(
+            text.pos_to_line_col(12, TextPosUnit::Unicode).unwrap(),
+            (1, 0)
+        );
+        assert_eq!(
+            text.pos_to_line_col(17, TextPosUnit::Unicode).unwrap(),
+            (1, 5)
+        );
+        assert_eq!(
+            text.pos_to_line_col(18, TextPosUnit::Utf16).unwrap(),
+            (1, 6)
+        );
+        assert_eq!(
+            text.pos_to_line_col(19, TextPosUnit::Unicode).unwrap(),
+            (2, 1)
+        );
+        assert!(text.pos_to_line_col(20, TextPosUnit::Unicode).is_err());
+
+        assert_eq!(
+            text.line_col_to_pos(1, 5, TextPosUnit::Unicode).unwrap(),
+            17
+        );
+        assert_eq!(text.line_col_to_pos(1, 6, TextPosUnit::Utf16).unwrap(), 18);
+        assert_eq!(text.line_col_to_pos(1, 8, TextPosUnit::Utf8).unwrap(), 20);
+        assert!(text.line_col_to_pos(1, 6, TextPosUnit::Unicode).is_err());
+
+        text.delete(11, 5).unwrap();
+        assert_eq!(text.line_count(), 2);
+        assert_eq!(text.line(0).unwrap(), "fn main() {😀");
+    }
+
+    #[test]
+    fn line_change_from_delta() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "a\nb\nc\nd").unwrap();
+        let old_line_count = text.line_count();
+        // Replace "b\nc" with "x"
+        text.delete(2, 3).unwrap();
+        text.insert(2, "x").unwrap();
+        let delta = vec![
+            TextDelta::Retain {
+                retain: 2,
+                attributes: None,
+            },
+            TextDelta::Insert {
+                insert: "x".into(),
+                attributes: None,
+            },
+            TextDelta::Delete { delete: 3 },
+        ];
+        assert_eq!(
+            text.line_change(&delta, old_line_count),
+            Some(LineChange {
+                old_lines: 1..3,
+                new_lines: vec!["x".into()],
+            })
+        );
+        assert_eq!(
+            text.line_change(
+                &[TextDelta::Retain {
+                    retain: 1,
+                    attributes: None
+                }],
+                2
+            ),
+            None
+        );
+    }
+}
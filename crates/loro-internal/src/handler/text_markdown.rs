@@ -0,0 +1,595 @@
+use loro_common::{LoroError, LoroResult, LoroValue};
+
+use super::{MarkRender, TextHandler, TextRenderOptions};
+
+/// The styles that can be parsed from Markdown
+const PARSED_STYLES: [MarkRender; 5] = [
+    MarkRender::Bold,
+    MarkRender::Italic,
+    MarkRender::Strikethrough,
+    MarkRender::Code,
+    MarkRender::Link,
+];
+
+impl TextHandler {
+    /// Parse the inline Markdown and insert it at `pos`.
+    ///
+    /// The parsed styles are mapped to the style keys by `options`. The styles whose keys are
+    /// not in the doc's `StyleConfigMap` are dropped.
+    pub fn insert_markdown(
+        &self,
+        pos: usize,
+        markdown: &str,
+        options: &TextRenderOptions,
+    ) -> LoroResult<()> {
+        let parsed = parse_markdown(markdown);
+        self.insert(pos, &parsed.text)?;
+        for (start, end, render, value) in parsed.marks {
+            if let Some(key) = options.key_of(render) {
+                ignore_missing_config(self.mark(pos + start, pos + end, key.clone(), value))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse the inline Markdown and update the text to it with the minimal set of ops.
+    ///
+    /// The text is updated like [`TextHandler::update`], then only the ranges whose styles
+    /// differ are marked or unmarked. The styles mapped by `options` that are not in the
+    /// Markdown are removed. The styles whose keys are not in the doc's `StyleConfigMap` are
+    /// dropped.
+    pub fn update_by_markdown(
+        &self,
+        markdown: &str,
+        options: &TextRenderOptions,
+    ) -> LoroResult<()> {
+        let parsed = parse_markdown(markdown);
+        if self.to_string() != parsed.text {
+            // No timeout is set, so it never fails
+            self.update(&parsed.text, Default::default()).unwrap();
+        }
+
+        let len = event_len(&parsed.text);
+        let delta = self.get_richtext_value();
+        for render in PARSED_STYLES {
+            let Some(key) = options.key_of(render) else {
+                continue;
+            };
+
+            let mut target: Vec<Option<LoroValue>> = vec![None; len];
+            for (start, end, r, value) in parsed.marks.iter() {
+                if *r == render {
+                    target[*start..*end].fill(Some(value.clone()));
+                }
+            }
+            let current = current_styles(&delta, key, len);
+            let mut i = 0;
+            while i < len {
+                if current[i] == target[i] {
+                    i += 1;
+                    continue;
+                }
+
+                let end = (i..len)
+                    .find(|&j| target[j] != target[i] || current[j] == target[j])
+                    .unwrap_or(len);
+                let result = match &target[i] {
+                    Some(value) => self.mark(i, end, key.clone(), value.clone()),
+                    None => self.unmark(i, end, key.clone()),
+                };
+                ignore_missing_config(result)?;
+                i = end;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn ignore_missing_config(result: LoroResult<()>) -> LoroResult<()> {
+    match result {
+        Err(LoroError::StyleConfigMissing(_)) => Ok(()),
+        r => r,
+    }
+}
+
+fn event_len(s: &str) -> usize {
+    if cfg!(feature = "wasm") {
+        s.encode_utf16().count()
+    } else {
+        s.chars().count()
+    }
+}
+
+/// The value of the style at each position of the rich text value
+fn current_styles(delta: &LoroValue, key: &str, len: usize) -> Vec<Option<LoroValue>> {
+    let mut ans = Vec::with_capacity(len);
+    if let LoroValue::List(items) = delta {
+        for item in items.iter() {
+            let LoroValue::Map(item) = item else {
+                continue;
+            };
+            let Some(insert) = item.get("insert") else {
+                continue;
+            };
+            let value = match item.get("attributes") {
+                Some(LoroValue::Map(attributes)) => attributes
+                    .get(key)
+                    .filter(|v| !matches!(v, LoroValue::Null | LoroValue::Bool(false)))
+                    .cloned(),
+                _ => None,
+            };
+            // An inline embed occupies a single position
+            let len = match insert {
+                LoroValue::String(text) => event_len(text),
+                _ => 1,
+            };
+            ans.extend(std::iter::repeat(value).take(len));
+        }
+    }
+
+    ans.resize(len, None);
+    ans
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ParsedMarkdown {
+    pub text: String,
+    /// The styled ranges in event indexes
+    pub marks: Vec<(usize, usize, MarkRender, LoroValue)>,
+}
+
+/// Parse the inline subset of CommonMark: emphasis, strong emphasis, code spans, links,
+/// autolinks and backslash escapes, plus GFM strikethrough.
+///
+/// Only the inline syntax is supported. Block syntax such as headings, lists, block quotes
+/// and code blocks is kept as plain text, and reference links, raw HTML and entities are
+/// not parsed.
+pub(crate) fn parse_markdown(markdown: &str) -> ParsedMarkdown {
+    let mut ans = ParsedMarkdown::default();
+    let mut pos = 0;
+    emit(&parse_inline(markdown), &mut ans, &mut pos);
+    ans
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    /// A run of `*`, `_` or `~`
+    Delim {
+        ch: char,
+        /// The number of the delimiters that are not matched yet
+        len: usize,
+        /// The length of the whole run
+        run: usize,
+        can_open: bool,
+        can_close: bool,
+    },
+    Styled {
+        render: MarkRender,
+        value: LoroValue,
+        children: Vec<Token>,
+    },
+}
+
+fn emit(tokens: &[Token], ans: &mut ParsedMarkdown, pos: &mut usize) {
+    for token in tokens {
+        match token {
+            Token::Text(s) => {
+                ans.text.push_str(s);
+                *pos += event_len(s);
+            }
+            Token::Delim { ch, len, .. } => {
+                for _ in 0..*len {
+                    ans.text.push(*ch);
+                }
+                *pos += len;
+            }
+            Token::Styled {
+                render,
+                value,
+                children,
+            } => {
+                let start = *pos;
+                emit(children, ans, pos);
+                if *pos > start {
+                    ans.marks.push((start, *pos, *render, value.clone()));
+                }
+            }
+        }
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_alphanumeric() && !c.is_whitespace())
+}
+
+fn parse_inline(s: &str) -> Vec<Token> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if chars.get(i + 1).is_some_and(|x| x.is_ascii_punctuation()) => {
+                buf.push(chars[i + 1]);
+                i += 2;
+            }
+            '`' => {
+                let run = run_len(&chars, i);
+                match find_backticks(&chars, i + run, run) {
+                    Some(end) => {
+                        flush(&mut buf, &mut tokens);
+                        tokens.push(Token::Styled {
+                            render: MarkRender::Code,
+                            value: true.into(),
+                            children: vec![Token::Text(code_content(&chars[i + run..end]))],
+                        });
+                        i = end + run;
+                    }
+                    None => {
+                        buf.extend(&chars[i..i + run]);
+                        i += run;
+                    }
+                }
+            }
+            '*' | '_' | '~' => {
+                let run = run_len(&chars, i);
+                let prev = if i == 0 { None } else { Some(chars[i - 1]) };
+                let next = chars.get(i + run).copied();
+                let prev_space = prev.map_or(true, |x| x.is_whitespace());
+                let next_space = next.map_or(true, |x| x.is_whitespace());
+                let prev_punct = prev.is_some_and(is_punctuation);
+                let next_punct = next.is_some_and(is_punctuation);
+                let left = !next_space && (!next_punct || prev_space || prev_punct);
+                let right = !prev_space && (!prev_punct || next_space || next_punct);
+                let (can_open, can_close) = if c == '_' {
+                    (
+                        left && (!right || prev_punct),
+                        right && (!left || next_punct),
+                    )
+                } else {
+                    (left, right)
+                };
+                flush(&mut buf, &mut tokens);
+                tokens.push(Token::Delim {
+                    ch: c,
+                    len: run,
+                    run,
+                    can_open,
+                    can_close,
+                });
+                i += run;
+            }
+            '[' => match parse_link(&chars, i) {
+                Some((label, url, next)) => {
+                    flush(&mut buf, &mut tokens);
+                    tokens.push(Token::Styled {
+                        render: MarkRender::Link,
+                        value: url.into(),
+                        children: parse_inline(&label),
+                    });
+                    i = next;
+                }
+                None => {
+                    buf.push(c);
+                    i += 1;
+                }
+            },
+            '<' => match parse_autolink(&chars, i) {
+                Some((url, next)) => {
+                    flush(&mut buf, &mut tokens);
+                    tokens.push(Token::Styled {
+                        render: MarkRender::Link,
+                        value: url.as_str().into(),
+                        children: vec![Token::Text(url)],
+                    });
+                    i = next;
+                }
+                None => {
+                    buf.push(c);
+                    i += 1;
+                }
+            },
+            c => {
+                buf.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    flush(&mut buf, &mut tokens);
+    process_emphasis(&mut tokens);
+    tokens
+}
+
+fn flush(buf: &mut String, tokens: &mut Vec<Token>) {
+    if !buf.is_empty() {
+        tokens.push(Token::Text(std::mem::take(buf)));
+    }
+}
+
+fn run_len(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .take_while(|&&x| x == chars[start])
+        .count()
+}
+
+/// Find the start of the next backtick run of exactly `len`
+fn find_backticks(chars: &[char], mut i: usize, len: usize) -> Option<usize> {
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let run = run_len(chars, i);
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+
+    None
+}
+
+fn code_content(chars: &[char]) -> String {
+    let s: String = chars.iter().collect();
+    if s.len() >= 2 && s.starts_with(' ') && s.ends_with(' ') && !s.trim().is_empty() {
+        s[1..s.len() - 1].to_string()
+    } else {
+        s
+    }
+}
+
+/// Parse `[label](url)` at `start`. Return the label, the url and the index after the link.
+fn parse_link(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let label_end = find_closing(chars, start + 1, '[', ']')?;
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+
+    let dest_end = find_closing(chars, label_end + 2, '(', ')')?;
+    let dest: String = chars[label_end + 2..dest_end].iter().collect();
+    let dest = dest.trim();
+    let url = match dest.strip_prefix('<') {
+        Some(rest) => rest.split('>').next().unwrap_or_default(),
+        // The title after the url is ignored
+        None => dest.split_whitespace().next().unwrap_or_default(),
+    };
+    let label = chars[start + 1..label_end].iter().collect();
+    Some((label, unescape(url), dest_end + 1))
+}
+
+/// Find the index of the closing bracket that matches the opened one before `i`
+fn find_closing(chars: &[char], mut i: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// Parse `<scheme:rest>` at `start`. Return the url and the index after the autolink.
+fn parse_autolink(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let end = start + 1 + chars[start + 1..].iter().position(|&c| c == '>')?;
+    let url: String = chars[start + 1..end].iter().collect();
+    let (scheme, _) = url.split_once(':')?;
+    if scheme.is_empty()
+        || !scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        || url.chars().any(|c| c.is_whitespace() || c == '<')
+    {
+        return None;
+    }
+
+    Some((url, end + 1))
+}
+
+fn unescape(s: &str) -> String {
+    let mut ans = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && next.is_ascii_punctuation() => {
+                ans.push(next);
+                chars.next();
+            }
+            _ => ans.push(c),
+        }
+    }
+    ans
+}
+
+/// Match the delimiter runs into styled tokens, following the CommonMark algorithm.
+///
+/// When a closer is matched, the tokens between it and its opener become the children
+/// of a new styled token. The unmatched delimiters among them stay literal.
+fn process_emphasis(tokens: &mut Vec<Token>) {
+    let mut c = 0;
+    while c < tokens.len() {
+        let (ch, closer_len, closer_run, closer_can_open) = match &tokens[c] {
+            Token::Delim {
+                ch,
+                len,
+                run,
+                can_open,
+                can_close: true,
+            } if *len > 0 => (*ch, *len, *run, *can_open),
+            _ => {
+                c += 1;
+                continue;
+            }
+        };
+
+        let opener = (0..c).rev().find_map(|o| match &tokens[o] {
+            Token::Delim {
+                ch: x,
+                len,
+                run,
+                can_open: true,
+                can_close,
+            } if *x == ch
+                && *len > 0
+                && (ch != '~' || (*len >= 2 && closer_len >= 2))
+                && !is_multiple_of_3_pair(*run, *can_close, closer_run, closer_can_open) =>
+            {
+                Some((o, *len))
+            }
+            _ => None,
+        });
+        let Some((o, opener_len)) = opener else {
+            c += 1;
+            continue;
+        };
+
+        let n = if opener_len >= 2 && closer_len >= 2 {
+            2
+        } else {
+            1
+        };
+        let render = match (ch, n) {
+            ('~', _) => MarkRender::Strikethrough,
+            (_, 2) => MarkRender::Bold,
+            _ => MarkRender::Italic,
+        };
+        for i in [o, c] {
+            if let Token::Delim { len, .. } = &mut tokens[i] {
+                *len -= n;
+            }
+        }
+        let children: Vec<Token> = tokens.drain(o + 1..c).collect();
+        tokens.insert(
+            o + 1,
+            Token::Styled {
+                render,
+                value: true.into(),
+                children,
+            },
+        );
+        // The closer may still have delimiters to match
+        c = o + 2;
+    }
+}
+
+/// The "multiple of 3" rule: if either run can both open and close, the runs can't match
+/// when the sum of their lengths is a multiple of 3, unless both lengths are
+fn is_multiple_of_3_pair(
+    opener_run: usize,
+    opener_can_close: bool,
+    closer_run: usize,
+    closer_can_open: bool,
+) -> bool {
+    (opener_can_close || closer_can_open)
+        && (opener_run + closer_run) % 3 == 0
+        && !(opener_run % 3 == 0 && closer_run % 3 == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoroDoc;
+
+    fn marks(md: &str) -> (String, Vec<(usize, usize, MarkRender)>) {
+        let parsed = parse_markdown(md);
+        let mut marks: Vec<_> = parsed.marks.iter().map(|m| (m.0, m.1, m.2)).collect();
+        marks.sort_unstable_by_key(|m| (m.0, m.1));
+        (parsed.text, marks)
+    }
+
+    #[test]
+    fn parse_inline_styles() {
+        assert_eq!(
+            marks("**Hello _w_**_orld_"),
+            (
+                "Hello world".to_string(),
+                vec![
+                    (0, 7, MarkRender::Bold),
+                    (6, 7, MarkRender::Italic),
+                    (7, 11, MarkRender::Italic)
+                ]
+            )
+        );
+        assert_eq!(
+            marks("a `b **c**` \\*d\\* ~~e~~ snake_case_name"),
+            (
+                "a b **c** *d* e snake_case_name".to_string(),
+                vec![
+                    (2, 9, MarkRender::Code),
+                    (14, 15, MarkRender::Strikethrough)
+                ]
+            )
+        );
+        assert_eq!(marks("***a*").0, "**a");
+        assert_eq!(marks("2 * 3 * 4").0, "2 * 3 * 4");
+        // The "multiple of 3" rule
+        assert_eq!(
+            marks("*foo**bar**baz*"),
+            (
+                "foobarbaz".to_string(),
+                vec![(0, 9, MarkRender::Italic), (3, 6, MarkRender::Bold)]
+            )
+        );
+        assert_eq!(
+            marks("*foo**bar*"),
+            ("foo**bar".to_string(), vec![(0, 8, MarkRender::Italic)])
+        );
+
+        let parsed = parse_markdown("see [the *docs*](https://a.b/(c) \"title\") or <https://x.y>");
+        assert_eq!(parsed.text, "see the docs or https://x.y");
+        assert!(parsed
+            .marks
+            .contains(&(4, 12, MarkRender::Link, "https://a.b/(c)".into())));
+        assert!(parsed
+            .marks
+            .contains(&(8, 12, MarkRender::Italic, true.into())));
+        assert!(parsed
+            .marks
+            .contains(&(16, 27, MarkRender::Link, "https://x.y".into())));
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        let options = TextRenderOptions::default();
+        let md = "**Hello *[world](https://a.b/)*** `x*y`\n**next** \\# 1";
+        text.insert_markdown(0, md, &options).unwrap();
+        assert_eq!(text.to_string(), "Hello world x*y\nnext # 1");
+        assert_eq!(text.to_markdown(&options), md);
+    }
+
+    #[test]
+    fn update_by_markdown_is_minimal() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        let options = TextRenderOptions::default();
+        let update = |md: &str| text.update_by_markdown(md, &options).unwrap();
+        update("Hello **world**");
+        doc.commit_then_renew();
+        let ops = doc.len_ops();
+        update("Oh, Hello **world**");
+        doc.commit_then_renew();
+        // Only "Oh, " is inserted
+        assert_eq!(doc.len_ops(), ops + 4);
+        assert_eq!(text.to_markdown(&options), "Oh, Hello **world**");
+
+        update("*Hello* world!");
+        assert_eq!(text.to_markdown(&options), "*Hello* world!");
+    }
+}
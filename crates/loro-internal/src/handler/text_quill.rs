@@ -0,0 +1,162 @@
+use generic_btree::rle::HasLength;
+use loro_common::{LoroResult, LoroValue};
+use loro_delta::DeltaItem;
+
+use super::{TextDelta, TextHandler};
+use crate::delta::{QuillAttributes, QuillDelta, QuillValue};
+
+impl TextHandler {
+    /// Get the text as a [QuillDelta] that only contains inserts.
+    pub fn to_quill_delta(&self) -> QuillDelta {
+        let mut ans = QuillDelta::new();
+        if let LoroValue::List(items) = self.get_richtext_value() {
+            for item in items.iter() {
+                let LoroValue::Map(item) = item else {
+                    continue;
+                };
+                let Some(insert) = item.get("insert") else {
+                    continue;
+                };
+                let attributes = match item.get("attributes") {
+                    Some(LoroValue::Map(attributes)) => attributes
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect(),
+                    _ => QuillAttributes::new(),
+                };
+                match insert {
+                    LoroValue::String(text) => ans.insert(text, attributes),
+                    embed => ans.insert_embed(embed.clone(), attributes),
+                };
+            }
+        }
+
+        ans
+    }
+
+    /// Apply a [QuillDelta] to the text.
+    ///
+    /// A `null` attribute on a retain removes the style from the range. Embeds are inserted
+    /// as inline embeds of the text.
+    pub fn apply_quill_delta(&self, delta: &QuillDelta) -> LoroResult<()> {
+        let mut ans = Vec::new();
+        // The ranges whose styles are removed by `null` attributes
+        let mut removed = Vec::new();
+        let mut index = 0;
+        for item in delta.iter() {
+            match item {
+                DeltaItem::Retain { len, attr } => {
+                    let mut attributes = (**attr).clone();
+                    attributes.retain(|key, v| {
+                        if v.is_null() {
+                            removed.push((index, index + len, key.clone()));
+                        }
+                        !v.is_null()
+                    });
+                    ans.push(TextDelta::Retain {
+                        retain: *len,
+                        attributes: (!attributes.is_empty()).then_some(attributes),
+                    });
+                    index += len;
+                }
+                DeltaItem::Replace {
+                    value,
+                    attr,
+                    delete,
+                } => {
+                    match value {
+                        QuillValue::Text(text) if text.is_empty() => {}
+                        QuillValue::Text(text) => {
+                            let mut attributes = (**attr).clone();
+                            attributes.retain(|_, v| !v.is_null());
+                            ans.push(TextDelta::Insert {
+                                insert: text.clone(),
+                                attributes: Some(attributes),
+                            });
+                        }
+                        QuillValue::Embed(value) => {
+                            let mut attributes = (**attr).clone();
+                            attributes.retain(|_, v| !v.is_null());
+                            ans.push(TextDelta::InsertEmbed {
+                                insert: value.clone(),
+                                attributes: Some(attributes),
+                            });
+                        }
+                    }
+                    index += value.rle_len();
+                    if *delete > 0 {
+                        ans.push(TextDelta::Delete { delete: *delete });
+                    }
+                }
+            }
+        }
+
+        self.apply_delta(&ans)?;
+        let len = self.len_event();
+        for (start, end, key) in removed {
+            let end = end.min(len);
+            if start < end {
+                self.unmark(start, end, key)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{value::ToJson, LoroDoc};
+
+    use super::*;
+
+    #[test]
+    fn quill_delta_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        let delta: QuillDelta = serde_json::from_value(json!({
+            "ops": [
+                { "insert": "Hello", "attributes": { "bold": true } },
+                { "insert": " world" },
+            ]
+        }))
+        .unwrap();
+        text.apply_quill_delta(&delta).unwrap();
+        assert_eq!(text.to_quill_delta(), delta);
+
+        let change: QuillDelta = serde_json::from_value(json!([
+            { "retain": 2, "attributes": { "bold": null } },
+            { "retain": 4, "attributes": { "italic": true } },
+            { "delete": 1 },
+        ]))
+        .unwrap();
+        text.apply_quill_delta(&change).unwrap();
+        let mut expected = delta.clone();
+        expected.compose(&change);
+        assert_eq!(text.to_quill_delta(), expected);
+        assert_eq!(
+            expected.to_json_value(),
+            json!({
+                "ops": [
+                    { "insert": "He" },
+                    { "insert": "llo", "attributes": { "bold": true, "italic": true } },
+                    { "insert": " ", "attributes": { "italic": true } },
+                    { "insert": "orld" },
+                ]
+            })
+        );
+
+        let mut embed = QuillDelta::new();
+        embed.retain(2, QuillAttributes::new());
+        embed.insert_embed(
+            serde_json::from_value::<LoroValue>(json!({ "image": "image.png" })).unwrap(),
+            QuillAttributes::new(),
+        );
+        text.apply_quill_delta(&embed).unwrap();
+        expected.compose(&embed);
+        assert_eq!(text.to_quill_delta(), expected);
+        assert_eq!(text.to_string(), "Hello orld");
+    }
+}
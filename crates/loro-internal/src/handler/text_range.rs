@@ -0,0 +1,50 @@
+use super::{MaybeDetached, PosType, TextHandler, TextPosUnit};
+use crate::cursor::RangeCursor;
+
+impl TextHandler {
+    /// Get a stable range for the event index range `start..end`.
+    ///
+    /// Returns `None` if the text is detached or the range is invalid.
+    pub fn get_range_cursor(&self, start: usize, end: usize) -> Option<RangeCursor> {
+        if end > self.len_event() {
+            return None;
+        }
+
+        RangeCursor::from_range(start..end, |pos, side| self.get_cursor(pos, side))
+    }
+
+    /// Convert an event index into an index in the given unit.
+    pub(crate) fn event_index_to_pos(&self, index: usize, unit: TextPosUnit) -> usize {
+        let pos_type = unit.to_pos_type();
+        match &self.inner {
+            MaybeDetached::Detached(t) => t
+                .try_lock()
+                .unwrap()
+                .value
+                .event_index_to_index(index, pos_type),
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .event_index_to_index(index, pos_type)
+            }),
+        }
+    }
+
+    /// The event index right after the element at the event index `index`.
+    pub(crate) fn event_index_after(&self, index: usize) -> usize {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                let unicode_index = t.value.event_index_to_unicode_index(index);
+                t.value
+                    .index_to_event_index(unicode_index + 1, PosType::Unicode)
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let state = state.as_richtext_state_mut().unwrap();
+                let unicode_index = state.event_index_to_unicode_index(index);
+                state.index_to_event_index(unicode_index + 1, PosType::Unicode)
+            }),
+        }
+    }
+}
@@ -0,0 +1,593 @@
+use std::{cmp::Reverse, fmt::Debug, sync::Arc};
+
+use fxhash::FxHashMap;
+use loro_common::{InternalString, LoroValue};
+
+use super::TextHandler;
+
+/// The output format of [`TextHandler::to_markdown`] and [`TextHandler::to_html`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Markdown,
+    Html,
+}
+
+/// How a style key is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkRender {
+    /// `**text**` or `<strong>text</strong>`
+    Bold,
+    /// `*text*` or `<em>text</em>`
+    Italic,
+    /// `<u>text</u>` in both formats, as Markdown has no underline
+    Underline,
+    /// `~~text~~` or `<s>text</s>`
+    Strikethrough,
+    /// `` `text` `` or `<code>text</code>`
+    Code,
+    /// `[text](url)` or `<a href="url">text</a>`. The style value must be the url string.
+    Link,
+}
+
+/// Render the style keys that are not in the mapping table.
+///
+/// It receives the format, the style key and the style value, and returns the opening and closing
+/// strings. Returning `None` leaves the text unstyled.
+pub type CustomMarkRender =
+    Arc<dyn Fn(TextFormat, &str, &LoroValue) -> Option<(String, String)> + Send + Sync>;
+
+/// The mapping table from style keys to their renderings.
+///
+/// By default, it maps `bold`, `italic`, `underline`, `strikethrough`, `code` and `link`
+/// to the renderings of the same names.
+#[derive(Clone)]
+pub struct TextRenderOptions {
+    marks: FxHashMap<InternalString, MarkRender>,
+    custom: Option<CustomMarkRender>,
+}
+
+impl Default for TextRenderOptions {
+    fn default() -> Self {
+        Self::empty()
+            .with_mark("bold", MarkRender::Bold)
+            .with_mark("italic", MarkRender::Italic)
+            .with_mark("underline", MarkRender::Underline)
+            .with_mark("strikethrough", MarkRender::Strikethrough)
+            .with_mark("code", MarkRender::Code)
+            .with_mark("link", MarkRender::Link)
+    }
+}
+
+impl Debug for TextRenderOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextRenderOptions")
+            .field("marks", &self.marks)
+            .field("custom", &self.custom.is_some())
+            .finish()
+    }
+}
+
+impl TextRenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options without any mapping, so all the styles are ignored unless a custom renderer is set
+    pub fn empty() -> Self {
+        Self {
+            marks: Default::default(),
+            custom: None,
+        }
+    }
+
+    pub fn with_mark(mut self, key: impl Into<InternalString>, render: MarkRender) -> Self {
+        self.marks.insert(key.into(), render);
+        self
+    }
+
+    /// The style key mapped to `render`. The smallest key is used if there are several.
+    pub(super) fn key_of(&self, render: MarkRender) -> Option<&InternalString> {
+        self.marks
+            .iter()
+            .filter(|(_, r)| **r == render)
+            .map(|(k, _)| k)
+            .min()
+    }
+
+    pub fn with_custom(
+        mut self,
+        f: impl Fn(TextFormat, &str, &LoroValue) -> Option<(String, String)> + Send + Sync + 'static,
+    ) -> Self {
+        self.custom = Some(Arc::new(f));
+        self
+    }
+}
+
+impl TextHandler {
+    /// Render the rich text as Markdown.
+    ///
+    /// Overlapping styles are closed and reopened so that the output is always well nested.
+    /// Styles never span a newline, and the whitespace at the edges of a styled range is
+    /// moved out of it, as CommonMark requires.
+    ///
+    /// Inline embeds have no generic rendering, so they are left out of the output.
+    pub fn to_markdown(&self, options: &TextRenderOptions) -> String {
+        render(&self.get_richtext_value(), TextFormat::Markdown, options)
+    }
+
+    /// Render the rich text as an HTML fragment. Newlines are rendered as `<br>`.
+    ///
+    /// Overlapping styles are closed and reopened so that the output is always well nested.
+    /// Inline embeds are left out of the output.
+    pub fn to_html(&self, options: &TextRenderOptions) -> String {
+        render(&self.get_richtext_value(), TextFormat::Html, options)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Mark {
+    key: InternalString,
+    value: LoroValue,
+    open: String,
+    close: String,
+    /// Whether other styles can't be nested inside, e.g. Markdown code spans
+    innermost: bool,
+    /// Where the styled content starts in the output, set when the mark is opened
+    content_start: usize,
+}
+
+impl Mark {
+    fn is_same(&self, other: &Mark) -> bool {
+        self.key == other.key && self.value == other.value
+    }
+}
+
+struct Segment {
+    text: String,
+    marks: Vec<Mark>,
+}
+
+fn render(delta: &LoroValue, format: TextFormat, options: &TextRenderOptions) -> String {
+    let segments = collect_segments(delta, format, options);
+    let mut out = String::new();
+    let mut stack: Vec<Mark> = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        if format == TextFormat::Markdown {
+            if segment.text == "\n" {
+                close_marks(&mut out, &mut stack, 0, format);
+                out.push('\n');
+                continue;
+            }
+
+            if segment.text.trim().is_empty() {
+                // Whitespace doesn't need to be styled. Keeping the styles open avoids
+                // closing and reopening them around it.
+                out.push_str(&segment.text);
+                continue;
+            }
+        }
+
+        // Keep the longest prefix of the opened styles that are still active
+        let mut keep = stack
+            .iter()
+            .take_while(|m| segment.marks.iter().any(|x| x.is_same(m)))
+            .count();
+        let need_open = segment
+            .marks
+            .iter()
+            .any(|m| !stack[..keep].iter().any(|x| x.is_same(m)));
+        if need_open {
+            if let Some(pos) = stack[..keep].iter().position(|m| m.innermost) {
+                keep = pos;
+            }
+        }
+        close_marks(&mut out, &mut stack, keep, format);
+
+        let mut new_marks: Vec<&Mark> = segment
+            .marks
+            .iter()
+            .filter(|m| !stack.iter().any(|x| x.is_same(m)))
+            .collect();
+        // The styles that last longer are opened first, so they are reopened less often
+        new_marks.sort_by_cached_key(|m| {
+            (
+                m.innermost,
+                Reverse(run_len(&segments[i..], m)),
+                m.key.clone(),
+            )
+        });
+        let text = if format == TextFormat::Markdown && !new_marks.is_empty() {
+            let trimmed = segment.text.trim_start();
+            out.push_str(&segment.text[..segment.text.len() - trimmed.len()]);
+            trimmed
+        } else {
+            segment.text.as_str()
+        };
+        for m in new_marks {
+            if format == TextFormat::Markdown && m.open.starts_with('[') && out.ends_with('!') {
+                // Otherwise the link would be read as an image
+                out.insert(out.len() - 1, '\\');
+            }
+            out.push_str(&m.open);
+            stack.push(Mark {
+                content_start: out.len(),
+                ..m.clone()
+            });
+        }
+
+        let in_code = stack.iter().any(|m| m.innermost);
+        push_text(&mut out, text, format, in_code);
+    }
+
+    close_marks(&mut out, &mut stack, 0, format);
+    out
+}
+
+/// The number of segments from the start that have the same style
+fn run_len(segments: &[Segment], mark: &Mark) -> usize {
+    segments
+        .iter()
+        .take_while(|s| s.marks.iter().any(|m| m.is_same(mark)))
+        .count()
+}
+
+fn close_marks(out: &mut String, stack: &mut Vec<Mark>, keep: usize, format: TextFormat) {
+    if stack.len() <= keep {
+        return;
+    }
+
+    let trailing = if format == TextFormat::Markdown {
+        let len = out.trim_end_matches([' ', '\t']).len();
+        out.split_off(len)
+    } else {
+        String::new()
+    };
+    while stack.len() > keep {
+        let mark = stack.pop().unwrap();
+        if format == TextFormat::Markdown && mark.innermost {
+            fence_code_span(out, &mark);
+        } else {
+            out.push_str(&mark.close);
+        }
+    }
+    out.push_str(&trailing);
+}
+
+/// Close the Markdown code span with a backtick fence longer than any backtick run inside it,
+/// as backslash escapes don't work in code spans
+fn fence_code_span(out: &mut String, mark: &Mark) {
+    let content = out.split_off(mark.content_start);
+    out.truncate(mark.content_start - mark.open.len());
+    let mut longest = 0;
+    let mut run = 0;
+    for c in content.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+
+    let fence = "`".repeat(longest + 1);
+    // A space is stripped from both sides when parsing, so the content can start or end
+    // with a backtick
+    let pad = if content.starts_with('`') || content.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    out.push_str(&fence);
+    out.push_str(pad);
+    out.push_str(&content);
+    out.push_str(pad);
+    out.push_str(&fence);
+}
+
+fn collect_segments(
+    delta: &LoroValue,
+    format: TextFormat,
+    options: &TextRenderOptions,
+) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let LoroValue::List(items) = delta else {
+        return segments;
+    };
+
+    for item in items.iter() {
+        let LoroValue::Map(item) = item else {
+            continue;
+        };
+        // Inline embeds are not rendered
+        let Some(LoroValue::String(text)) = item.get("insert") else {
+            continue;
+        };
+
+        let mut marks = Vec::new();
+        if let Some(LoroValue::Map(attributes)) = item.get("attributes") {
+            let mut attributes: Vec<_> = attributes.iter().collect();
+            attributes.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (key, value) in attributes {
+                if let Some(mark) = to_mark(key, value, format, options) {
+                    marks.push(mark);
+                }
+            }
+        }
+
+        if format == TextFormat::Markdown {
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    segments.push(Segment {
+                        text: "\n".to_string(),
+                        marks: Vec::new(),
+                    });
+                }
+                if !line.is_empty() {
+                    segments.push(Segment {
+                        text: line.to_string(),
+                        marks: marks.clone(),
+                    });
+                }
+            }
+        } else {
+            segments.push(Segment {
+                text: text.to_string(),
+                marks,
+            });
+        }
+    }
+
+    segments
+}
+
+fn to_mark(
+    key: &str,
+    value: &LoroValue,
+    format: TextFormat,
+    options: &TextRenderOptions,
+) -> Option<Mark> {
+    if matches!(value, LoroValue::Null | LoroValue::Bool(false)) {
+        return None;
+    }
+
+    let mut innermost = false;
+    let (open, close) = match options.marks.get(key) {
+        Some(render) => match (render, format) {
+            (MarkRender::Bold, TextFormat::Markdown) => ("**".into(), "**".into()),
+            (MarkRender::Bold, TextFormat::Html) => ("<strong>".into(), "</strong>".into()),
+            (MarkRender::Italic, TextFormat::Markdown) => ("*".into(), "*".into()),
+            (MarkRender::Italic, TextFormat::Html) => ("<em>".into(), "</em>".into()),
+            (MarkRender::Underline, _) => ("<u>".into(), "</u>".into()),
+            (MarkRender::Strikethrough, TextFormat::Markdown) => ("~~".into(), "~~".into()),
+            (MarkRender::Strikethrough, TextFormat::Html) => ("<s>".into(), "</s>".into()),
+            (MarkRender::Code, TextFormat::Markdown) => {
+                innermost = true;
+                ("`".into(), "`".into())
+            }
+            (MarkRender::Code, TextFormat::Html) => ("<code>".into(), "</code>".into()),
+            (MarkRender::Link, _) => {
+                let LoroValue::String(url) = value else {
+                    return None;
+                };
+                match format {
+                    TextFormat::Markdown => ("[".into(), format!("]({})", escape_url(url))),
+                    TextFormat::Html => {
+                        (format!("<a href=\"{}\">", escape_html(url)), "</a>".into())
+                    }
+                }
+            }
+        },
+        None => (options.custom.as_ref()?)(format, key, value)?,
+    };
+
+    Some(Mark {
+        key: key.into(),
+        value: value.clone(),
+        open,
+        close,
+        innermost,
+        content_start: 0,
+    })
+}
+
+fn push_text(out: &mut String, text: &str, format: TextFormat, in_code: bool) {
+    match format {
+        TextFormat::Markdown if in_code => out.push_str(text),
+        TextFormat::Markdown => {
+            // The markers of lists and headings are only special at the start of a line
+            let line_start = out.rfind('\n').map_or(0, |i| i + 1);
+            let block_marker = if out[line_start..].chars().all(|c| c == ' ' || c == '\t') {
+                let trimmed = text.trim_start_matches([' ', '\t']);
+                let offset = text.len() - trimmed.len();
+                let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+                match trimmed[digits..].chars().next() {
+                    Some('-' | '+') if digits == 0 => Some(offset),
+                    Some('.' | ')') if digits > 0 => Some(offset + digits),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            for (i, c) in text.char_indices() {
+                if block_marker == Some(i)
+                    || matches!(
+                        c,
+                        '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~' | '#'
+                    )
+                {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+        }
+        TextFormat::Html => {
+            for c in text.chars() {
+                match c {
+                    '\n' => out.push_str("<br>"),
+                    c => push_html_char(out, c),
+                }
+            }
+        }
+    }
+}
+
+fn push_html_char(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        '\'' => out.push_str("&#39;"),
+        c => out.push(c),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut ans = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_html_char(&mut ans, c);
+    }
+    ans
+}
+
+fn escape_url(s: &str) -> String {
+    let mut ans = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ' ' | '(' | ')' | '<' | '>') || c.is_control() {
+            for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                ans.push_str(&format!("%{:02X}", b));
+            }
+        } else {
+            ans.push(c);
+        }
+    }
+    ans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoroDoc;
+
+    fn text_with_marks(s: &str, marks: &[(usize, usize, &str, LoroValue)]) -> TextHandler {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, s).unwrap();
+        for (start, end, key, value) in marks {
+            text.mark(*start, *end, *key, value.clone()).unwrap();
+        }
+        text
+    }
+
+    #[test]
+    fn overlapping_marks() {
+        let text = text_with_marks(
+            "Hello world",
+            &[(0, 7, "bold", true.into()), (6, 11, "italic", true.into())],
+        );
+        let options = TextRenderOptions::default();
+        assert_eq!(text.to_markdown(&options), "**Hello *w****orld*");
+        assert_eq!(
+            text.to_html(&options),
+            "<strong>Hello <em>w</em></strong><em>orld</em>"
+        );
+    }
+
+    #[test]
+    fn nested_marks_and_whitespace() {
+        let text = text_with_marks(
+            "Hello world\nnext",
+            &[
+                (0, 16, "bold", true.into()),
+                (5, 11, "italic", true.into()),
+                (6, 11, "link", "https://a.b/(c)".into()),
+            ],
+        );
+        let options = TextRenderOptions::default();
+        assert_eq!(
+            text.to_markdown(&options),
+            "**Hello *[world](https://a.b/%28c%29)***\n**next**"
+        );
+        assert_eq!(
+            text.to_html(&options),
+            "<strong>Hello<em> <a href=\"https://a.b/(c)\">world</a></em><br>next</strong>"
+        );
+    }
+
+    #[test]
+    fn code_and_custom_marks() {
+        let text = text_with_marks(
+            "a*b c",
+            &[
+                (0, 3, "code", true.into()),
+                (0, 5, "bold", true.into()),
+                (4, 5, "highlight", "red".into()),
+            ],
+        );
+        let options = TextRenderOptions::default().with_custom(|format, key, value| {
+            match (format, key, value) {
+                (TextFormat::Html, "highlight", LoroValue::String(c)) => Some((
+                    format!("<span style=\"background:{}\">", c.as_str()),
+                    "</span>".into(),
+                )),
+                _ => None,
+            }
+        });
+        assert_eq!(text.to_markdown(&options), "**`a*b` c**");
+        assert_eq!(
+            text.to_html(&options),
+            "<strong><code>a*b</code> <span style=\"background:red\">c</span></strong>"
+        );
+    }
+
+    #[test]
+    fn intraword_italic_and_links() {
+        let text = text_with_marks(
+            "Hello Wow!link",
+            &[
+                (3, 5, "italic", true.into()),
+                (10, 14, "link", "https://a.b/<c>\td".into()),
+            ],
+        );
+        let options = TextRenderOptions::default();
+        let md = text.to_markdown(&options);
+        assert_eq!(md, "Hel*lo* Wow\\![link](https://a.b/%3Cc%3E%09d)");
+
+        let doc = LoroDoc::new_auto_commit();
+        let parsed = doc.get_text("text");
+        parsed.insert_markdown(0, &md, &options).unwrap();
+        assert_eq!(parsed.to_string(), "Hello Wow!link");
+        assert_eq!(parsed.to_markdown(&options), md);
+    }
+
+    #[test]
+    fn escape_block_markers_and_code_spans() {
+        let text = text_with_marks(
+            "- a\n+ b\n 1. c\n# d\ne - 2. f\ng`h\n`i",
+            &[(27, 30, "code", true.into()), (31, 33, "code", true.into())],
+        );
+        let options = TextRenderOptions::default();
+        let md = text.to_markdown(&options);
+        assert_eq!(
+            md,
+            "\\- a\n\\+ b\n 1\\. c\n\\# d\ne - 2. f\n``g`h``\n`` `i ``"
+        );
+
+        // The output is parsed back to the same rich text
+        let doc = LoroDoc::new_auto_commit();
+        let parsed = doc.get_text("text");
+        parsed.insert_markdown(0, &md, &options).unwrap();
+        assert_eq!(parsed.get_richtext_value(), text.get_richtext_value());
+
+        let text = text_with_marks(
+            "Hello world\nnext",
+            &[
+                (0, 16, "bold", true.into()),
+                (5, 11, "italic", true.into()),
+                (6, 11, "link", "https://a.b/(c)".into()),
+            ],
+        );
+        let doc = LoroDoc::new_auto_commit();
+        let parsed = doc.get_text("text");
+        parsed
+            .insert_markdown(0, &text.to_markdown(&options), &options)
+            .unwrap();
+        assert_eq!(parsed.to_markdown(&options), text.to_markdown(&options));
+    }
+}
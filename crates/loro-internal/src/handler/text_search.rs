@@ -0,0 +1,272 @@
+use std::ops::Range;
+
+use fxhash::FxHashMap;
+use loro_common::{InternalString, LoroError, LoroResult, LoroValue};
+pub use regex::Regex;
+
+use super::{text_haystack::Haystack, MaybeDetached, TextHandler, TextPosUnit};
+use crate::{
+    cursor::{Cursor, Side},
+    txn::Transaction,
+};
+
+/// A match returned by [`TextHandler::find_all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextMatch {
+    /// The range of the match in the requested unit
+    pub range: Range<usize>,
+    /// A stable cursor at the start of the match.
+    ///
+    /// It's `None` if the text is detached.
+    pub start: Option<Cursor>,
+    /// A stable cursor at the end of the match.
+    ///
+    /// It's `None` if the text is detached.
+    pub end: Option<Cursor>,
+}
+
+impl Haystack {
+    /// Convert sorted byte offsets into `(event index, unit index)` pairs in one pass.
+    fn positions(&self, offsets: &[usize], unit: TextPosUnit) -> Vec<(usize, usize)> {
+        let mut ans = Vec::with_capacity(offsets.len());
+        let mut event = 0;
+        let mut index = 0;
+        let mut offsets = offsets.iter().copied().peekable();
+        for (byte, c) in self.text.char_indices() {
+            while offsets.peek().is_some_and(|&x| x <= byte) {
+                offsets.next();
+                ans.push((event, index));
+            }
+
+            if offsets.peek().is_none() {
+                return ans;
+            }
+
+            if self.is_embed(byte) {
+                event += 1;
+                index += 1;
+            } else {
+                event += char_event_len(c);
+                index += match unit {
+                    TextPosUnit::Unicode => 1,
+                    TextPosUnit::Utf16 => c.len_utf16(),
+                    TextPosUnit::Utf8 => c.len_utf8(),
+                };
+            }
+        }
+
+        ans.extend(offsets.map(|_| (event, index)));
+        ans
+    }
+}
+
+fn char_event_len(c: char) -> usize {
+    if cfg!(feature = "wasm") {
+        c.len_utf16()
+    } else {
+        1
+    }
+}
+
+impl TextHandler {
+    /// Find all the non-overlapping matches of `regex` in the text.
+    ///
+    /// The ranges are in the given unit. Inline embeds are matched as U+FFFC.
+    pub fn find_all(&self, regex: &Regex, unit: TextPosUnit) -> Vec<TextMatch> {
+        let haystack = self.haystack();
+        let matches: Vec<Range<usize>> =
+            regex.find_iter(&haystack.text).map(|m| m.range()).collect();
+        let offsets: Vec<usize> = matches.iter().flat_map(|m| [m.start, m.end]).collect();
+        let positions = haystack.positions(&offsets, unit);
+        positions
+            .chunks(2)
+            .map(|pair| {
+                let (start_event, start) = pair[0];
+                let (end_event, end) = pair[1];
+                TextMatch {
+                    range: start..end,
+                    start: self.get_cursor(start_event, Side::Middle),
+                    end: self.get_cursor(end_event, Side::Left),
+                }
+            })
+            .collect()
+    }
+
+    /// Replace all the non-overlapping matches of `regex` with `replacement` in one transaction.
+    ///
+    /// `replacement` may refer to capture groups, like [`Regex::replace_all`].
+    /// Only the characters that differ between a match and its replacement are edited, so
+    /// the retained characters keep their styles. The inserted characters take the styles
+    /// of the characters they replace.
+    ///
+    /// Returns the number of replaced matches.
+    pub fn replace_all(&self, regex: &Regex, replacement: &str) -> LoroResult<usize> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => Err(LoroError::MisuseDetachedContainer {
+                method: "replace_all",
+            }),
+            MaybeDetached::Attached(a) => {
+                a.with_txn(|txn| self.replace_all_with_txn(txn, regex, replacement))
+            }
+        }
+    }
+
+    pub fn replace_all_with_txn(
+        &self,
+        txn: &mut Transaction,
+        regex: &Regex,
+        replacement: &str,
+    ) -> LoroResult<usize> {
+        let haystack = self.haystack();
+        let mut edits = Vec::new();
+        for caps in regex.captures_iter(&haystack.text) {
+            let m = caps.get(0).unwrap();
+            let mut new = String::new();
+            caps.expand(replacement, &mut new);
+            edits.push((m.range(), new));
+        }
+
+        let offsets: Vec<usize> = edits
+            .iter()
+            .flat_map(|(range, _)| [range.start, range.end])
+            .collect();
+        let positions = haystack.positions(&offsets, TextPosUnit::Unicode);
+        let count = edits.len();
+        // Apply from the end so the positions of the earlier matches stay valid
+        for (i, (range, new)) in edits.into_iter().enumerate().rev() {
+            let start_event = positions[i * 2].0;
+            self.replace_match_with_txn(txn, &haystack, range, start_event, &new)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Replace one match with the minimal edit: the common prefix and suffix are kept.
+    fn replace_match_with_txn(
+        &self,
+        txn: &mut Transaction,
+        haystack: &Haystack,
+        range: Range<usize>,
+        start_event: usize,
+        new: &str,
+    ) -> LoroResult<()> {
+        // Embeds never equal to the characters of the replacement
+        let old: Vec<(char, bool)> = haystack.text[range.clone()]
+            .char_indices()
+            .map(|(i, c)| (c, haystack.is_embed(range.start + i)))
+            .collect();
+        let new_chars: Vec<char> = new.chars().collect();
+        let same = |a: &(char, bool), b: &char| !a.1 && a.0 == *b;
+        let prefix = old
+            .iter()
+            .zip(new_chars.iter())
+            .take_while(|(a, b)| same(a, b))
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new_chars[prefix..].iter().rev())
+            .take_while(|(a, b)| same(a, b))
+            .count();
+
+        let removed = &old[prefix..old.len() - suffix];
+        let inserted: String = new_chars[prefix..new_chars.len() - suffix].iter().collect();
+        let pos = start_event
+            + old[..prefix]
+                .iter()
+                .map(|(c, is_embed)| if *is_embed { 1 } else { char_event_len(*c) })
+                .sum::<usize>();
+        let removed_len: usize = removed
+            .iter()
+            .map(|(c, is_embed)| if *is_embed { 1 } else { char_event_len(*c) })
+            .sum();
+
+        let styles = if inserted.is_empty() {
+            None
+        } else {
+            Some(self.replacement_styles(pos, removed_len)?)
+        };
+
+        self.delete_with_txn(txn, pos, removed_len)?;
+        let Some(styles) = styles else {
+            return Ok(());
+        };
+
+        self.insert_with_marks_with_txn(txn, pos, &inserted, &styles)
+    }
+
+    /// The styles of the inserted part of a replacement.
+    ///
+    /// They come from the first replaced character, or from the neighbor when nothing is removed.
+    /// Block attributes belong to the paragraph, so they are never copied.
+    fn replacement_styles(
+        &self,
+        pos: usize,
+        removed_len: usize,
+    ) -> LoroResult<FxHashMap<String, LoroValue>> {
+        let unit = if cfg!(feature = "wasm") {
+            TextPosUnit::Utf16
+        } else {
+            TextPosUnit::Unicode
+        };
+        let len = self.len_event();
+        let source = if removed_len > 0 || (pos == 0 && len > 0) {
+            pos
+        } else if pos > 0 {
+            pos - 1
+        } else {
+            return Ok(FxHashMap::default());
+        };
+
+        let mut styles = self.get_styles_at(source, unit)?;
+        styles.retain(|key, _| !self.is_block_key(&InternalString::from(key.as_str())));
+        Ok(styles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoroDoc;
+
+    #[test]
+    fn replace_all_keeps_styles_of_retained_chars() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "😀 color colour").unwrap();
+        text.mark(2, 7, "bold", true.into()).unwrap();
+
+        let regex = Regex::new("colou?r").unwrap();
+        let found = text.find_all(&regex, TextPosUnit::Unicode);
+        assert_eq!(
+            found.iter().map(|m| m.range.clone()).collect::<Vec<_>>(),
+            vec![2..7, 8..14]
+        );
+        let found = text.find_all(&regex, TextPosUnit::Utf16);
+        assert_eq!(
+            found.iter().map(|m| m.range.clone()).collect::<Vec<_>>(),
+            vec![3..8, 9..15]
+        );
+
+        assert_eq!(text.replace_all(&regex, "colored").unwrap(), 2);
+        assert_eq!(text.to_string(), "😀 colored colored");
+        assert_eq!(
+            text.ranges_with_style("bold", TextPosUnit::Unicode),
+            vec![(2..9, true.into())]
+        );
+
+        let start = found[1].start.clone().unwrap();
+        let pos = doc.query_pos(&start).unwrap().current.pos;
+        assert_eq!(pos, 10);
+    }
+
+    #[test]
+    fn replace_all_with_captures() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "a-b c-d").unwrap();
+        let regex = Regex::new(r"(\w)-(\w)").unwrap();
+        assert_eq!(text.replace_all(&regex, "$2-$1").unwrap(), 2);
+        assert_eq!(text.to_string(), "b-a d-c");
+    }
+}
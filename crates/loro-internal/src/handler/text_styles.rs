@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use fxhash::FxHashMap;
+use loro_common::{LoroError, LoroResult, LoroValue};
+
+use super::{MaybeDetached, TextHandler, TextPosUnit};
+use crate::delta::StyleMeta;
+
+impl TextHandler {
+    /// Get the length of the text in the given unit.
+    pub fn len_in(&self, unit: TextPosUnit) -> usize {
+        let pos_type = unit.to_pos_type();
+        match &self.inner {
+            MaybeDetached::Detached(t) => t.try_lock().unwrap().value.len(pos_type),
+            MaybeDetached::Attached(a) => {
+                a.with_state(|state| state.as_richtext_state_mut().unwrap().len(pos_type))
+            }
+        }
+    }
+
+    /// Get the styles of the character at `pos`.
+    ///
+    /// Styles removed by `unmark` are not included.
+    pub fn get_styles_at(
+        &self,
+        pos: usize,
+        unit: TextPosUnit,
+    ) -> LoroResult<FxHashMap<String, LoroValue>> {
+        let pos_type = unit.to_pos_type();
+        let styles = match &self.inner {
+            MaybeDetached::Detached(t) => t.try_lock().unwrap().value.get_styles_at(pos, pos_type),
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .get_styles_at(pos, pos_type)
+            }),
+        };
+
+        match styles {
+            Some(styles) => Ok(styles.to_map_without_null_value()),
+            None => Err(LoroError::OutOfBound {
+                pos,
+                len: self.len_in(unit),
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            }),
+        }
+    }
+
+    /// Get the ranges where the style `key` is active, with the value of the style.
+    ///
+    /// Adjacent ranges with the same value are merged.
+    pub fn ranges_with_style(
+        &self,
+        key: &str,
+        unit: TextPosUnit,
+    ) -> Vec<(Range<usize>, LoroValue)> {
+        let mut ans: Vec<(Range<usize>, LoroValue)> = Vec::new();
+        for (range, styles) in self.get_style_ranges(unit) {
+            let Some(value) = styles.to_map_without_null_value().remove(key) else {
+                continue;
+            };
+
+            match ans.last_mut() {
+                Some((last, last_value)) if last.end == range.start && *last_value == value => {
+                    last.end = range.end;
+                }
+                _ => ans.push((range, value)),
+            }
+        }
+
+        ans
+    }
+
+    /// Split `range` into spans that have the same styles.
+    ///
+    /// The spans cover the whole range, so unstyled text shows up as spans with empty styles.
+    pub fn style_spans(
+        &self,
+        range: Range<usize>,
+        unit: TextPosUnit,
+    ) -> LoroResult<Vec<(Range<usize>, FxHashMap<String, LoroValue>)>> {
+        let len = self.len_in(unit);
+        if range.start > range.end || range.end > len {
+            return Err(LoroError::OutOfBound {
+                pos: range.end,
+                len,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        let mut ans: Vec<(Range<usize>, FxHashMap<String, LoroValue>)> = Vec::new();
+        let mut push = |span: Range<usize>, styles: FxHashMap<String, LoroValue>| {
+            if span.is_empty() {
+                return;
+            }
+
+            match ans.last_mut() {
+                Some((last, last_styles)) if last.end == span.start && *last_styles == styles => {
+                    last.end = span.end;
+                }
+                _ => ans.push((span, styles)),
+            }
+        };
+
+        let mut index = range.start;
+        for (styled, styles) in self.get_style_ranges(unit) {
+            if styled.end <= index {
+                continue;
+            }
+
+            if styled.start >= range.end {
+                break;
+            }
+
+            let start = styled.start.max(index);
+            let end = styled.end.min(range.end);
+            push(index..start, FxHashMap::default());
+            push(start..end, styles.to_map_without_null_value());
+            index = end;
+        }
+
+        push(index..range.end, FxHashMap::default());
+        Ok(ans)
+    }
+
+    fn get_style_ranges(&self, unit: TextPosUnit) -> Vec<(Range<usize>, StyleMeta)> {
+        let pos_type = unit.to_pos_type();
+        match &self.inner {
+            MaybeDetached::Detached(t) => t.try_lock().unwrap().value.get_style_ranges(pos_type),
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .get_style_ranges(pos_type)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoroDoc;
+
+    fn styles(items: &[(&str, LoroValue)]) -> FxHashMap<String, LoroValue> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn query_styles_in_all_units() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "a😀bc").unwrap();
+        text.mark(1, 3, "bold", true.into()).unwrap();
+        text.mark(2, 4, "link", "x".into()).unwrap();
+
+        let bold = styles(&[("bold", true.into())]);
+        let both = styles(&[("bold", true.into()), ("link", "x".into())]);
+        assert_eq!(
+            text.get_styles_at(0, TextPosUnit::Unicode).unwrap(),
+            styles(&[])
+        );
+        assert_eq!(text.get_styles_at(1, TextPosUnit::Unicode).unwrap(), bold);
+        assert_eq!(text.get_styles_at(2, TextPosUnit::Unicode).unwrap(), both);
+        assert_eq!(text.get_styles_at(3, TextPosUnit::Utf16).unwrap(), both);
+        assert_eq!(text.get_styles_at(5, TextPosUnit::Utf8).unwrap(), both);
+        assert!(text.get_styles_at(4, TextPosUnit::Unicode).is_err());
+
+        assert_eq!(
+            text.ranges_with_style("bold", TextPosUnit::Unicode),
+            vec![(1..3, true.into())]
+        );
+        assert_eq!(
+            text.ranges_with_style("bold", TextPosUnit::Utf16),
+            vec![(1..4, true.into())]
+        );
+        assert_eq!(
+            text.ranges_with_style("bold", TextPosUnit::Utf8),
+            vec![(1..6, true.into())]
+        );
+        assert_eq!(
+            text.style_spans(0..4, TextPosUnit::Unicode).unwrap(),
+            vec![
+                (0..1, styles(&[])),
+                (1..2, bold),
+                (2..3, both),
+                (3..4, styles(&[("link", "x".into())])),
+            ]
+        );
+
+        text.unmark(1, 2, "bold").unwrap();
+        assert_eq!(
+            text.ranges_with_style("bold", TextPosUnit::Unicode),
+            vec![(2..3, true.into())]
+        );
+        assert_eq!(
+            text.style_spans(0..2, TextPosUnit::Unicode).unwrap(),
+            vec![(0..2, styles(&[]))]
+        );
+    }
+}
@@ -1,162 +1,507 @@
-use std::{char, sync::Arc};
+use std::{char, ops::Range, sync::Arc};
 
 use fxhash::FxHashMap;
-use itertools::Itertools;
 use tracing::trace;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::diff::DiffHandler;
+use crate::diff::{
+    diff,
+    diff_impl::{DiffGranularity, UpdateOptions, UpdateTimeoutError},
+    DiffHandler, OperateProxy,
+};
 
 use super::TextHandler;
 
-pub(super) struct DiffHook<'a> {
-    text: &'a TextHandler,
-    new: &'a [u32],
-    last_old_index: usize,
-    current_index: usize,
+/// How a text is split into diff tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Tokenizer {
+    /// Grapheme clusters, so a diff never splits a cluster
+    Grapheme,
+    /// Words, whitespace runs and punctuation, split by the unicode word boundaries
+    Word,
+    /// Lines, including their `\n`
+    Line,
 }
 
-impl<'a> DiffHook<'a> {
-    pub(crate) fn new(text: &'a TextHandler, new: &'a [u32]) -> Self {
-        Self {
-            text,
-            new,
-            last_old_index: 0,
-            current_index: 0,
+impl Tokenizer {
+    pub(super) fn from_options(options: &UpdateOptions) -> Self {
+        match options.granularity {
+            DiffGranularity::Char => Tokenizer::Grapheme,
+            DiffGranularity::Word => Tokenizer::Word,
         }
     }
 }
 
-impl DiffHandler for DiffHook<'_> {
-    fn insert(&mut self, old_index: usize, new_index: usize, new_len: usize) {
-        trace!("insert {old_index} {new_index} {new_len}");
-        if old_index > self.last_old_index {
-            self.current_index += old_index - self.last_old_index;
-            self.last_old_index = old_index;
+/// Interned diff tokens.
+///
+/// A single-char token is represented by its code point. Longer tokens are interned and get
+/// ids above `char::MAX`. Each embed gets an id of its own, so it never matches anything.
+#[derive(Default)]
+struct Tokens {
+    strs: Vec<Arc<str>>,
+    lookup: FxHashMap<Arc<str>, u32>,
+}
+
+impl Tokens {
+    const INTERNED_ID_START: u32 = char::MAX as u32 + 1;
+
+    fn intern(&mut self, s: &str) -> u32 {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return c as u32;
         }
 
-        self.text
-            .insert_unicode(
-                self.current_index,
-                &self.new[new_index..new_index + new_len]
-                    .iter()
-                    .map(|x| char::from_u32(*x).unwrap())
-                    .collect::<String>(),
-            )
-            .unwrap();
-        self.current_index += new_len;
+        if let Some(&id) = self.lookup.get(s) {
+            return id;
+        }
+
+        let id = Self::INTERNED_ID_START + self.strs.len() as u32;
+        let s: Arc<str> = Arc::from(s);
+        self.strs.push(s.clone());
+        self.lookup.insert(s, id);
+        id
     }
 
-    fn delete(&mut self, old_index: usize, old_len: usize) {
-        trace!("delete {old_index} {old_len}");
-        self.current_index += old_index - self.last_old_index;
-        self.text
-            .delete_unicode(self.current_index, old_len)
-            .unwrap();
-        self.last_old_index = old_index + old_len;
-    }
-}
-
-pub(super) struct DiffHookForLine<'a> {
-    text: &'a TextHandler,
-    old: Vec<u32>,
-    new: Vec<u32>,
-    lines: Vec<Arc<str>>,
-    lines_lookup: FxHashMap<Arc<str>, usize>,
-
-    last_old_index: usize,
-    current_index: usize,
-}
-
-impl<'a> DiffHookForLine<'a> {
-    pub(crate) fn new(text: &'a TextHandler, new_str: &str) -> Self {
-        let mut this = Self {
-            text,
-            old: Vec::new(),
-            new: Vec::new(),
-            lines: Vec::new(),
-            lines_lookup: FxHashMap::default(),
-            last_old_index: 0,
-            current_index: 0,
-        };
-
-        let text_str = text.to_string();
-        for line in text_str.split_inclusive('\n') {
-            let line: Arc<str> = Arc::from(line);
-            let id = this.register_line(line);
-            this.old.push(id as u32);
+    /// A new token for an embed. It counts as a single char like the embed.
+    fn embed(&mut self) -> u32 {
+        let id = Self::INTERNED_ID_START + self.strs.len() as u32;
+        self.strs.push(Arc::from("\u{FFFC}"));
+        id
+    }
+
+    fn unicode_len(&self, token: u32) -> usize {
+        match token.checked_sub(Self::INTERNED_ID_START) {
+            Some(index) => self.strs[index as usize].chars().count(),
+            None => 1,
         }
+    }
+
+    fn push_to(&self, token: u32, s: &mut String) {
+        match token.checked_sub(Self::INTERNED_ID_START) {
+            Some(index) => s.push_str(&self.strs[index as usize]),
+            None => s.push(char::from_u32(token).unwrap()),
+        }
+    }
+
+    fn unicode_len_of(&self, tokens: &[u32]) -> usize {
+        tokens.iter().map(|x| self.unicode_len(*x)).sum()
+    }
 
-        for line in new_str.split_inclusive('\n') {
-            let line: Arc<str> = Arc::from(line);
-            let id = this.register_line(line);
-            this.new.push(id as u32);
+    fn to_string(&self, tokens: &[u32]) -> String {
+        let mut s = String::new();
+        for token in tokens {
+            self.push_to(*token, &mut s);
         }
+        s
+    }
+}
+
+/// A text split into tokens.
+struct TokenizedText {
+    content: Vec<u32>,
+    /// The tokens used to match the texts. They differ from `content` when whitespace is ignored.
+    keys: Option<Vec<u32>>,
+}
 
-        trace!("{:#?}", &this.lines);
-        this
+impl TokenizedText {
+    fn new(s: &str, tokenizer: Tokenizer, ignore_whitespace: bool, tokens: &mut Tokens) -> Self {
+        Self::from_chunks(&[Some(s)], tokenizer, ignore_whitespace, tokens)
     }
 
-    fn register_line(&mut self, line: Arc<str>) -> usize {
-        if let Some(&index) = self.lines_lookup.get(&line) {
-            return index;
+    /// Tokenize the text chunks, where `None` is an embed.
+    ///
+    /// A token never spans an embed, and each embed is a token of its own.
+    fn from_chunks(
+        chunks: &[Option<&str>],
+        tokenizer: Tokenizer,
+        ignore_whitespace: bool,
+        tokens: &mut Tokens,
+    ) -> Self {
+        let mut content = Vec::new();
+        let mut keys = ignore_whitespace.then(Vec::new);
+        for chunk in chunks {
+            let Some(s) = chunk else {
+                let token = tokens.embed();
+                content.push(token);
+                if let Some(keys) = &mut keys {
+                    keys.push(token);
+                }
+
+                continue;
+            };
+
+            let segments: Vec<&str> = match tokenizer {
+                Tokenizer::Grapheme => s.graphemes(true).collect(),
+                Tokenizer::Word => s.split_word_bounds().collect(),
+                Tokenizer::Line => s.split_inclusive('\n').collect(),
+            };
+            for x in segments {
+                content.push(tokens.intern(x));
+                if let Some(keys) = &mut keys {
+                    keys.push(tokens.intern(&normalize_whitespace(x, tokenizer)));
+                }
+            }
         }
 
-        self.lines.push(line.clone());
-        self.lines_lookup.insert(line, self.lines.len() - 1);
-        self.lines.len() - 1
+        Self { content, keys }
     }
 
-    pub fn get_old_arr(&self) -> &[u32] {
-        &self.old
+    fn keys(&self) -> &[u32] {
+        self.keys.as_deref().unwrap_or(&self.content)
     }
+}
 
-    pub fn get_new_arr(&self) -> &[u32] {
-        &self.new
+/// The form of a token that is compared when whitespace is ignored.
+fn normalize_whitespace(s: &str, tokenizer: Tokenizer) -> String {
+    match tokenizer {
+        Tokenizer::Line => s.split_whitespace().collect::<Vec<_>>().join(" "),
+        Tokenizer::Grapheme | Tokenizer::Word => {
+            if !s.chars().all(char::is_whitespace) {
+                s.to_string()
+            } else if s.contains('\n') {
+                "\n".to_string()
+            } else {
+                " ".to_string()
+            }
+        }
     }
 }
 
-impl DiffHandler for DiffHookForLine<'_> {
-    fn insert(&mut self, old_index: usize, new_index: usize, new_len: usize) {
-        trace!("insert line {old_index} {new_index} {new_len}");
-        if self.last_old_index < old_index {
-            trace!(
-                "current_index: {} last_old_index: {} old_index: {old_index}",
-                self.current_index,
-                self.last_old_index
-            );
-            assert!(self.last_old_index < old_index);
-            self.current_index += (self.last_old_index..old_index)
-                .map(|x| self.lines[self.old[x] as usize].chars().count())
-                .sum::<usize>();
-            self.last_old_index = old_index;
+/// A step of the edit script between two token arrays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DiffOp {
+    Equal {
+        old: Range<usize>,
+        new: Range<usize>,
+    },
+    /// An empty `old` is an insertion and an empty `new` is a deletion
+    Replace {
+        old: Range<usize>,
+        new: Range<usize>,
+    },
+}
+
+/// Collects the callbacks of [`diff`] into an edit script.
+///
+/// The callbacks come in the order of the old index. Adjacent edits are merged into a single
+/// [`DiffOp::Replace`].
+struct EditRecorder {
+    ops: Vec<DiffOp>,
+    old_index: usize,
+    new_index: usize,
+}
+
+impl EditRecorder {
+    fn new() -> Self {
+        Self {
+            ops: Vec::new(),
+            old_index: 0,
+            new_index: 0,
+        }
+    }
+
+    fn push_equal_until(&mut self, old_index: usize) {
+        if old_index <= self.old_index {
+            return;
         }
 
-        let s = self.new[new_index..new_index + new_len]
-            .iter()
-            .map(|x| self.lines[*x as usize].clone())
-            .join("");
-        trace!("insert at {} {:?}", self.current_index, &s);
-        self.text.insert_unicode(self.current_index, &s).unwrap();
-        self.current_index += s.chars().count();
+        let len = old_index - self.old_index;
+        self.ops.push(DiffOp::Equal {
+            old: self.old_index..old_index,
+            new: self.new_index..self.new_index + len,
+        });
+        self.old_index = old_index;
+        self.new_index += len;
+    }
+
+    fn push_replace(&mut self, old: Range<usize>, new: Range<usize>) {
+        self.old_index = old.end;
+        self.new_index = new.end;
+        if let Some(DiffOp::Replace {
+            old: last_old,
+            new: last_new,
+        }) = self.ops.last_mut()
+        {
+            last_old.end = old.end;
+            last_new.end = new.end;
+            return;
+        }
+
+        self.ops.push(DiffOp::Replace { old, new });
+    }
+
+    fn finish(mut self, old_len: usize) -> Vec<DiffOp> {
+        self.push_equal_until(old_len);
+        self.ops
+    }
+}
+
+impl DiffHandler for EditRecorder {
+    fn insert(&mut self, old_index: usize, new_index: usize, new_len: usize) {
+        trace!("insert {old_index} {new_index} {new_len}");
+        self.push_equal_until(old_index);
+        debug_assert_eq!(new_index, self.new_index);
+        self.push_replace(
+            self.old_index..self.old_index,
+            new_index..new_index + new_len,
+        );
     }
 
     fn delete(&mut self, old_index: usize, old_len: usize) {
-        trace!("delete line {old_index} {old_len}");
-        if self.last_old_index != old_index {
-            assert!(self.last_old_index < old_index);
-            self.current_index += (self.last_old_index..old_index)
-                .map(|x| self.lines[self.old[x] as usize].chars().count())
-                .sum::<usize>();
+        trace!("delete {old_index} {old_len}");
+        self.push_equal_until(old_index);
+        self.push_replace(
+            old_index..old_index + old_len,
+            self.new_index..self.new_index,
+        );
+    }
+}
+
+/// Merge the edits separated by equalities that are not longer than the edits on both sides.
+///
+/// It's the semantic cleanup of diff-match-patch. A scattered char-level diff becomes a few
+/// edits of whole words, which is what a human would write. The lengths are measured in
+/// unicode chars by `old_len` and `new_len`.
+fn semantic_cleanup(
+    ops: &mut Vec<DiffOp>,
+    old_len: impl Fn(Range<usize>) -> usize,
+    new_len: impl Fn(Range<usize>) -> usize,
+) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut i = 1;
+        while i + 1 < ops.len() {
+            let (
+                DiffOp::Replace {
+                    old: left_old,
+                    new: left_new,
+                },
+                DiffOp::Equal { old: equal, .. },
+                DiffOp::Replace {
+                    old: right_old,
+                    new: right_new,
+                },
+            ) = (&ops[i - 1], &ops[i], &ops[i + 1])
+            else {
+                i += 1;
+                continue;
+            };
+
+            let equal_len = old_len(equal.clone());
+            let left_len = old_len(left_old.clone()).max(new_len(left_new.clone()));
+            let right_len = old_len(right_old.clone()).max(new_len(right_new.clone()));
+            if equal_len > left_len || equal_len > right_len {
+                i += 1;
+                continue;
+            }
+
+            let merged = DiffOp::Replace {
+                old: left_old.start..right_old.end,
+                new: left_new.start..right_new.end,
+            };
+            ops.splice(i - 1..i + 2, [merged]);
+            changed = true;
         }
+    }
+}
+
+/// Update `text` to `new_str` by diffing the tokens of the old and the new text.
+///
+/// The new text is plain text, so the inline embeds of the old text never match it and
+/// are deleted. The edits around them still land at the right positions.
+pub(super) fn update_text(
+    text: &TextHandler,
+    new_str: &str,
+    tokenizer: Tokenizer,
+    options: UpdateOptions,
+) -> Result<(), UpdateTimeoutError> {
+    let haystack = text.haystack();
+    let mut tokens = Tokens::default();
+    let old = TokenizedText::from_chunks(
+        &haystack.chunks(),
+        tokenizer,
+        options.ignore_whitespace,
+        &mut tokens,
+    );
+    let new = TokenizedText::new(new_str, tokenizer, options.ignore_whitespace, &mut tokens);
+    let semantic_cleanup = options.semantic_cleanup;
+    let ops = diff_tokens(&old, &new, &tokens, options, semantic_cleanup)?;
+    apply_ops(text, 0, &ops, &old, &new, &tokens);
+    Ok(())
+}
+
+fn diff_tokens(
+    old: &TokenizedText,
+    new: &TokenizedText,
+    tokens: &Tokens,
+    options: UpdateOptions,
+    cleanup: bool,
+) -> Result<Vec<DiffOp>, UpdateTimeoutError> {
+    let mut proxy = OperateProxy::new(EditRecorder::new());
+    diff(&mut proxy, options, old.keys(), new.keys())?;
+    let mut ops = proxy.unwrap().finish(old.content.len());
+    if cleanup {
+        semantic_cleanup(
+            &mut ops,
+            |range| tokens.unicode_len_of(&old.content[range]),
+            |range| tokens.unicode_len_of(&new.content[range]),
+        );
+    }
+
+    Ok(ops)
+}
+
+/// Apply the edit script to `text`, where the old text starts at the unicode index `pos`.
+///
+/// Returns the unicode index after the new text.
+fn apply_ops(
+    text: &TextHandler,
+    mut pos: usize,
+    ops: &[DiffOp],
+    old: &TokenizedText,
+    new: &TokenizedText,
+    tokens: &Tokens,
+) -> usize {
+    for op in ops {
+        match op {
+            DiffOp::Equal {
+                old: old_range,
+                new: new_range,
+            } => {
+                let old_tokens = &old.content[old_range.clone()];
+                let new_tokens = &new.content[new_range.clone()];
+                for (&a, &b) in old_tokens.iter().zip(new_tokens) {
+                    if a == b {
+                        pos += tokens.unicode_len(a);
+                    } else {
+                        // The tokens only match when whitespace is ignored.
+                        // Update them by their grapheme diff to keep the cursors inside.
+                        pos = update_token(
+                            text,
+                            pos,
+                            &tokens.to_string(&[a]),
+                            &tokens.to_string(&[b]),
+                        );
+                    }
+                }
+            }
+            DiffOp::Replace {
+                old: old_range,
+                new: new_range,
+            } => {
+                let len = tokens.unicode_len_of(&old.content[old_range.clone()]);
+                let s = tokens.to_string(&new.content[new_range.clone()]);
+                trace!("replace {len} chars at {pos} with {s:?}");
+                if len > 0 {
+                    text.delete_unicode(pos, len).unwrap();
+                }
+                if !s.is_empty() {
+                    text.insert_unicode(pos, &s).unwrap();
+                    pos += s.chars().count();
+                }
+            }
+        }
+    }
+
+    pos
+}
+
+/// Replace `old` at `pos` with `new` by their grapheme diff. Returns the position after `new`.
+fn update_token(text: &TextHandler, pos: usize, old: &str, new: &str) -> usize {
+    let mut tokens = Tokens::default();
+    let old = TokenizedText::new(old, Tokenizer::Grapheme, false, &mut tokens);
+    let new = TokenizedText::new(new, Tokenizer::Grapheme, false, &mut tokens);
+    // Without a timeout the diff never fails
+    let ops = diff_tokens(&old, &new, &tokens, UpdateOptions::default(), false).unwrap();
+    apply_ops(text, pos, &ops, &old, &new, &tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_around_embeds() {
+        let doc = crate::LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "ab").unwrap();
+        let image = loro_common::loro_value!({"image": "img"});
+        text.insert_embed(1, image).unwrap();
+        let cursor = text.get_cursor(2, Default::default()).unwrap();
+        // "b" is at unicode index 2 because of the embed
+        text.update("ab!", UpdateOptions::default()).unwrap();
+        assert_eq!(text.to_string(), "ab!");
+        assert_eq!(text.len_unicode(), 3);
+        let result = doc.query_pos(&cursor).unwrap();
+        assert!(result.update.is_none());
+        assert_eq!(result.current.pos, 1);
+
+        let image = loro_common::loro_value!({"image": "img"});
+        text.insert_embed(1, image).unwrap();
+        let options = UpdateOptions::default().granularity(DiffGranularity::Word);
+        text.update("a b!", options).unwrap();
+        assert_eq!(text.to_string(), "a b!");
+        assert_eq!(text.len_unicode(), 4);
+    }
+
+    #[test]
+    fn semantic_cleanup_merges_small_equalities() {
+        // "abcd" -> "xbyd": the "b" between two edits is absorbed
+        let mut ops = vec![
+            DiffOp::Replace {
+                old: 0..1,
+                new: 0..1,
+            },
+            DiffOp::Equal {
+                old: 1..2,
+                new: 1..2,
+            },
+            DiffOp::Replace {
+                old: 2..3,
+                new: 2..3,
+            },
+            DiffOp::Equal {
+                old: 3..4,
+                new: 3..4,
+            },
+        ];
+        semantic_cleanup(&mut ops, |range| range.len(), |range| range.len());
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Replace {
+                    old: 0..3,
+                    new: 0..3,
+                },
+                DiffOp::Equal {
+                    old: 3..4,
+                    new: 3..4,
+                },
+            ]
+        );
+    }
 
-        self.last_old_index = old_index + old_len;
-        let delete_len = (old_index..old_index + old_len)
-            .map(|x| self.lines[self.old[x] as usize].chars().count())
-            .sum::<usize>();
+    #[test]
+    fn word_diff_keeps_unchanged_words() {
+        let doc = crate::LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "the quick brown fox").unwrap();
+        let cursor = text.get_cursor(10, Default::default()).unwrap();
+        let options = UpdateOptions::default().granularity(DiffGranularity::Word);
+        text.update("the slow brown  fox", options).unwrap();
+        assert_eq!(text.to_string(), "the slow brown  fox");
+        let result = doc.query_pos(&cursor).unwrap();
+        assert!(result.update.is_none());
+        assert_eq!(result.current.pos, 9);
 
-        trace!("delete at {} with len {}", self.current_index, delete_len);
-        self.text
-            .delete_unicode(self.current_index, delete_len)
+        // With whitespace ignored, the re-indented line keeps its words
+        text.insert(0, "a\n").unwrap();
+        let cursor = text.get_cursor(6, Default::default()).unwrap();
+        let options = UpdateOptions::default().ignore_whitespace(true);
+        text.update_by_line("a\n  the slow brown fox", options)
             .unwrap();
+        assert_eq!(text.to_string(), "a\n  the slow brown fox");
+        assert!(doc.query_pos(&cursor).unwrap().update.is_none());
     }
 }
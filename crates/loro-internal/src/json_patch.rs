@@ -0,0 +1,634 @@
+//! Conversion between Loro diffs and [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902)
+//! JSON Patch operations.
+//!
+//! The paths are [RFC 6901](https://datatracker.ietf.org/doc/html/rfc6901) JSON Pointers into
+//! the value returned by [`LoroDoc::get_deep_value`].
+use fxhash::FxHashSet;
+use loro_common::{ContainerID, LoroResult, TreeID};
+use loro_delta::DeltaItem;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    delta::TreeExternalDiff,
+    event::{Diff, Index},
+    handler::{HandlerTrait, TreeHandler, ValueOrHandler},
+    loro::LoroDoc,
+    state::TreeParentId,
+    undo::DiffBatch,
+    value::ToJson,
+    version::Frontiers,
+};
+
+mod apply;
+pub use apply::JsonPatchError;
+
+/// A single JSON Patch operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl LoroDoc {
+    /// Get the JSON Patch that turns the deep value at version `a` into the deep value at version `b`.
+    ///
+    /// The current state of the doc is not changed.
+    pub fn json_patch_between(&self, a: &Frontiers, b: &Frontiers) -> LoroResult<Vec<JsonPatchOp>> {
+        let doc = self.fork_at(b);
+        let diff = doc.diff(a, b)?;
+        Ok(diff.to_json_patch(&doc))
+    }
+}
+
+impl DiffBatch {
+    /// Convert the diff into JSON Patch operations.
+    ///
+    /// `doc` should be at the target version of the diff, because the paths of the containers,
+    /// the tree nodes and the new values of texts and counters are resolved from its current state.
+    ///
+    /// - Parent containers are patched before their children.
+    /// - A container created inside the diff is added with its deep value as a whole.
+    /// - A text is replaced as a whole string.
+    /// - A tree move becomes a `move` of the node object followed by `replace`s of its
+    ///   `parent`, `index` and `fractional_index` fields. The `index` fields of its siblings
+    ///   are not rewritten.
+    pub fn to_json_patch(&self, doc: &LoroDoc) -> Vec<JsonPatchOp> {
+        let mut diffs: Vec<(Vec<(ContainerID, Index)>, &Diff)> = self
+            .0
+            .iter()
+            .filter_map(|(id, diff)| Some((doc.get_path_to_container(id)?, diff)))
+            .collect();
+        diffs.sort_by_cached_key(|(path, _)| (path.len(), path.last().unwrap().0.to_string()));
+
+        let mut ans = Vec::new();
+        let mut filled: FxHashSet<ContainerID> = FxHashSet::default();
+        for (path, diff) in diffs {
+            if path.iter().any(|(id, _)| filled.contains(id)) {
+                continue;
+            }
+
+            let Some(pointer) = container_pointer(doc, &path) else {
+                continue;
+            };
+            let id = &path.last().unwrap().0;
+            match diff {
+                Diff::List(list) => {
+                    let mut index = 0;
+                    for item in list.iter() {
+                        match item {
+                            DeltaItem::Retain { len, .. } => {
+                                index += len;
+                            }
+                            DeltaItem::Replace { value, delete, .. } => {
+                                for _ in 0..*delete {
+                                    ans.push(JsonPatchOp::Remove {
+                                        path: child_pointer(&pointer, &index.to_string()),
+                                    });
+                                }
+                                for v in value.iter() {
+                                    ans.push(JsonPatchOp::Add {
+                                        path: child_pointer(&pointer, &index.to_string()),
+                                        value: fill_value(v, &mut filled),
+                                    });
+                                    index += 1;
+                                }
+                            }
+                        }
+                    }
+                }
+                Diff::Map(map) => {
+                    let mut keys: Vec<_> = map.updated.iter().collect();
+                    keys.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                    for (key, v) in keys {
+                        let path = child_pointer(&pointer, key);
+                        match &v.value {
+                            Some(v) => ans.push(JsonPatchOp::Add {
+                                path,
+                                value: fill_value(v, &mut filled),
+                            }),
+                            None => ans.push(JsonPatchOp::Remove { path }),
+                        }
+                    }
+                }
+                Diff::Tree(tree_diff) => {
+                    let tree = doc.get_tree(id);
+                    for item in tree_diff.diff.iter() {
+                        tree_item_to_patch(
+                            &tree,
+                            &pointer,
+                            item.target,
+                            &item.action,
+                            &mut filled,
+                            &mut ans,
+                        );
+                    }
+                }
+                Diff::Text(_) => {
+                    ans.push(JsonPatchOp::Replace {
+                        path: pointer,
+                        value: doc.get_text(id).get_value().to_json_value(),
+                    });
+                }
+                #[cfg(feature = "counter")]
+                Diff::Counter(_) => {
+                    ans.push(JsonPatchOp::Replace {
+                        path: pointer,
+                        value: doc.get_counter(id).get_value().to_json_value(),
+                    });
+                }
+                Diff::Unknown => {}
+            }
+        }
+
+        ans
+    }
+}
+
+fn tree_item_to_patch(
+    tree: &TreeHandler,
+    tree_pointer: &str,
+    target: TreeID,
+    action: &TreeExternalDiff,
+    filled: &mut FxHashSet<ContainerID>,
+    ans: &mut Vec<JsonPatchOp>,
+) {
+    let node_at = |parent: &TreeParentId, index: usize| {
+        children_pointer(tree, tree_pointer, parent)
+            .map(|ptr| child_pointer(&ptr, &index.to_string()))
+    };
+    match action {
+        TreeExternalDiff::Create {
+            parent,
+            index,
+            position,
+        } => {
+            if let Some(path) = node_at(parent, *index) {
+                ans.push(JsonPatchOp::Add {
+                    path,
+                    value: tree_node_value(tree, target, parent, *index, position, filled),
+                });
+            }
+        }
+        TreeExternalDiff::Move {
+            parent,
+            index,
+            position,
+            old_parent,
+            old_index,
+        } => match (node_at(old_parent, *old_index), node_at(parent, *index)) {
+            (Some(from), Some(path)) => {
+                ans.push(JsonPatchOp::Move {
+                    from,
+                    path: path.clone(),
+                });
+                ans.push(JsonPatchOp::Replace {
+                    path: child_pointer(&path, "parent"),
+                    value: tree_parent_value(parent),
+                });
+                ans.push(JsonPatchOp::Replace {
+                    path: child_pointer(&path, "index"),
+                    value: json!(index),
+                });
+                ans.push(JsonPatchOp::Replace {
+                    path: child_pointer(&path, "fractional_index"),
+                    value: json!(position.to_string()),
+                });
+            }
+            (None, Some(path)) => {
+                ans.push(JsonPatchOp::Add {
+                    path,
+                    value: tree_node_value(tree, target, parent, *index, position, filled),
+                });
+            }
+            (Some(path), None) => {
+                ans.push(JsonPatchOp::Remove { path });
+            }
+            (None, None) => {}
+        },
+        TreeExternalDiff::Delete {
+            old_parent,
+            old_index,
+        } => {
+            if let Some(path) = node_at(old_parent, *old_index) {
+                ans.push(JsonPatchOp::Remove { path });
+            }
+        }
+    }
+}
+
+fn tree_node_value(
+    tree: &TreeHandler,
+    target: TreeID,
+    parent: &TreeParentId,
+    index: usize,
+    position: &fractional_index::FractionalIndex,
+    filled: &mut FxHashSet<ContainerID>,
+) -> Value {
+    let meta = match tree.get_meta(target) {
+        Ok(meta) => {
+            filled.insert(meta.id());
+            meta.get_deep_value().to_json_value()
+        }
+        Err(_) => json!({}),
+    };
+    json!({
+        "id": target.to_string(),
+        "parent": tree_parent_value(parent),
+        "meta": meta,
+        "index": index,
+        "fractional_index": position.to_string(),
+        "children": [],
+    })
+}
+
+fn tree_parent_value(parent: &TreeParentId) -> Value {
+    match parent {
+        TreeParentId::Node(id) => json!(id.to_string()),
+        _ => Value::Null,
+    }
+}
+
+fn fill_value(v: &ValueOrHandler, filled: &mut FxHashSet<ContainerID>) -> Value {
+    if let ValueOrHandler::Handler(h) = v {
+        filled.insert(h.id());
+    }
+    v.to_deep_value().to_json_value()
+}
+
+/// Get the JSON Pointer of the container in the deep value of the doc
+fn container_pointer(doc: &LoroDoc, path: &[(ContainerID, Index)]) -> Option<String> {
+    let mut ans = String::new();
+    for (i, (_, index)) in path.iter().enumerate() {
+        match index {
+            Index::Key(key) => push_token(&mut ans, key),
+            Index::Seq(seq) => push_token(&mut ans, &seq.to_string()),
+            Index::Node(node) => {
+                // The parent of a tree node's meta container is always the tree
+                let tree = doc.get_tree(&path[i.checked_sub(1)?].0);
+                ans = node_pointer(&tree, &ans, *node)?;
+                push_token(&mut ans, "meta");
+            }
+        }
+    }
+
+    Some(ans)
+}
+
+fn node_pointer(tree: &TreeHandler, tree_pointer: &str, target: TreeID) -> Option<String> {
+    let parent = tree.get_node_parent(&target)?;
+    let index = tree.get_index_by_tree_id(&target)?;
+    let mut ans = children_pointer(tree, tree_pointer, &parent)?;
+    push_token(&mut ans, &index.to_string());
+    Some(ans)
+}
+
+fn children_pointer(
+    tree: &TreeHandler,
+    tree_pointer: &str,
+    parent: &TreeParentId,
+) -> Option<String> {
+    match parent {
+        TreeParentId::Root => Some(tree_pointer.to_string()),
+        TreeParentId::Node(id) => {
+            let mut ans = node_pointer(tree, tree_pointer, *id)?;
+            push_token(&mut ans, "children");
+            Some(ans)
+        }
+        TreeParentId::Deleted | TreeParentId::Unexist => None,
+    }
+}
+
+fn child_pointer(pointer: &str, token: &str) -> String {
+    let mut ans = pointer.to_string();
+    push_token(&mut ans, token);
+    ans
+}
+
+/// Append a reference token to the pointer, escaping `~` and `/` as RFC 6901 requires
+fn push_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => pointer.push_str("~0"),
+            '/' => pointer.push_str("~1"),
+            c => pointer.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_list_and_text_to_json_patch() {
+        let doc = LoroDoc::new_auto_commit();
+        let map = doc.get_map("map");
+        map.insert("a", 1).unwrap();
+        map.insert("b/c", 2).unwrap();
+        doc.commit_then_renew();
+        let a = doc.oplog_frontiers();
+
+        map.delete("a").unwrap();
+        map.insert("b/c", 3).unwrap();
+        let list = doc.get_list("list");
+        list.insert(0, "x").unwrap();
+        let text = list
+            .insert_container(1, crate::TextHandler::new_detached())
+            .unwrap();
+        text.insert(0, "hello").unwrap();
+        doc.commit_then_renew();
+        let b = doc.oplog_frontiers();
+
+        let patch = doc.json_patch_between(&a, &b).unwrap();
+        assert_eq!(
+            patch,
+            vec![
+                JsonPatchOp::Add {
+                    path: "/list/0".into(),
+                    value: json!("x")
+                },
+                JsonPatchOp::Add {
+                    path: "/list/1".into(),
+                    value: json!("hello")
+                },
+                JsonPatchOp::Remove {
+                    path: "/map/a".into()
+                },
+                JsonPatchOp::Add {
+                    path: "/map/b~1c".into(),
+                    value: json!(3)
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&patch[2]).unwrap(),
+            json!({"op": "remove", "path": "/map/a"})
+        );
+    }
+
+    #[test]
+    fn tree_move_to_json_patch() {
+        let doc = LoroDoc::new_auto_commit();
+        let tree = doc.get_tree("tree");
+        let root = tree.create(TreeParentId::Root).unwrap();
+        let child = tree.create(TreeParentId::Root).unwrap();
+        doc.commit_then_renew();
+        let a = doc.oplog_frontiers();
+
+        tree.mov(child, TreeParentId::Node(root)).unwrap();
+        tree.get_meta(child).unwrap().insert("name", "c").unwrap();
+        doc.commit_then_renew();
+        let b = doc.oplog_frontiers();
+
+        let patch = doc.json_patch_between(&a, &b).unwrap();
+        assert_eq!(
+            patch[0],
+            JsonPatchOp::Move {
+                from: "/tree/1".into(),
+                path: "/tree/0/children/0".into()
+            }
+        );
+        assert_eq!(
+            patch.last().unwrap(),
+            &JsonPatchOp::Add {
+                path: "/tree/0/children/0/meta/name".into(),
+                value: json!("c")
+            }
+        );
+    }
+
+    #[test]
+    fn apply_json_patch_round_trip() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_map("map").insert("a", 1).unwrap();
+        doc.get_text("text").insert(0, "hello world").unwrap();
+        let tree = doc.get_tree("tree");
+        let root = tree.create(TreeParentId::Root).unwrap();
+        let child = tree.create(TreeParentId::Root).unwrap();
+        doc.commit_then_renew();
+        let a = doc.oplog_frontiers();
+        let other = doc.fork_at(&a);
+
+        let map = doc.get_map("map");
+        map.delete("a").unwrap();
+        let list = map
+            .insert_container("list", crate::ListHandler::new_detached())
+            .unwrap();
+        list.insert(0, "x").unwrap();
+        doc.get_text("text").insert(5, ",").unwrap();
+        tree.mov(child, TreeParentId::Node(root)).unwrap();
+        doc.commit_then_renew();
+        let b = doc.oplog_frontiers();
+
+        let patch = doc.json_patch_between(&a, &b).unwrap();
+        other.apply_json_patch(&patch).unwrap();
+        other.commit_then_renew();
+        assert_eq!(
+            other.get_map("map").get_deep_value(),
+            doc.get_map("map").get_deep_value()
+        );
+        assert_eq!(other.get_text("text").to_string(), "hello, world");
+        let other_tree = other.get_tree("tree");
+        assert_eq!(
+            other_tree.children(&TreeParentId::Node(root)),
+            Some(vec![child])
+        );
+
+        // The text is edited in place instead of being overwritten
+        let text_len = other.len_ops();
+        other
+            .apply_json_patch(&[JsonPatchOp::Replace {
+                path: "/text".into(),
+                value: json!("hello, world!"),
+            }])
+            .unwrap();
+        other.commit_then_renew();
+        assert_eq!(other.len_ops(), text_len + 1);
+    }
+
+    #[test]
+    fn apply_json_patch_errors() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_map("map")
+            .insert("a", loro_common::loro_value!({"b": [1, 2]}))
+            .unwrap();
+        doc.apply_json_patch(&[
+            JsonPatchOp::Test {
+                path: "/map/a/b/1".into(),
+                value: json!(2),
+            },
+            JsonPatchOp::Add {
+                path: "/map/a/b/-".into(),
+                value: json!(3),
+            },
+            JsonPatchOp::Copy {
+                from: "/map/a".into(),
+                path: "/map/c".into(),
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({"map": {"a": {"b": [1, 2, 3]}, "c": {"b": [1, 2, 3]}}})
+        );
+
+        assert!(matches!(
+            doc.apply_json_patch(&[JsonPatchOp::Test {
+                path: "/map/a/b/0".into(),
+                value: json!(2),
+            }]),
+            Err(JsonPatchError::TestFailed(_))
+        ));
+        assert!(matches!(
+            doc.apply_json_patch(&[JsonPatchOp::Remove {
+                path: "/map/d".into(),
+            }]),
+            Err(JsonPatchError::PathNotFound(_))
+        ));
+        assert!(matches!(
+            doc.apply_json_patch(&[JsonPatchOp::Remove { path: "map".into() }]),
+            Err(JsonPatchError::InvalidPointer(_))
+        ));
+
+        // Numbers are compared by value
+        doc.apply_json_patch(&[JsonPatchOp::Test {
+            path: "/map/a/b/1".into(),
+            value: json!(2.0),
+        }])
+        .unwrap();
+    }
+
+    #[test]
+    fn apply_json_patch_is_atomic() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_map("map").insert("a", 1).unwrap();
+        doc.commit_then_renew();
+        let version = doc.oplog_frontiers();
+        assert!(matches!(
+            doc.apply_json_patch(&[
+                JsonPatchOp::Add {
+                    path: "/map/b".into(),
+                    value: json!([1, 2]),
+                },
+                JsonPatchOp::Remove {
+                    path: "/map/a".into(),
+                },
+                JsonPatchOp::Test {
+                    path: "/map/a".into(),
+                    value: json!(1),
+                },
+            ]),
+            Err(JsonPatchError::PathNotFound(_))
+        ));
+        doc.commit_then_renew();
+        assert_eq!(doc.oplog_frontiers(), version);
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({"map": {"a": 1}})
+        );
+    }
+
+    #[test]
+    fn replace_in_movable_list_keeps_the_element() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        let list = doc.get_movable_list("list");
+        for (i, v) in ["x", "y", "z"].into_iter().enumerate() {
+            list.insert(i, v).unwrap();
+        }
+        doc.commit_then_renew();
+        let other = doc.fork();
+        other.set_peer_id(2).unwrap();
+        other.get_movable_list("list").mov(0, 2).unwrap();
+        other.commit_then_renew();
+
+        doc.apply_json_patch(&[JsonPatchOp::Replace {
+            path: "/list/0".into(),
+            value: json!("w"),
+        }])
+        .unwrap();
+        doc.commit_then_renew();
+        let updates = other
+            .export(crate::loro::ExportMode::all_updates())
+            .unwrap();
+        doc.import(&updates).unwrap();
+        // The concurrent move applies to the replaced element
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({"list": ["y", "z", "w"]})
+        );
+    }
+
+    #[test]
+    fn apply_json_merge_patch() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.apply_json_merge_patch(&json!({
+            "map": {"a": 1, "b": {"c": "d", "e": "f"}},
+            "text": "hello"
+        }))
+        .unwrap();
+        doc.apply_json_merge_patch(&json!({
+            "map": {"a": null, "b": {"e": null, "g": {"h": null, "i": 1}}},
+            "text": "hello world"
+        }))
+        .unwrap();
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({
+                "map": {"b": {"c": "d", "g": {"i": 1}}},
+                "text": "hello world"
+            })
+        );
+    }
+
+    #[test]
+    fn apply_json_merge_patch_null_root() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_map("map").insert("a", 1).unwrap();
+        let empty = doc.get_map("empty");
+        empty.insert("b", 2).unwrap();
+        empty.delete("b").unwrap();
+        // Removing a missing or empty root is a no-op
+        doc.apply_json_merge_patch(&json!({"missing": null, "empty": null}))
+            .unwrap();
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({"map": {"a": 1}, "empty": {}})
+        );
+        assert!(matches!(
+            doc.apply_json_merge_patch(&json!({"map": null})),
+            Err(JsonPatchError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn apply_json_merge_patch_is_atomic() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_map("map").insert("a", 1).unwrap();
+        doc.get_text("text").insert(0, "x").unwrap();
+        doc.commit_then_renew();
+        let version = doc.oplog_frontiers();
+        // The members are applied in key order, so "text" fails after the others
+        assert!(matches!(
+            doc.apply_json_merge_patch(&json!({
+                "map": {"a": null, "b": 2},
+                "other": {"c": 3},
+                "text": null
+            })),
+            Err(JsonPatchError::Unsupported(_))
+        ));
+        doc.commit_then_renew();
+        assert_eq!(doc.oplog_frontiers(), version);
+        assert_eq!(
+            doc.get_deep_value().to_json_value(),
+            json!({"map": {"a": 1}, "text": "x"})
+        );
+    }
+}
@@ -0,0 +1,756 @@
+//! Apply JSON Patch and JSON Merge Patch to a [`LoroDoc`] as CRDT ops.
+use loro_common::{LoroError, LoroValue, TreeID};
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use super::JsonPatchOp;
+use crate::{
+    event::Index,
+    handler::{
+        self, Handler, HandlerTrait, ListHandler, MapHandler, MovableListHandler, TreeHandler,
+        ValueOrHandler,
+    },
+    loro::LoroDoc,
+    state::TreeParentId,
+    value::ToJson,
+};
+
+#[derive(Error, Debug)]
+pub enum JsonPatchError {
+    #[error("Invalid JSON Pointer: {0}")]
+    InvalidPointer(String),
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+    #[error("Test failed at: {0}")]
+    TestFailed(String),
+    #[error("Unsupported patch: {0}")]
+    Unsupported(String),
+    #[error(transparent)]
+    LoroError(#[from] LoroError),
+}
+
+type Result<T> = std::result::Result<T, JsonPatchError>;
+
+impl LoroDoc {
+    /// Apply an [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902) JSON Patch
+    /// against the value returned by [`LoroDoc::get_deep_value`].
+    ///
+    /// The patch is turned into minimal CRDT ops:
+    ///
+    /// - A JSON object or array added to the doc becomes a map or list container.
+    /// - A string that replaces a text container is applied with [`crate::TextHandler::update`].
+    /// - A number that replaces a counter is applied as an increment.
+    /// - A `move` inside the same movable list or tree is applied as a native move.
+    ///
+    /// The derived fields of the tree nodes (`id`, `parent`, `index` and `fractional_index`)
+    /// are ignored.
+    ///
+    /// The patch is atomic as required by RFC 6902: it is first applied to a fork of the doc,
+    /// and the doc is changed only if every operation succeeds. The ops of a successful patch
+    /// are therefore generated twice, and forking copies the doc and commits the pending
+    /// transaction, so a large doc pays for a snapshot on every call.
+    pub fn apply_json_patch(&self, patch: &[JsonPatchOp]) -> Result<()> {
+        if patch.is_empty() {
+            return Ok(());
+        }
+
+        self.apply_atomically(|doc| {
+            for op in patch {
+                apply_op(doc, op)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Apply an [RFC 7396](https://datatracker.ietf.org/doc/html/rfc7396) JSON Merge Patch
+    /// against the value returned by [`LoroDoc::get_deep_value`].
+    ///
+    /// Nested maps are merged in place. The other values are applied with the same rules as
+    /// [`LoroDoc::apply_json_patch`]. Root containers cannot be removed, so a `null` member
+    /// of the patch is only accepted when the root container is missing or already empty.
+    ///
+    /// The patch is atomic in the same way as [`LoroDoc::apply_json_patch`].
+    pub fn apply_json_merge_patch(&self, patch: &Value) -> Result<()> {
+        let Value::Object(patch) = patch else {
+            return Err(JsonPatchError::Unsupported(
+                "the merge patch of a doc should be an object".into(),
+            ));
+        };
+
+        if patch.is_empty() {
+            return Ok(());
+        }
+
+        self.apply_atomically(|doc| {
+            for (key, value) in patch.iter() {
+                match (child(doc, &Parent::Doc, key), value) {
+                    (None, Value::Null) => {}
+                    (Some(Child::Parent(Parent::Container(h))), Value::Null)
+                        if is_empty_value(&h.get_deep_value()) => {}
+                    (_, Value::Null) => {
+                        return Err(JsonPatchError::Unsupported(format!(
+                            "root container {} cannot be removed",
+                            key
+                        )))
+                    }
+                    (
+                        Some(Child::Parent(Parent::Container(Handler::Map(map)))),
+                        Value::Object(v),
+                    ) => merge_map(&map, v)?,
+                    (_, value) => add(doc, &Parent::Doc, key, &strip_nulls(value))?,
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Run `f` on a fork first, and on the doc only if it succeeds on the fork
+    fn apply_atomically(&self, f: impl Fn(&LoroDoc) -> Result<()>) -> Result<()> {
+        let fork = self.fork();
+        fork.start_auto_commit();
+        f(&fork)?;
+        f(self)
+    }
+}
+
+/// A location in the doc that has children
+#[derive(Clone)]
+enum Parent {
+    Doc,
+    Container(Handler),
+    TreeChildren(TreeHandler, TreeParentId),
+    TreeNode(TreeHandler, TreeID),
+}
+
+enum Child {
+    Parent(Parent),
+    Value(LoroValue),
+}
+
+enum Location {
+    /// The `key` slot of the parent
+    Slot(Parent, String),
+    /// A path inside the plain value stored in the `key` slot of the parent
+    InValue(Parent, String, Value, Vec<String>),
+}
+
+fn apply_op(doc: &LoroDoc, op: &JsonPatchOp) -> Result<()> {
+    match op {
+        JsonPatchOp::Add { path, value } => match locate(doc, path)? {
+            Location::Slot(parent, key) => add(doc, &parent, &key, value),
+            Location::InValue(parent, key, mut v, rest) => {
+                json_add(&mut v, &rest, value.clone(), path)?;
+                replace_plain_value(&parent, &key, &v, path)
+            }
+        },
+        JsonPatchOp::Remove { path } => match locate(doc, path)? {
+            Location::Slot(parent, key) => remove(&parent, &key, path),
+            Location::InValue(parent, key, mut v, rest) => {
+                json_remove(&mut v, &rest, path)?;
+                replace_plain_value(&parent, &key, &v, path)
+            }
+        },
+        JsonPatchOp::Replace { path, value } => match locate(doc, path)? {
+            Location::Slot(parent, key) => replace(doc, &parent, &key, value, path),
+            Location::InValue(parent, key, mut v, rest) => {
+                json_remove(&mut v, &rest, path)?;
+                json_add(&mut v, &rest, value.clone(), path)?;
+                replace_plain_value(&parent, &key, &v, path)
+            }
+        },
+        JsonPatchOp::Move { from, path } => {
+            if from == path {
+                return Ok(());
+            }
+
+            if let (Location::Slot(from_parent, from_key), Location::Slot(to_parent, to_key)) =
+                (locate(doc, from)?, locate(doc, path)?)
+            {
+                if native_move(&from_parent, &from_key, &to_parent, &to_key)? {
+                    return Ok(());
+                }
+            }
+
+            let value = get(doc, from)?;
+            apply_op(doc, &JsonPatchOp::Remove { path: from.clone() })?;
+            apply_op(
+                doc,
+                &JsonPatchOp::Add {
+                    path: path.clone(),
+                    value,
+                },
+            )
+        }
+        JsonPatchOp::Copy { from, path } => {
+            let value = get(doc, from)?;
+            apply_op(
+                doc,
+                &JsonPatchOp::Add {
+                    path: path.clone(),
+                    value,
+                },
+            )
+        }
+        JsonPatchOp::Test { path, value } => {
+            if !json_eq(&get(doc, path)?, value) {
+                return Err(JsonPatchError::TestFailed(path.clone()));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Compare two JSON values, where numbers are equal if they have the same value
+/// (`2` equals `2.0`)
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => a.as_f64() == b.as_f64(),
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).is_some_and(|other| json_eq(v, other)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Parse a JSON Pointer into unescaped reference tokens
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(JsonPatchError::InvalidPointer(pointer.to_string()));
+    };
+
+    Ok(rest
+        .split('/')
+        .map(|x| x.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn parse_index(token: &str, len: usize, allow_end: bool, pointer: &str) -> Result<usize> {
+    if allow_end && token == "-" {
+        return Ok(len);
+    }
+
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && (index < len || (allow_end && index == len)) => Ok(index),
+        _ => Err(JsonPatchError::PathNotFound(pointer.to_string())),
+    }
+}
+
+fn locate(doc: &LoroDoc, pointer: &str) -> Result<Location> {
+    let mut tokens = parse_pointer(pointer)?;
+    let Some(key) = tokens.pop() else {
+        return Err(JsonPatchError::Unsupported(
+            "the whole doc cannot be the target".into(),
+        ));
+    };
+
+    let mut parent = Parent::Doc;
+    for (i, token) in tokens.iter().enumerate() {
+        match child(doc, &parent, token) {
+            Some(Child::Parent(p)) => parent = p,
+            Some(Child::Value(v)) => {
+                let mut rest = tokens[i + 1..].to_vec();
+                rest.push(key);
+                return Ok(Location::InValue(
+                    parent,
+                    token.clone(),
+                    v.to_json_value(),
+                    rest,
+                ));
+            }
+            None => return Err(JsonPatchError::PathNotFound(pointer.to_string())),
+        }
+    }
+
+    Ok(Location::Slot(parent, key))
+}
+
+fn child(doc: &LoroDoc, parent: &Parent, token: &str) -> Option<Child> {
+    let from_value_or_handler = |v: ValueOrHandler| match v {
+        ValueOrHandler::Value(v) => Child::Value(v),
+        ValueOrHandler::Handler(h) => Child::Parent(Parent::Container(h)),
+    };
+    let index = |len: usize| parse_index(token, len, false, "").ok();
+    match parent {
+        Parent::Doc => doc
+            .get_by_path(&[Index::Key(token.into())])
+            .map(from_value_or_handler),
+        Parent::Container(Handler::Map(m)) => m.get_(token).map(from_value_or_handler),
+        Parent::Container(Handler::List(l)) => l.get_(index(l.len())?).map(from_value_or_handler),
+        Parent::Container(Handler::MovableList(l)) => {
+            l.get_(index(l.len())?).map(from_value_or_handler)
+        }
+        Parent::Container(Handler::Tree(t)) => child(
+            doc,
+            &Parent::TreeChildren(t.clone(), TreeParentId::Root),
+            token,
+        ),
+        Parent::Container(_) => None,
+        Parent::TreeChildren(t, p) => {
+            let target = t.get_child_at(p, index(t.children_num(p)?)?)?;
+            Some(Child::Parent(Parent::TreeNode(t.clone(), target)))
+        }
+        Parent::TreeNode(t, target) => match token {
+            "meta" => Some(Child::Parent(Parent::Container(
+                t.get_meta(*target).ok()?.to_handler(),
+            ))),
+            "children" => Some(Child::Parent(Parent::TreeChildren(
+                t.clone(),
+                TreeParentId::Node(*target),
+            ))),
+            _ => tree_node_json(t, *target)
+                .get(token)
+                .map(|v| Child::Value(to_loro_value(v))),
+        },
+    }
+}
+
+fn get(doc: &LoroDoc, pointer: &str) -> Result<Value> {
+    let not_found = || JsonPatchError::PathNotFound(pointer.to_string());
+    if pointer.is_empty() {
+        return Ok(doc.get_deep_value().to_json_value());
+    }
+
+    match locate(doc, pointer)? {
+        Location::Slot(parent, key) => match child(doc, &parent, &key).ok_or_else(not_found)? {
+            Child::Value(v) => Ok(v.to_json_value()),
+            Child::Parent(p) => Ok(parent_json(&p)),
+        },
+        Location::InValue(_, _, v, rest) => {
+            let mut v = &v;
+            for token in rest.iter() {
+                v = match v {
+                    Value::Object(o) => o.get(token),
+                    Value::Array(a) => a.get(parse_index(token, a.len(), false, pointer)?),
+                    _ => None,
+                }
+                .ok_or_else(not_found)?;
+            }
+            Ok(v.clone())
+        }
+    }
+}
+
+fn parent_json(parent: &Parent) -> Value {
+    match parent {
+        Parent::Doc => unreachable!(),
+        Parent::Container(h) => HandlerTrait::get_deep_value(h).to_json_value(),
+        Parent::TreeChildren(t, p) => Value::Array(
+            t.children(p)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|x| tree_node_json(t, x))
+                .collect(),
+        ),
+        Parent::TreeNode(t, target) => tree_node_json(t, *target),
+    }
+}
+
+fn tree_node_json(tree: &TreeHandler, target: TreeID) -> Value {
+    let parent = match tree.get_node_parent(&target) {
+        Some(TreeParentId::Node(p)) => json!(p.to_string()),
+        _ => Value::Null,
+    };
+    let meta = tree
+        .get_meta(target)
+        .map(|m| m.get_deep_value().to_json_value())
+        .unwrap_or_else(|_| json!({}));
+    json!({
+        "id": target.to_string(),
+        "parent": parent,
+        "meta": meta,
+        "index": tree.get_index_by_tree_id(&target),
+        "fractional_index": tree.get_position_by_tree_id(&target).map(|x| x.to_string()),
+        "children": parent_json(&Parent::TreeChildren(tree.clone(), TreeParentId::Node(target))),
+    })
+}
+
+fn add(doc: &LoroDoc, parent: &Parent, key: &str, value: &Value) -> Result<()> {
+    match parent {
+        Parent::Doc => {
+            if let Some(Child::Parent(Parent::Container(h))) = child(doc, parent, key) {
+                if reconcile(&h, value)? {
+                    return Ok(());
+                }
+
+                return Err(JsonPatchError::Unsupported(format!(
+                    "root container {} cannot be replaced by {}",
+                    key, value
+                )));
+            }
+
+            let h = match value {
+                Value::Object(_) => doc.get_map(key).to_handler(),
+                Value::Array(_) => doc.get_list(key).to_handler(),
+                Value::String(_) => doc.get_text(key).to_handler(),
+                _ => {
+                    return Err(JsonPatchError::Unsupported(format!(
+                        "root container {} cannot be created from {}",
+                        key, value
+                    )))
+                }
+            };
+            reconcile(&h, value)?;
+        }
+        Parent::Container(Handler::Map(m)) => set_map_value(m, key, value)?,
+        Parent::Container(Handler::List(l)) => {
+            insert_into_list(l, parse_index(key, l.len(), true, key)?, value)?
+        }
+        Parent::Container(Handler::MovableList(l)) => {
+            insert_into_movable_list(l, parse_index(key, l.len(), true, key)?, value)?
+        }
+        Parent::Container(Handler::Tree(t)) => add(
+            doc,
+            &Parent::TreeChildren(t.clone(), TreeParentId::Root),
+            key,
+            value,
+        )?,
+        Parent::Container(_) => return Err(JsonPatchError::PathNotFound(key.to_string())),
+        Parent::TreeChildren(t, p) => {
+            let len = t.children_num(p).unwrap_or(0);
+            create_tree_node(t, *p, parse_index(key, len, true, key)?, value)?;
+        }
+        Parent::TreeNode(t, target) => match key {
+            "meta" => {
+                if !reconcile(&t.get_meta(*target)?.to_handler(), value)? {
+                    return Err(JsonPatchError::Unsupported(
+                        "the meta of a tree node should be an object".into(),
+                    ));
+                }
+            }
+            "id" | "parent" | "index" | "fractional_index" => {
+                // Derived from the structure of the tree
+            }
+            _ => {
+                return Err(JsonPatchError::Unsupported(format!(
+                    "cannot set {} of a tree node",
+                    key
+                )))
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn remove(parent: &Parent, key: &str, pointer: &str) -> Result<()> {
+    let not_found = || JsonPatchError::PathNotFound(pointer.to_string());
+    match parent {
+        Parent::Doc => {
+            return Err(JsonPatchError::Unsupported(format!(
+                "root container {} cannot be removed",
+                key
+            )))
+        }
+        Parent::Container(Handler::Map(m)) => {
+            m.get_(key).ok_or_else(not_found)?;
+            m.delete(key)?;
+        }
+        Parent::Container(Handler::List(l)) => {
+            l.delete(parse_index(key, l.len(), false, pointer)?, 1)?
+        }
+        Parent::Container(Handler::MovableList(l)) => {
+            l.delete(parse_index(key, l.len(), false, pointer)?, 1)?
+        }
+        Parent::Container(Handler::Tree(t)) => remove(
+            &Parent::TreeChildren(t.clone(), TreeParentId::Root),
+            key,
+            pointer,
+        )?,
+        Parent::Container(_) => return Err(not_found()),
+        Parent::TreeChildren(t, p) => {
+            let index = parse_index(key, t.children_num(p).unwrap_or(0), false, pointer)?;
+            t.delete(t.get_child_at(p, index).ok_or_else(not_found)?)?;
+        }
+        Parent::TreeNode(..) => {
+            return Err(JsonPatchError::Unsupported(format!(
+                "cannot remove {} of a tree node",
+                key
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+fn replace(doc: &LoroDoc, parent: &Parent, key: &str, value: &Value, pointer: &str) -> Result<()> {
+    let existing =
+        child(doc, parent, key).ok_or_else(|| JsonPatchError::PathNotFound(pointer.to_string()))?;
+    match (parent, existing) {
+        (Parent::Container(Handler::List(l)), existing) => {
+            let index = parse_index(key, l.len(), false, pointer)?;
+            if let Child::Parent(Parent::Container(h)) = existing {
+                if reconcile(&h, value)? {
+                    return Ok(());
+                }
+            }
+
+            l.delete(index, 1)?;
+            insert_into_list(l, index, value)?;
+        }
+        (Parent::Container(Handler::MovableList(l)), _) => {
+            // The existing child container is reused, otherwise the element is set in place
+            // so that it keeps its identity
+            let index = parse_index(key, l.len(), false, pointer)?;
+            handler::replace_movable_list_value(l, index, &to_loro_value(value))?;
+        }
+        (Parent::TreeChildren(..) | Parent::Container(Handler::Tree(_)), Child::Parent(node)) => {
+            // Only the meta of the node can be changed in place
+            if let Some(meta) = value.get("meta") {
+                add(doc, &node, "meta", meta)?;
+            }
+        }
+        _ => add(doc, parent, key, value)?,
+    }
+
+    Ok(())
+}
+
+/// Overwrite the plain value in the slot, so that it won't become a container
+fn replace_plain_value(parent: &Parent, key: &str, value: &Value, pointer: &str) -> Result<()> {
+    match parent {
+        Parent::Container(Handler::Map(m)) => m.insert(key, to_loro_value(value))?,
+        Parent::Container(Handler::List(l)) => {
+            let index = parse_index(key, l.len(), false, pointer)?;
+            l.delete(index, 1)?;
+            l.insert(index, to_loro_value(value))?;
+        }
+        Parent::Container(Handler::MovableList(l)) => l.set(
+            parse_index(key, l.len(), false, pointer)?,
+            to_loro_value(value),
+        )?,
+        _ => {
+            return Err(JsonPatchError::Unsupported(format!(
+                "cannot change the derived field {}",
+                pointer
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// Move the child natively if both locations are in the same movable list or tree.
+///
+/// Return false if the move cannot be applied natively.
+fn native_move(
+    from_parent: &Parent,
+    from_key: &str,
+    to_parent: &Parent,
+    to_key: &str,
+) -> Result<bool> {
+    let tree_children = |p: &Parent| match p {
+        Parent::Container(Handler::Tree(t)) => Some((t.clone(), TreeParentId::Root)),
+        Parent::TreeChildren(t, p) => Some((t.clone(), *p)),
+        _ => None,
+    };
+    match (from_parent, to_parent) {
+        (
+            Parent::Container(Handler::MovableList(a)),
+            Parent::Container(Handler::MovableList(b)),
+        ) if a.id() == b.id() => {
+            let from = parse_index(from_key, a.len(), false, from_key)?;
+            let to = parse_index(to_key, a.len(), false, to_key)?;
+            a.mov(from, to)?;
+            Ok(true)
+        }
+        _ => match (tree_children(from_parent), tree_children(to_parent)) {
+            (Some((a, from_p)), Some((b, to_p))) if a.id() == b.id() => {
+                let from_len = a.children_num(&from_p).unwrap_or(0);
+                let from = parse_index(from_key, from_len, false, from_key)?;
+                let target = a
+                    .get_child_at(&from_p, from)
+                    .ok_or_else(|| JsonPatchError::PathNotFound(from_key.to_string()))?;
+                let to_len = a.children_num(&to_p).unwrap_or(0);
+                let to_len = if from_p == to_p { to_len - 1 } else { to_len };
+                let to = parse_index(to_key, to_len, true, to_key)?;
+                if a.is_fractional_index_enabled() {
+                    a.move_to(target, to_p, to)?;
+                } else {
+                    // The order of siblings is not kept without fractional index
+                    a.mov(target, to_p)?;
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        },
+    }
+}
+
+/// Update the container in place to the given value with minimal ops.
+///
+/// Return false if the container cannot represent the value.
+fn reconcile(h: &Handler, value: &Value) -> Result<bool> {
+    Ok(h.reconcile(&to_loro_value(value))?)
+}
+
+fn set_map_value(m: &MapHandler, key: &str, value: &Value) -> Result<()> {
+    Ok(handler::set_map_value(m, key, &to_loro_value(value))?)
+}
+
+fn insert_into_list(l: &ListHandler, index: usize, value: &Value) -> Result<()> {
+    Ok(handler::insert_list_value(l, index, &to_loro_value(value))?)
+}
+
+fn insert_into_movable_list(l: &MovableListHandler, index: usize, value: &Value) -> Result<()> {
+    Ok(handler::insert_movable_list_value(
+        l,
+        index,
+        &to_loro_value(value),
+    )?)
+}
+
+fn create_tree_node(
+    tree: &TreeHandler,
+    parent: TreeParentId,
+    index: usize,
+    value: &Value,
+) -> Result<()> {
+    let target = if tree.is_fractional_index_enabled() {
+        tree.create_at(parent, index)?
+    } else {
+        // The order of siblings is not kept without fractional index
+        tree.create(parent)?
+    };
+    if let Some(meta) = value.get("meta") {
+        reconcile(&tree.get_meta(target)?.to_handler(), meta)?;
+    }
+    if let Some(Value::Array(children)) = value.get("children") {
+        for (i, child) in children.iter().enumerate() {
+            create_tree_node(tree, TreeParentId::Node(target), i, child)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn to_loro_value(value: &Value) -> LoroValue {
+    match value {
+        Value::Null => LoroValue::Null,
+        Value::Bool(b) => LoroValue::Bool(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => LoroValue::I64(i),
+            None => LoroValue::Double(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => LoroValue::String(s.as_str().into()),
+        Value::Array(a) => LoroValue::List(a.iter().map(to_loro_value).collect::<Vec<_>>().into()),
+        Value::Object(o) => LoroValue::Map(
+            o.iter()
+                .map(|(k, v)| (k.clone(), to_loro_value(v)))
+                .collect::<fxhash::FxHashMap<_, _>>()
+                .into(),
+        ),
+    }
+}
+
+fn json_add(target: &mut Value, tokens: &[String], value: Value, pointer: &str) -> Result<()> {
+    let (last, parent) = json_parent(target, tokens, pointer)?;
+    match parent {
+        Value::Object(o) => {
+            o.insert(last.clone(), value);
+        }
+        Value::Array(a) => {
+            let index = parse_index(last, a.len(), true, pointer)?;
+            a.insert(index, value);
+        }
+        _ => return Err(JsonPatchError::PathNotFound(pointer.to_string())),
+    }
+
+    Ok(())
+}
+
+fn json_remove(target: &mut Value, tokens: &[String], pointer: &str) -> Result<()> {
+    let not_found = || JsonPatchError::PathNotFound(pointer.to_string());
+    let (last, parent) = json_parent(target, tokens, pointer)?;
+    match parent {
+        Value::Object(o) => {
+            o.remove(last).ok_or_else(not_found)?;
+        }
+        Value::Array(a) => {
+            let index = parse_index(last, a.len(), false, pointer)?;
+            a.remove(index);
+        }
+        _ => return Err(not_found()),
+    }
+
+    Ok(())
+}
+
+fn json_parent<'a, 'b>(
+    target: &'a mut Value,
+    tokens: &'b [String],
+    pointer: &str,
+) -> Result<(&'b String, &'a mut Value)> {
+    let not_found = || JsonPatchError::PathNotFound(pointer.to_string());
+    let (last, tokens) = tokens.split_last().ok_or_else(not_found)?;
+    let mut parent = target;
+    for token in tokens {
+        parent = match parent {
+            Value::Object(o) => o.get_mut(token),
+            Value::Array(a) => {
+                let index = parse_index(token, a.len(), false, pointer)?;
+                a.get_mut(index)
+            }
+            _ => None,
+        }
+        .ok_or_else(not_found)?;
+    }
+
+    Ok((last, parent))
+}
+
+fn merge_map(map: &MapHandler, patch: &Map<String, Value>) -> Result<()> {
+    for (key, value) in patch.iter() {
+        match (map.get_(key), value) {
+            (Some(_), Value::Null) => map.delete(key)?,
+            (None, Value::Null) => {}
+            (Some(ValueOrHandler::Handler(Handler::Map(child))), Value::Object(v)) => {
+                merge_map(&child, v)?
+            }
+            (_, value) => set_map_value(map, key, &strip_nulls(value))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn is_empty_value(value: &LoroValue) -> bool {
+    match value {
+        LoroValue::Null => true,
+        LoroValue::String(s) => s.is_empty(),
+        LoroValue::List(l) => l.is_empty(),
+        LoroValue::Map(m) => m.is_empty(),
+        _ => false,
+    }
+}
+
+/// Remove the null members recursively, which is the result of merging the patch into
+/// a non-object value
+fn strip_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), strip_nulls(v)))
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
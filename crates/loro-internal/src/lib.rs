@@ -45,6 +45,7 @@ pub mod dag;
 pub mod encoding;
 pub(crate) mod fork;
 pub mod id;
+pub mod json_patch;
 #[cfg(feature = "jsonpath")]
 pub mod jsonpath;
 pub mod kv_store;
@@ -2,8 +2,8 @@ use either::Either;
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
 use loro_common::{
-    ContainerID, ContainerType, HasIdSpan, HasLamportSpan, IdSpan, LoroEncodeError, LoroResult,
-    LoroValue, ID,
+    ContainerID, ContainerType, HasIdSpan, HasLamportSpan, IdSpan, LoroEncodeError, LoroMapValue,
+    LoroResult, LoroValue, ID,
 };
 use rle::HasLength;
 use std::{
@@ -29,17 +29,21 @@ use crate::{
         idx::ContainerIdx, list::list_op::InnerListOp, richtext::config::StyleConfigMap,
         IntoContainerId,
     },
-    cursor::{AbsolutePosition, CannotFindRelativePosition, Cursor, PosQueryResult},
+    cursor::{
+        AbsolutePosition, CannotFindRelativePosition, Cursor, IdLookup, PosQueryResult,
+        RangeCursor, RangeQueryResult,
+    },
     dag::Dag,
-    diff_calc::DiffCalculator,
+    diff_calc::{ContainerDiffCalculator, DiffCalculator},
     encoding::{
         self, decode_snapshot, export_fast_snapshot, export_fast_updates,
-        export_fast_updates_in_range, export_shallow_snapshot, export_snapshot, export_snapshot_at,
-        export_state_only_snapshot, json_schema::json::JsonSchema, parse_header_and_body,
-        EncodeMode, ImportBlobMetadata, ImportStatus, ParsedHeaderAndBody,
+        export_fast_updates_in_range, export_shallow_snapshot, export_shallow_snapshot_since,
+        export_snapshot, export_snapshot_at, export_squashed_snapshot, export_state_only_snapshot,
+        json_schema::json::{JsonSchema, RedactError},
+        parse_header_and_body, EncodeMode, ImportBlobMetadata, ImportStatus, ParsedHeaderAndBody,
     },
     event::{str_to_path, EventTriggerKind, Index, InternalDocDiff},
-    handler::{Handler, MovableListHandler, TextHandler, TreeHandler, ValueOrHandler},
+    handler::{Handler, MovableListHandler, TextHandler, TextPosUnit, TreeHandler, ValueOrHandler},
     id::PeerID,
     op::InnerContent,
     oplog::{loro_dag::FrontiersNotIncluded, OpLog},
@@ -48,7 +52,7 @@ use crate::{
     txn::Transaction,
     undo::DiffBatch,
     utils::subscription::{SubscriberSetWithQueue, Subscription},
-    version::{shrink_frontiers, Frontiers, ImVersionVector},
+    version::{shrink_frontiers, Frontiers, ImVersionVector, VersionRange},
     ChangeMeta, DocDiff, HandlerTrait, InternalString, ListHandler, LoroError, MapHandler,
     VersionVector,
 };
@@ -344,6 +348,10 @@ impl LoroDoc {
             txn.set_msg(Some(msg.clone()));
         }
 
+        if let Some(metadata) = config.commit_metadata.as_ref() {
+            txn.set_metadata(Some(metadata.clone()));
+        }
+
         let id_span = txn.id_span();
         txn.commit().unwrap();
         if config.immediate_renew {
@@ -578,6 +586,90 @@ impl LoroDoc {
         json
     }
 
+    /// Export the json updates as NDJSON, one line per change.
+    ///
+    /// Unlike [`LoroDoc::export_json_updates`], it doesn't build the whole [`JsonSchema`] in memory.
+    /// The changes are written in causal order.
+    /// The output can be imported with [`LoroDoc::import_json_updates_ndjson`].
+    pub fn export_json_updates_ndjson<W: std::io::Write>(
+        &self,
+        start_vv: &VersionVector,
+        end_vv: &VersionVector,
+        writer: W,
+    ) -> std::io::Result<()> {
+        self.commit_then_stop();
+        let oplog = self.oplog.try_lock().unwrap();
+        let ans =
+            crate::encoding::json_schema::export_json_ndjson(&oplog, start_vv, end_vv, writer);
+        drop(oplog);
+        self.renew_txn_if_auto_commit();
+        ans
+    }
+
+    /// Import the NDJSON updates exported by [`LoroDoc::export_json_updates_ndjson`].
+    ///
+    /// The changes are read and imported in batches, so the whole input is never held in memory.
+    /// If an error occurs, the batches before it remain imported.
+    #[tracing::instrument(skip_all)]
+    pub fn import_json_updates_ndjson<R: std::io::BufRead>(
+        &self,
+        reader: R,
+    ) -> LoroResult<ImportStatus> {
+        self.commit_then_stop();
+        let result = self._import_json_updates_ndjson(reader);
+        self.emit_events();
+        self.renew_txn_if_auto_commit();
+        result
+    }
+
+    fn _import_json_updates_ndjson<R: std::io::BufRead>(
+        &self,
+        reader: R,
+    ) -> LoroResult<ImportStatus> {
+        const BATCH_SIZE: usize = 1024;
+        let mut reader = crate::encoding::json_schema::JsonUpdatesReader::new(reader);
+        let mut success = VersionRange::new();
+        let mut pending = VersionRange::new();
+        loop {
+            let changes = reader.next_batch(BATCH_SIZE)?;
+            if changes.is_empty() {
+                break;
+            }
+
+            let status = self.update_oplog_and_apply_delta_to_state_if_needed(
+                |oplog| {
+                    crate::encoding::json_schema::import_json_changes(
+                        oplog,
+                        changes,
+                        reader.peers(),
+                    )
+                },
+                Default::default(),
+            )?;
+            for (&peer, &(start, end)) in status.success.iter() {
+                success.extends_to_include_id_span(IdSpan::new(peer, start, end));
+            }
+            for (&peer, &(start, end)) in status.pending.iter().flat_map(|x| x.iter()) {
+                pending.extends_to_include_id_span(IdSpan::new(peer, start, end));
+            }
+        }
+
+        // The pending changes of the earlier batches may be imported by the later ones
+        let vv = self.oplog_vv();
+        pending.0.retain(|peer, (start, end)| {
+            *start = (*start).max(vv.get(peer).copied().unwrap_or(0));
+            start < end
+        });
+        Ok(ImportStatus {
+            success,
+            pending: if pending.is_empty() {
+                None
+            } else {
+                Some(pending)
+            },
+        })
+    }
+
     /// Get the version vector of the current OpLog
     #[inline]
     pub fn oplog_vv(&self) -> VersionVector {
@@ -616,6 +708,28 @@ impl LoroDoc {
         self.get_by_path(&path)
     }
 
+    /// Update the root container to the target deep value with the smallest set of ops.
+    ///
+    /// If the root container doesn't exist yet, a map, list or text is created according
+    /// to the type of the value. See [`Handler::set_deep_value`] for the details.
+    pub fn set_deep_value(&self, root: &str, value: &LoroValue) -> LoroResult<()> {
+        let handler = match self.get_by_path(&[Index::Key(root.into())]) {
+            Some(ValueOrHandler::Handler(h)) => h,
+            _ => match value {
+                LoroValue::Map(_) => self.get_map(root).to_handler(),
+                LoroValue::List(_) => self.get_list(root).to_handler(),
+                LoroValue::String(_) => self.get_text(root).to_handler(),
+                _ => {
+                    return Err(LoroError::ArgErr(
+                        format!("Cannot create root container {} from {:?}", root, value)
+                            .into_boxed_str(),
+                    ))
+                }
+            },
+        };
+        handler.set_deep_value(value)
+    }
+
     #[inline]
     pub fn get_handler(&self, id: ContainerID) -> Handler {
         self.assert_container_exists(&id);
@@ -1093,6 +1207,26 @@ impl LoroDoc {
         Ok(())
     }
 
+    /// Get the frontiers of the latest causally consistent version whose changes all have
+    /// timestamps less than or equal to `timestamp`.
+    ///
+    /// See [OpLog::frontiers_at_timestamp] for how skewed clocks are handled.
+    pub fn frontiers_at_timestamp(&self, timestamp: Timestamp) -> Frontiers {
+        self.commit_then_renew();
+        self.oplog
+            .try_lock()
+            .unwrap()
+            .frontiers_at_timestamp(timestamp)
+    }
+
+    /// Checkout [DocState] to the version as of the given timestamp.
+    ///
+    /// It's the same as `checkout(&frontiers_at_timestamp(timestamp))`.
+    pub fn checkout_at_timestamp(&self, timestamp: Timestamp) -> LoroResult<()> {
+        let frontiers = self.frontiers_at_timestamp(timestamp);
+        self.checkout(&frontiers)
+    }
+
     #[instrument(level = "info", skip(self))]
     pub(crate) fn checkout_without_emitting(
         &self,
@@ -1350,83 +1484,11 @@ impl LoroDoc {
             // commit the txn to make sure we can query the history correctly
             drop(state);
             self.commit_then_renew();
-            let oplog = self.oplog().try_lock().unwrap();
             // TODO: assert pos.id is not unknown
             if let Some(id) = pos.id {
-                let idx = oplog
-                    .arena
-                    .id_to_idx(&pos.container)
-                    .ok_or(CannotFindRelativePosition::ContainerDeleted)?;
-                // We know where the target id is when we trace back to the delete_op_id.
-                let Some(delete_op_id) = find_last_delete_op(&oplog, id, idx) else {
-                    if oplog.shallow_since_vv().includes_id(id) {
-                        return Err(CannotFindRelativePosition::HistoryCleared);
-                    }
-
-                    tracing::error!("Cannot find id {}", id);
-                    return Err(CannotFindRelativePosition::IdNotFound);
-                };
-                // Should use persist mode so that it will force all the diff calculators to use the `checkout` mode
-                let mut diff_calc = DiffCalculator::new(true);
-                let before_frontiers: Frontiers = oplog.dag.find_deps_of_id(delete_op_id);
-                let before = &oplog.dag.frontiers_to_vv(&before_frontiers).unwrap();
-                // TODO: PERF: it doesn't need to calc the effects here
-                diff_calc.calc_diff_internal(
-                    &oplog,
-                    before,
-                    &before_frontiers,
-                    oplog.vv(),
-                    oplog.frontiers(),
-                    Some(&|target| idx == target),
-                );
-                // TODO: remove depth info
-                let depth = self.arena.get_depth(idx);
-                let (_, diff_calc) = &mut diff_calc.get_or_create_calc(idx, depth);
-                match diff_calc {
-                    crate::diff_calc::ContainerDiffCalculator::Richtext(text) => {
-                        let c = text.get_id_latest_pos(id).unwrap();
-                        let new_pos = c.pos;
-                        let handler = self.get_text(&pos.container);
-                        let current_pos = handler.convert_entity_index_to_event_index(new_pos);
-                        Ok(PosQueryResult {
-                            update: handler.get_cursor(current_pos, c.side),
-                            current: AbsolutePosition {
-                                pos: current_pos,
-                                side: c.side,
-                            },
-                        })
-                    }
-                    crate::diff_calc::ContainerDiffCalculator::List(list) => {
-                        let c = list.get_id_latest_pos(id).unwrap();
-                        let new_pos = c.pos;
-                        let handler = self.get_list(&pos.container);
-                        Ok(PosQueryResult {
-                            update: handler.get_cursor(new_pos, c.side),
-                            current: AbsolutePosition {
-                                pos: new_pos,
-                                side: c.side,
-                            },
-                        })
-                    }
-                    crate::diff_calc::ContainerDiffCalculator::MovableList(list) => {
-                        let c = list.get_id_latest_pos(id).unwrap();
-                        let new_pos = c.pos;
-                        let handler = self.get_movable_list(&pos.container);
-                        let new_pos = handler.op_pos_to_user_pos(new_pos);
-                        Ok(PosQueryResult {
-                            update: handler.get_cursor(new_pos, c.side),
-                            current: AbsolutePosition {
-                                pos: new_pos,
-                                side: c.side,
-                            },
-                        })
-                    }
-                    crate::diff_calc::ContainerDiffCalculator::Tree(_) => unreachable!(),
-                    crate::diff_calc::ContainerDiffCalculator::Map(_) => unreachable!(),
-                    #[cfg(feature = "counter")]
-                    crate::diff_calc::ContainerDiffCalculator::Counter(_) => unreachable!(),
-                    crate::diff_calc::ContainerDiffCalculator::Unknown(_) => unreachable!(),
-                }
+                self.query_deleted_pos_batch(&pos.container, &[id])
+                    .pop()
+                    .unwrap()
             } else {
                 match pos.container.container_type() {
                     ContainerType::Text => {
@@ -1484,6 +1546,312 @@ impl LoroDoc {
         }
     }
 
+    /// Get the positions of many cursors at once, in the same order as `cursors`.
+    ///
+    /// It gives the same results as [`LoroDoc::query_pos`] on each cursor, but the cursors
+    /// of a container are resolved in a single pass over its state. The cursors whose
+    /// elements are deleted share one walk over the history and one diff calculation per
+    /// container.
+    pub fn query_pos_batch(
+        &self,
+        cursors: &[Cursor],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        let mut groups: FxHashMap<&ContainerID, Vec<usize>> = FxHashMap::default();
+        for (i, cursor) in cursors.iter().enumerate() {
+            groups.entry(&cursor.container).or_default().push(i);
+        }
+
+        let mut ans = vec![None; cursors.len()];
+        let mut pending = Vec::new();
+        {
+            let mut state = self.state.try_lock().unwrap();
+            for (container, indexes) in groups {
+                let group: Vec<&Cursor> = indexes.iter().map(|&i| &cursors[i]).collect();
+                let positions = state.get_relative_positions(container, &group, true);
+                let mut deleted = Vec::new();
+                for (i, pos) in indexes.into_iter().zip(positions) {
+                    match pos {
+                        Some(pos) => {
+                            ans[i] = Some(Ok(PosQueryResult {
+                                update: None,
+                                current: AbsolutePosition {
+                                    pos,
+                                    side: cursors[i].side,
+                                },
+                            }))
+                        }
+                        None => deleted.push(i),
+                    }
+                }
+
+                if !deleted.is_empty() {
+                    pending.push((container, deleted));
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            // commit the txn to make sure we can query the history correctly
+            self.commit_then_renew();
+        }
+
+        for (container, indexes) in pending {
+            let (with_id, without_id): (Vec<usize>, Vec<usize>) =
+                indexes.into_iter().partition(|&i| cursors[i].id.is_some());
+            for i in without_id {
+                ans[i] = Some(self.query_pos_internal(&cursors[i], true));
+            }
+
+            if with_id.is_empty() {
+                continue;
+            }
+
+            let ids: Vec<ID> = with_id.iter().map(|&i| cursors[i].id.unwrap()).collect();
+            for (i, result) in with_id
+                .into_iter()
+                .zip(self.query_deleted_pos_batch(container, &ids))
+            {
+                ans[i] = Some(result);
+            }
+        }
+
+        ans.into_iter().map(|x| x.unwrap()).collect()
+    }
+
+    /// Trace back the positions of the deleted elements `ids` of `container`.
+    fn query_deleted_pos_batch(
+        &self,
+        container: &ContainerID,
+        ids: &[ID],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        let oplog = self.oplog().try_lock().unwrap();
+        let Some(idx) = oplog.arena.id_to_idx(container) else {
+            return vec![Err(CannotFindRelativePosition::ContainerDeleted); ids.len()];
+        };
+
+        // We know where the target ids are when we trace back to the delete ops.
+        let delete_op_ids = find_last_delete_ops(&oplog, ids, idx);
+        // The diff from the version before all the deletions covers every one of them
+        let mut before: Option<(VersionVector, Option<Frontiers>)> = None;
+        for delete_op_id in delete_op_ids.iter().flatten() {
+            let deps = oplog.dag.find_deps_of_id(*delete_op_id);
+            let vv = oplog.dag.frontiers_to_vv(&deps).unwrap();
+            before = Some(match before {
+                None => (vv, Some(deps)),
+                Some((before_vv, before_frontiers)) if before_vv == vv => {
+                    (before_vv, before_frontiers)
+                }
+                Some((before_vv, _)) => (before_vv.intersection(&vv), None),
+            });
+        }
+
+        // Should use persist mode so that it will force all the diff calculators to use the `checkout` mode
+        let mut diff_calc = DiffCalculator::new(true);
+        if let Some((before, before_frontiers)) = &before {
+            let before_frontiers = before_frontiers
+                .clone()
+                .unwrap_or_else(|| oplog.dag.vv_to_frontiers(before));
+            // TODO: PERF: it doesn't need to calc the effects here
+            diff_calc.calc_diff_internal(
+                &oplog,
+                before,
+                &before_frontiers,
+                oplog.vv(),
+                oplog.frontiers(),
+                Some(&|target| idx == target),
+            );
+        }
+
+        // TODO: remove depth info
+        let depth = self.arena.get_depth(idx);
+        let (_, diff_calc) = &mut diff_calc.get_or_create_calc(idx, depth);
+        ids.iter()
+            .zip(delete_op_ids)
+            .map(|(&id, delete_op_id)| {
+                if delete_op_id.is_none() {
+                    if oplog.shallow_since_vv().includes_id(id) {
+                        return Err(CannotFindRelativePosition::HistoryCleared);
+                    }
+
+                    tracing::error!("Cannot find id {}", id);
+                    return Err(CannotFindRelativePosition::IdNotFound);
+                }
+
+                Ok(self.pos_in_diff_calc(diff_calc, container, id))
+            })
+            .collect()
+    }
+
+    /// Get the current position of the deleted element `id` from the diff calculator
+    /// that has traced back to the version before its deletion.
+    fn pos_in_diff_calc(
+        &self,
+        diff_calc: &ContainerDiffCalculator,
+        container: &ContainerID,
+        id: ID,
+    ) -> PosQueryResult {
+        match diff_calc {
+            ContainerDiffCalculator::Richtext(text) => {
+                let c = text.get_id_latest_pos(id).unwrap();
+                let new_pos = c.pos;
+                let handler = self.get_text(container);
+                let current_pos = handler.convert_entity_index_to_event_index(new_pos);
+                PosQueryResult {
+                    update: handler.get_cursor(current_pos, c.side),
+                    current: AbsolutePosition {
+                        pos: current_pos,
+                        side: c.side,
+                    },
+                }
+            }
+            ContainerDiffCalculator::List(list) => {
+                let c = list.get_id_latest_pos(id).unwrap();
+                let new_pos = c.pos;
+                let handler = self.get_list(container);
+                PosQueryResult {
+                    update: handler.get_cursor(new_pos, c.side),
+                    current: AbsolutePosition {
+                        pos: new_pos,
+                        side: c.side,
+                    },
+                }
+            }
+            ContainerDiffCalculator::MovableList(list) => {
+                let c = list.get_id_latest_pos(id).unwrap();
+                let new_pos = c.pos;
+                let handler = self.get_movable_list(container);
+                let new_pos = handler.op_pos_to_user_pos(new_pos);
+                PosQueryResult {
+                    update: handler.get_cursor(new_pos, c.side),
+                    current: AbsolutePosition {
+                        pos: new_pos,
+                        side: c.side,
+                    },
+                }
+            }
+            ContainerDiffCalculator::Tree(_) => unreachable!(),
+            ContainerDiffCalculator::Map(_) => unreachable!(),
+            #[cfg(feature = "counter")]
+            ContainerDiffCalculator::Counter(_) => unreachable!(),
+            ContainerDiffCalculator::Unknown(_) => unreachable!(),
+        }
+    }
+
+    /// Get the current range of a [`RangeCursor`].
+    ///
+    /// The positions in a text are in `unit`, other containers ignore it. The deleted ends
+    /// are traced back in the history like [`LoroDoc::query_pos`]. If all the elements of a
+    /// range that is not collapsed are deleted, it returns [`RangeQueryResult::Deleted`].
+    pub fn query_range(
+        &self,
+        range: &RangeCursor,
+        unit: TextPosUnit,
+    ) -> Result<RangeQueryResult, CannotFindRelativePosition> {
+        let start = self.query_pos(&range.start)?;
+        let end = if range.is_collapsed() {
+            start.clone()
+        } else {
+            self.query_pos(&range.end)?
+        };
+
+        // `end` is at the right of its element, unless the element is deleted
+        let end_alive = !range.is_collapsed() && range.end.id.is_some() && end.update.is_none();
+        let container = &range.start.container;
+        let text =
+            (container.container_type() == ContainerType::Text).then(|| self.get_text(container));
+        let start_pos = start.current.pos;
+        let end_pos = match &text {
+            Some(text) if end_alive => text.event_index_after(end.current.pos),
+            _ => end.current.pos + end_alive as usize,
+        };
+        if !range.is_collapsed() && start_pos >= end_pos {
+            return Ok(RangeQueryResult::Deleted);
+        }
+
+        let update = if start.update.is_some() || end.update.is_some() {
+            match container.container_type() {
+                ContainerType::Text => text
+                    .as_ref()
+                    .and_then(|text| text.get_range_cursor(start_pos, end_pos)),
+                ContainerType::List => {
+                    let list = self.get_list(container);
+                    RangeCursor::from_range(start_pos..end_pos, |pos, side| {
+                        list.get_cursor(pos, side)
+                    })
+                }
+                ContainerType::MovableList => {
+                    let list = self.get_movable_list(container);
+                    RangeCursor::from_range(start_pos..end_pos, |pos, side| {
+                        list.get_cursor(pos, side)
+                    })
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let range = match &text {
+            Some(text) => {
+                text.event_index_to_pos(start_pos, unit)..text.event_index_to_pos(end_pos, unit)
+            }
+            None => start_pos..end_pos,
+        };
+        Ok(RangeQueryResult::Range { range, update })
+    }
+
+    /// Get the cursors that cannot be resolved in the shallow snapshot exported from `frontiers`.
+    ///
+    /// A cursor becomes unresolvable when its target has been deleted and the history
+    /// needed to trace back its position is trimmed. The check is based on the current
+    /// state of the document, so it should be called on an attached document.
+    pub fn unresolvable_cursors_in_shallow_snapshot(
+        &self,
+        frontiers: &Frontiers,
+        cursors: &[Cursor],
+    ) -> Result<Vec<Cursor>, LoroEncodeError> {
+        self.commit_then_renew();
+        let oplog = self.oplog.try_lock().unwrap();
+        if !oplog.dag.can_export_shallow_snapshot_on(frontiers) {
+            return Err(LoroEncodeError::FrontiersNotFound(format!(
+                "{:?}",
+                frontiers
+            )));
+        }
+
+        let (_, start_vv) =
+            encoding::shallow_snapshot::calc_shallow_doc_start_version(&oplog, frontiers);
+        let mut state = self.state.try_lock().unwrap();
+        let ans = cursors
+            .iter()
+            .filter(|cursor| {
+                let Some(id) = cursor.id else {
+                    return false;
+                };
+
+                if state.get_relative_position(cursor, false).is_some() {
+                    return false;
+                }
+
+                let Some(idx) = oplog.arena.id_to_idx(&cursor.container) else {
+                    return true;
+                };
+                let Some(delete_op_id) = find_last_delete_op(&oplog, id, idx) else {
+                    return true;
+                };
+                // The position is traced back from the version before the deletion,
+                // so the history since that version must be kept
+                let before = oplog.dag.find_deps_of_id(delete_op_id);
+                match oplog.dag.frontiers_to_vv(&before) {
+                    Some(vv) => !vv.includes_vv(&start_vv),
+                    None => true,
+                }
+            })
+            .cloned()
+            .collect();
+        Ok(ans)
+    }
+
     /// Free the history cache that is used for making checkout faster.
     ///
     /// If you use checkout that switching to an old/concurrent version, the history cache will be built.
@@ -1542,12 +1910,51 @@ impl LoroDoc {
                 None => export_state_only_snapshot(self, &self.oplog_frontiers())?,
             },
             ExportMode::SnapshotAt { version } => export_snapshot_at(self, &version)?,
+            ExportMode::SquashedSnapshot { merge_interval } => {
+                export_squashed_snapshot(self, merge_interval)
+            }
+            ExportMode::ShallowSince { older_than } => {
+                export_shallow_snapshot_since(self, older_than)?
+            }
         };
 
         self.renew_txn_if_auto_commit();
         Ok(ans)
     }
 
+    /// Redact the content of the ops in the given range from the history and the state.
+    ///
+    /// It follows the same rules as [`crate::encoding::json_schema::json::redact`]. The op ids,
+    /// lengths and lamports are kept, so the redacted doc can still sync with other peers.
+    ///
+    /// The redaction is not part of the history, so it's not carried by the exported updates.
+    /// Each peer that should purge the content calls this method with the same range, for
+    /// example after receiving the range from the app's own channel. Importing the redacted
+    /// ops doesn't overwrite the content that a peer already has.
+    ///
+    /// The state is rebuilt from the redacted history, and the change is emitted as a
+    /// checkout event. The state of a shallow doc at its shallow root is not affected.
+    pub fn redact(&self, range: &VersionRange) -> Result<(), RedactError> {
+        self.commit_then_stop();
+        let ans = self.oplog.try_lock().unwrap().redact(range);
+        if !matches!(ans, Ok(false)) {
+            // Rebuild the state from the redacted history
+            let was_detached = self.is_detached();
+            let state_frontiers = self.state_frontiers();
+            let shallow_root = self.shallow_since_frontiers();
+            *self.diff_calculator.try_lock().unwrap() = DiffCalculator::new(true);
+            self.checkout_without_emitting(&shallow_root, false)
+                .unwrap();
+            self.checkout_without_emitting(&state_frontiers, false)
+                .unwrap();
+            self.emit_events();
+            self.set_detached(was_detached);
+        }
+
+        self.renew_txn_if_auto_commit();
+        ans.map(|_| ())
+    }
+
     /// The doc only contains the history since the shallow history start version vector.
     ///
     /// This is empty if the doc is not shallow.
@@ -1675,28 +2082,112 @@ impl LoroDoc {
 
         set
     }
+
+    /// Get the changes that modified the given container or any of its descendants.
+    ///
+    /// If `since` is provided, only the changes after that version are returned.
+    /// The changes are sorted by their lamport timestamps.
+    ///
+    /// The first call builds an index from containers to changes, which is maintained
+    /// incrementally afterwards. It can be freed by `free_container_changes_index`.
+    pub fn changes_touching(
+        &self,
+        container: &ContainerID,
+        since: Option<&Frontiers>,
+    ) -> LoroResult<Vec<ChangeMeta>> {
+        self.commit_then_renew();
+        let mut oplog = self.oplog().try_lock().unwrap();
+        let Some(idx) = oplog.arena.id_to_idx(container) else {
+            return Ok(Vec::new());
+        };
+
+        let since_vv = match since {
+            Some(f) => Some(
+                oplog
+                    .dag
+                    .frontiers_to_vv(f)
+                    .ok_or_else(|| LoroError::FrontiersNotFound(f.iter().next().unwrap()))?,
+            ),
+            None => None,
+        };
+
+        let spans = oplog.spans_touching_container(idx, since_vv.as_ref());
+        let mut visited = FxHashSet::default();
+        let mut ans = Vec::new();
+        for span in spans {
+            for change in oplog.change_store().iter_changes(span) {
+                if visited.insert(change.id) {
+                    ans.push(ChangeMeta::from_change(&change));
+                }
+            }
+        }
+
+        ans.sort_unstable_by_key(|c| (c.lamport, c.id.peer));
+        Ok(ans)
+    }
+
+    /// Free the index built by `changes_touching`.
+    pub fn free_container_changes_index(&self) {
+        self.oplog()
+            .try_lock()
+            .unwrap()
+            .free_container_changes_index();
+    }
 }
 
 // FIXME: PERF: This method is quite slow because it iterates all the changes
 fn find_last_delete_op(oplog: &OpLog, id: ID, idx: ContainerIdx) -> Option<ID> {
-    let start_vv = oplog
-        .dag
-        .frontiers_to_vv(&id.into())
-        .unwrap_or_else(|| oplog.shallow_since_vv().to_vv());
+    find_last_delete_ops(oplog, &[id], idx).pop().unwrap()
+}
+
+/// Find the last op that deletes each of the ids in one walk over the history.
+fn find_last_delete_ops(oplog: &OpLog, ids: &[ID], idx: ContainerIdx) -> Vec<Option<ID>> {
+    let mut ans = vec![None; ids.len()];
+    // Start from the earliest version where an id is created
+    let mut start_vv: Option<VersionVector> = None;
+    for &id in ids {
+        let vv = oplog
+            .dag
+            .frontiers_to_vv(&id.into())
+            .unwrap_or_else(|| oplog.shallow_since_vv().to_vv());
+        start_vv = Some(match start_vv {
+            Some(start_vv) => start_vv.intersection(&vv),
+            None => vv,
+        });
+    }
+
+    let Some(start_vv) = start_vv else {
+        return ans;
+    };
+
+    let lookup = IdLookup::new(ids);
+    let mut remaining = ids.len();
     for change in oplog.iter_changes_causally_rev(&start_vv, oplog.vv()) {
         for op in change.ops.iter().rev() {
             if op.container != idx {
                 continue;
             }
             if let InnerContent::List(InnerListOp::Delete(d)) = &op.content {
-                if d.id_start.to_span(d.atom_len()).contains(id) {
-                    return Some(ID::new(change.peer(), op.counter));
+                let span = d.id_start.to_span(d.atom_len());
+                lookup.find_in(
+                    span.peer,
+                    span.counter.min()..span.counter.norm_end(),
+                    |slot, _| {
+                        if ans[slot].is_none() {
+                            ans[slot] = Some(ID::new(change.peer(), op.counter));
+                            remaining -= 1;
+                        }
+                    },
+                );
+
+                if remaining == 0 {
+                    return ans;
                 }
             }
         }
     }
 
-    None
+    ans
 }
 
 #[derive(Debug)]
@@ -1717,6 +2208,7 @@ pub struct CommitOptions {
     pub immediate_renew: bool,
     pub timestamp: Option<Timestamp>,
     pub commit_msg: Option<Arc<str>>,
+    pub commit_metadata: Option<LoroMapValue>,
 }
 
 impl CommitOptions {
@@ -1726,6 +2218,7 @@ impl CommitOptions {
             immediate_renew: true,
             timestamp: None,
             commit_msg: None,
+            commit_metadata: None,
         }
     }
 
@@ -1749,6 +2242,14 @@ impl CommitOptions {
         self
     }
 
+    /// Attach user defined metadata to the change, e.g. author id or client version.
+    ///
+    /// Changes with different metadata won't be merged.
+    pub fn commit_metadata(mut self, metadata: impl Into<LoroMapValue>) -> Self {
+        self.commit_metadata = Some(metadata.into());
+        self
+    }
+
     pub fn set_origin(&mut self, origin: Option<&str>) {
         self.origin = origin.map(|x| x.into())
     }
@@ -129,11 +129,7 @@ impl Op {
         match &self.content {
             InnerContent::List(l) => match l {
                 crate::container::list::list_op::InnerListOp::Insert { .. } => {
-                    if matches!(self.container.get_type(), ContainerType::Text) {
-                        Some(size.min(self.atom_len()))
-                    } else {
-                        Some((size / 4).min(self.atom_len()))
-                    }
+                    Some((size / 4).min(self.atom_len()))
                 }
                 crate::container::list::list_op::InnerListOp::InsertText { .. } => {
                     Some(size.min(self.atom_len()))
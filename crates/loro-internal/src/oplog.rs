@@ -1,4 +1,5 @@
 mod change_store;
+mod container_changes_index;
 pub(crate) mod loro_dag;
 mod pending_changes;
 
@@ -11,20 +12,23 @@ use std::sync::Mutex;
 use tracing::{debug, trace, trace_span};
 
 use self::change_store::iter::MergedChangeIter;
+use self::container_changes_index::ContainerChangesIndex;
 use self::pending_changes::PendingChanges;
 use super::arena::SharedArena;
 use crate::change::{get_sys_timestamp, Change, Lamport, Timestamp};
 use crate::configure::Configure;
+use crate::container::idx::ContainerIdx;
 use crate::container::list::list_op;
 use crate::dag::{Dag, DagUtils};
 use crate::diff_calc::DiffMode;
+use crate::encoding::json_schema::json::RedactError;
 use crate::encoding::{decode_oplog, encode_oplog, EncodeMode};
 use crate::encoding::{ImportStatus, ParsedHeaderAndBody};
 use crate::history_cache::ContainerHistoryCache;
 use crate::id::{Counter, PeerID, ID};
 use crate::op::{FutureInnerContent, ListSlice, RawOpContent, RemoteOp, RichOp};
 use crate::span::{HasCounterSpan, HasLamportSpan};
-use crate::version::{Frontiers, ImVersionVector, VersionVector};
+use crate::version::{Frontiers, ImVersionVector, VersionRange, VersionVector};
 use crate::LoroError;
 use change_store::BlockOpRef;
 use loro_common::{IdLp, IdSpan};
@@ -46,6 +50,9 @@ pub struct OpLog {
     pub(crate) arena: SharedArena,
     change_store: ChangeStore,
     history_cache: Mutex<ContainerHistoryCache>,
+    /// The index from containers to the ops that modified them.
+    /// It's built lazily when it's queried for the first time.
+    container_changes_index: Option<ContainerChangesIndex>,
     /// Pending changes that haven't been applied to the dag.
     /// A change can be imported only when all its deps are already imported.
     /// Key is the ID of the missing dep
@@ -74,6 +81,7 @@ impl OpLog {
         Self {
             history_cache: Mutex::new(ContainerHistoryCache::new(change_store.clone(), None)),
             dag: AppDag::new(change_store.clone()),
+            container_changes_index: None,
             change_store,
             arena,
             pending_changes: Default::default(),
@@ -117,6 +125,47 @@ impl OpLog {
         timestamp
     }
 
+    /// Get the frontiers of the maximal causally closed set of changes whose timestamps are
+    /// less than or equal to `timestamp`.
+    ///
+    /// A change is included only if all of its deps are included. So if a peer's clock is ahead
+    /// of the others, its changes and all the changes that depend on them are excluded until the
+    /// given timestamp catches up, even if the descendants carry smaller timestamps.
+    ///
+    /// For a shallow doc, the history before the shallow root is always included.
+    pub fn frontiers_at_timestamp(&self, timestamp: Timestamp) -> Frontiers {
+        let shallow_vv = self.shallow_since_vv().to_vv();
+        let mut changes = Vec::with_capacity(self.len_changes());
+        self.change_store.visit_all_changes(&mut |c| {
+            changes.push((c.lamport, c.id, c.ctr_end(), c.timestamp, c.deps.clone()));
+        });
+        changes.sort_unstable_by_key(|(lamport, id, ..)| (*lamport, id.peer));
+
+        // Deps always have smaller lamports than their dependents,
+        // so visiting the changes in the lamport order is enough.
+        let mut vv = shallow_vv.clone();
+        for (_, id, ctr_end, ts, deps) in changes {
+            if ts > timestamp {
+                continue;
+            }
+
+            let start = vv.get(&id.peer).copied().unwrap_or(0);
+            if start < id.counter || start >= ctr_end {
+                continue;
+            }
+
+            if deps.iter().all(|dep| vv.includes_id(dep)) {
+                vv.set_end(ID::new(id.peer, ctr_end));
+            }
+        }
+
+        if vv == shallow_vv {
+            return self.shallow_since_frontiers().clone();
+        }
+
+        self.dag.vv_to_frontiers(&vv)
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.dag.is_empty() && self.arena.can_import_snapshot()
@@ -158,6 +207,45 @@ impl OpLog {
         history_cache.free();
     }
 
+    /// Redact the content of the ops in the given range.
+    ///
+    /// Returns whether any content is changed. The history cache is freed in that case
+    /// because it may contain the original content.
+    pub(crate) fn redact(&self, range: &VersionRange) -> Result<bool, RedactError> {
+        let ans = self.change_store.redact(range);
+        if !matches!(ans, Ok(false)) {
+            self.free_history_cache();
+        }
+
+        ans
+    }
+
+    /// Get the op spans that modified the container or any of its descendants.
+    ///
+    /// The ops included by `since` are excluded. The result is sorted by peer and counter.
+    pub(crate) fn spans_touching_container(
+        &mut self,
+        container: ContainerIdx,
+        since: Option<&VersionVector>,
+    ) -> Vec<IdSpan> {
+        let mut index = self
+            .container_changes_index
+            .take()
+            .unwrap_or_else(|| ContainerChangesIndex::new(self.shallow_since_vv().to_vv()));
+        index.catch_up(self);
+        let ans = index.spans_touching(container, since);
+        self.container_changes_index = Some(index);
+        ans
+    }
+
+    pub fn has_container_changes_index(&self) -> bool {
+        self.container_changes_index.is_some()
+    }
+
+    pub fn free_container_changes_index(&mut self) {
+        self.container_changes_index = None;
+    }
+
     /// Import a change.
     ///
     /// Pending changes that haven't been applied to the dag.
@@ -335,6 +423,18 @@ impl OpLog {
             .export_from(vv, f, self.vv(), self.frontiers())
     }
 
+    /// Export all the changes in the oplog, merging the consecutive changes of
+    /// the same peer that are within `merge_interval` seconds
+    pub(crate) fn export_squashed_change_store(&self, merge_interval: i64) -> Bytes {
+        self.change_store.export_squashed_from(
+            &self.shallow_since_vv().to_vv(),
+            self.shallow_since_frontiers(),
+            self.vv(),
+            self.frontiers(),
+            merge_interval,
+        )
+    }
+
     #[inline(always)]
     pub(crate) fn export_change_store_in_range(
         &self,
@@ -631,6 +731,7 @@ pub(crate) fn convert_change_to_remote(
         lamport: change.lamport,
         timestamp: change.timestamp,
         commit_msg: change.commit_msg.clone(),
+        commit_metadata: change.commit_metadata.clone(),
     }
 }
 
@@ -643,18 +744,10 @@ pub(crate) fn local_op_to_remote(
     match &op.content {
         crate::op::InnerContent::List(list) => match list {
             list_op::InnerListOp::Insert { slice, pos } => match container.container_type() {
-                loro_common::ContainerType::Text => {
-                    let str = arena
-                        .slice_str_by_unicode_range(slice.0.start as usize..slice.0.end as usize);
-                    contents.push(RawOpContent::List(list_op::ListOp::Insert {
-                        slice: ListSlice::RawStr {
-                            unicode_len: str.chars().count(),
-                            str: Cow::Owned(str),
-                        },
-                        pos: *pos,
-                    }));
-                }
-                loro_common::ContainerType::List | loro_common::ContainerType::MovableList => {
+                // Text containers use it for embeds
+                loro_common::ContainerType::Text
+                | loro_common::ContainerType::List
+                | loro_common::ContainerType::MovableList => {
                     contents.push(RawOpContent::List(list_op::ListOp::Insert {
                         slice: ListSlice::RawData(Cow::Owned(
                             arena.get_values(slice.0.start as usize..slice.0.end as usize),
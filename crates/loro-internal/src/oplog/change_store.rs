@@ -3,11 +3,12 @@ use super::{loro_dag::AppDagNodeInner, AppDagNode};
 use crate::{
     arena::SharedArena,
     change::Change,
+    encoding::json_schema::json::RedactError,
     estimated_size::EstimatedSize,
     kv_store::KvStore,
     op::Op,
     parent::register_container_and_parent_link,
-    version::{Frontiers, ImVersionVector},
+    version::{Frontiers, ImVersionVector, VersionRange},
     VersionVector,
 };
 use block_encode::decode_block_range;
@@ -31,6 +32,7 @@ use tracing::{debug, info_span, trace, warn};
 mod block_encode;
 mod block_meta_encode;
 pub(super) mod iter;
+mod redact;
 
 #[cfg(not(test))]
 const MAX_BLOCK_SIZE: usize = 1024 * 4;
@@ -146,7 +148,46 @@ impl ChangeStore {
         latest_vv: &VersionVector,
         latest_frontiers: &Frontiers,
     ) -> Bytes {
-        let new_store = ChangeStore::new_mem(&self.arena, self.merge_interval.clone());
+        self.export_from_with_merge_interval(
+            start_vv,
+            start_frontiers,
+            latest_vv,
+            latest_frontiers,
+            self.merge_interval.clone(),
+        )
+    }
+
+    /// Export the changes like [`ChangeStore::export_from`], but re-merge the
+    /// consecutive changes of the same peer with the given merge interval.
+    ///
+    /// The op ids, lamports and deps of the ops are untouched. Only the boundaries
+    /// of the changes are different.
+    pub(super) fn export_squashed_from(
+        &self,
+        start_vv: &VersionVector,
+        start_frontiers: &Frontiers,
+        latest_vv: &VersionVector,
+        latest_frontiers: &Frontiers,
+        merge_interval: i64,
+    ) -> Bytes {
+        self.export_from_with_merge_interval(
+            start_vv,
+            start_frontiers,
+            latest_vv,
+            latest_frontiers,
+            Arc::new(AtomicI64::new(merge_interval)),
+        )
+    }
+
+    fn export_from_with_merge_interval(
+        &self,
+        start_vv: &VersionVector,
+        start_frontiers: &Frontiers,
+        latest_vv: &VersionVector,
+        latest_frontiers: &Frontiers,
+        merge_interval: Arc<AtomicI64>,
+    ) -> Bytes {
+        let new_store = ChangeStore::new_mem(&self.arena, merge_interval);
         for span in latest_vv.sub_iter(start_vv) {
             // PERF: this can be optimized by reusing the current encoded blocks
             // In the current method, it needs to parse and re-encode the blocks
@@ -677,6 +718,58 @@ mod mut_external_kv {
             store.set(VV_KEY, vv_bytes.into());
             store.set(FRONTIERS_KEY, frontiers_bytes.into());
         }
+
+        /// Redact the content of the ops in the given range.
+        ///
+        /// The changed flushed blocks are re-encoded and overwritten in the external kv store,
+        /// so the original content won't be included in the exported data.
+        ///
+        /// Returns whether any content is changed.
+        pub(crate) fn redact(&self, range: &VersionRange) -> Result<bool, RedactError> {
+            self.ensure_block_loaded_in_range(Bound::Unbounded, Bound::Unbounded);
+            let mut inner = self.inner.try_lock().unwrap();
+            let mut store = self.external_kv.try_lock().unwrap();
+            let mut any_changed = false;
+            let mut result = Ok(());
+            for (id, block) in inner.mem_parsed_kv.iter_mut() {
+                let Some(&(start, end)) = range.get(&block.peer) else {
+                    continue;
+                };
+                if end <= block.counter_range.0 || start >= block.counter_range.1 {
+                    continue;
+                }
+
+                let flushed = block.flushed;
+                let this = Arc::make_mut(block);
+                let changes = this
+                    .content
+                    .changes_mut(&self.arena)
+                    .expect("Parse block error");
+                let mut changed = false;
+                for change in Arc::make_mut(changes).iter_mut() {
+                    if change.id.counter >= end || change.ctr_end() <= start {
+                        continue;
+                    }
+
+                    match redact::redact_change(change, start..end, &self.arena) {
+                        Ok(c) => changed |= c,
+                        Err(e) => {
+                            changed = true;
+                            result = Err(e);
+                        }
+                    }
+                }
+
+                if flushed && changed {
+                    let bytes = block.to_bytes(&self.arena);
+                    store.set(&id.to_bytes(), bytes.bytes);
+                }
+
+                any_changed |= changed;
+            }
+
+            result.map(|_| any_changed)
+        }
     }
 }
 
@@ -908,6 +1001,7 @@ mod mut_inner_kv {
                 lamport: change.lamport,
                 timestamp: change.timestamp,
                 commit_msg: change.commit_msg.clone(),
+                commit_metadata: change.commit_metadata.clone(),
             };
 
             let mut total_len = 0;
@@ -981,6 +1075,7 @@ mod mut_inner_kv {
                 lamport: next_lamport,
                 timestamp: new_change.timestamp,
                 commit_msg: new_change.commit_msg.clone(),
+                commit_metadata: new_change.commit_metadata.clone(),
             };
 
             self.insert_change(new_change, false);
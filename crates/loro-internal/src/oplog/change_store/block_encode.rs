@@ -29,6 +29,9 @@
 //! ┌────────────────────────────────┬─────────────────────────────┐
 //! │    N Rle Commit Msg Lengths    │       Commit Messages       │
 //! └────────────────────────────────┴─────────────────────────────┘
+//! ┌────────────────────────────────┬─────────────────────────────┐
+//! │ N Rle Metadata Lengths (Opt.)  │   Postcard Metadata Maps    │
+//! └────────────────────────────────┴─────────────────────────────┘
 //!
 //!  ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ Encoded Operations ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ ─
 //!
@@ -80,7 +83,7 @@ use serde::{Deserialize, Serialize};
 use serde_columnar::{columnar, AnyRleDecoder, DeltaOfDeltaDecoder, Itertools};
 use tracing::info;
 
-use super::block_meta_encode::decode_changes_header;
+use super::block_meta_encode::{decode_changes_header, decode_commit_metadata};
 use crate::arena::SharedArena;
 use crate::change::{Change, Timestamp};
 use crate::container::tree::tree_op;
@@ -558,8 +561,14 @@ pub fn decode_block(
     let timestamp_decoder = DeltaOfDeltaDecoder::<i64>::new(&change_meta).unwrap();
     let (timestamps, bytes) = timestamp_decoder.take_n_finalize(n_changes).unwrap();
     let commit_msg_len_decoder = AnyRleDecoder::<u32>::new(bytes);
-    let (commit_msg_lens, commit_msgs) = commit_msg_len_decoder.take_n_finalize(n_changes).unwrap();
+    let (commit_msg_lens, bytes) = commit_msg_len_decoder.take_n_finalize(n_changes).unwrap();
+    let commit_msgs_len = commit_msg_lens.iter().map(|x| *x as usize).sum::<usize>();
+    if commit_msgs_len > bytes.len() {
+        return Err(LoroError::DecodeDataCorruptionError);
+    }
+    let (commit_msgs, metadata_bytes) = bytes.split_at(commit_msgs_len);
     let mut commit_msg_index = 0;
+    let metadata = decode_commit_metadata(metadata_bytes, n_changes)?;
     let keys = header.keys.get_or_init(|| decode_keys(&keys));
     let decode_arena = ValueDecodeArena {
         peers: &header.peers,
@@ -615,6 +624,7 @@ pub fn decode_block(
             lamport: header.lamports[i],
             timestamp: timestamps[i] as Timestamp,
             commit_msg,
+            commit_metadata: metadata.as_ref().and_then(|m| m[i].clone()),
         })
     }
 
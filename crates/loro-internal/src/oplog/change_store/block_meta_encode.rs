@@ -1,4 +1,4 @@
-use loro_common::{Counter, Lamport, PeerID, ID};
+use loro_common::{Counter, Lamport, LoroError, LoroMapValue, LoroResult, LoroValue, PeerID, ID};
 use once_cell::sync::OnceCell;
 use rle::HasLength;
 use serde_columnar::{
@@ -19,6 +19,9 @@ pub(crate) fn encode_changes(
     let mut lamport_encoder = DeltaOfDeltaEncoder::new();
     let mut commit_msg_len_encoder = AnyRleEncoder::<u32>::new();
     let mut commit_msgs = String::new();
+    let mut metadata_len_encoder = AnyRleEncoder::<u32>::new();
+    let mut metadata_bytes = Vec::new();
+    let has_metadata = block.iter().any(|c| c.commit_metadata.is_some());
     let mut dep_self_encoder = BoolRleEncoder::new();
     let mut dep_len_encoder = AnyRleEncoder::<usize>::new();
     let mut encoded_deps = EncodedDeps {
@@ -44,6 +47,16 @@ pub(crate) fn encode_changes(
             commit_msg_len_encoder.append(0).unwrap();
         }
 
+        if has_metadata {
+            if let Some(metadata) = c.commit_metadata.as_ref() {
+                let bytes = postcard::to_allocvec(&LoroValue::Map(metadata.clone())).unwrap();
+                metadata_len_encoder.append(bytes.len() as u32).unwrap();
+                metadata_bytes.extend_from_slice(&bytes);
+            } else {
+                metadata_len_encoder.append(0).unwrap();
+            }
+        }
+
         let mut dep_on_self = false;
         for dep in c.deps().iter() {
             if dep.peer == peer {
@@ -83,6 +96,12 @@ pub(crate) fn encode_changes(
     meta.append(&mut t);
     meta.append(&mut cml);
     meta.append(&mut cms);
+    // The metadata section is omitted when no change has metadata.
+    // Older versions ignore the bytes after the commit messages.
+    if has_metadata {
+        meta.append(&mut metadata_len_encoder.finish().unwrap());
+        meta.append(&mut metadata_bytes);
+    }
 
     (ans, meta)
 }
@@ -182,3 +201,42 @@ struct EncodedDeps {
     peer_idx: AnyRleEncoder<u32>,
     counter: DeltaOfDeltaEncoder,
 }
+
+/// Decode the optional metadata section that follows the commit messages.
+///
+/// Return `None` if the block doesn't contain any metadata.
+pub(crate) fn decode_commit_metadata(
+    bytes: &[u8],
+    n_changes: usize,
+) -> LoroResult<Option<Vec<Option<LoroMapValue>>>> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    let len_decoder = AnyRleDecoder::<u32>::new(bytes);
+    let (lens, mut bytes) = len_decoder
+        .take_n_finalize(n_changes)
+        .map_err(|_| LoroError::DecodeDataCorruptionError)?;
+    let mut ans = Vec::with_capacity(n_changes);
+    for len in lens {
+        let len = len as usize;
+        if len == 0 {
+            ans.push(None);
+            continue;
+        }
+
+        if len > bytes.len() {
+            return Err(LoroError::DecodeDataCorruptionError);
+        }
+
+        let value: LoroValue = postcard::from_bytes(&bytes[..len])
+            .map_err(|_| LoroError::DecodeDataCorruptionError)?;
+        let LoroValue::Map(map) = value else {
+            return Err(LoroError::DecodeDataCorruptionError);
+        };
+        ans.push(Some(map));
+        bytes = &bytes[len..];
+    }
+
+    Ok(Some(ans))
+}
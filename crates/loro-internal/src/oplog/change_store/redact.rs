@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use loro_common::{Counter, LoroValue};
+use rle::{HasLength, RleVec};
+use smallvec::SmallVec;
+
+use crate::{
+    arena::SharedArena,
+    change::Change,
+    container::list::list_op::InnerListOp,
+    encoding::json_schema::{json::RedactError, redact_value},
+    op::{FutureInnerContent, InnerContent, Op, SliceRange},
+};
+
+/// Redact the content of the ops of the change within the counter range.
+///
+/// It follows the same rules as [`crate::encoding::json_schema::json::redact`],
+/// so the ids, lengths and lamports of the ops are kept.
+///
+/// Returns whether any content is changed, so redacting the same range again is a no-op.
+pub(super) fn redact_change(
+    change: &mut Change,
+    range: Range<Counter>,
+    arena: &SharedArena,
+) -> Result<bool, RedactError> {
+    let mut ops: SmallVec<[Op; 1]> = change.ops.iter().cloned().collect();
+    let mut changed = false;
+    let mut result = Ok(());
+    for op in ops.iter_mut() {
+        let len = op.atom_len() as Counter;
+        let start = (range.start - op.counter).max(0).min(len);
+        let end = (range.end - op.counter).max(0).min(len);
+        if start == end {
+            continue;
+        }
+
+        match redact_op(op, start as usize..end as usize, arena) {
+            Ok(c) => changed |= c,
+            Err(e) => result = Err(e),
+        }
+    }
+
+    if changed {
+        change.ops = RleVec::from(ops);
+    }
+
+    result.map(|_| changed)
+}
+
+/// Whether the value is not redacted yet
+fn is_redactable(v: &LoroValue) -> bool {
+    !matches!(v, LoroValue::Null | LoroValue::Container(_))
+}
+
+fn redact_op(op: &mut Op, range: Range<usize>, arena: &SharedArena) -> Result<bool, RedactError> {
+    match &mut op.content {
+        InnerContent::List(list_op) => match list_op {
+            InnerListOp::Insert { slice, .. } => {
+                if slice.is_unknown() {
+                    return Ok(false);
+                }
+
+                let mut values = arena.get_values(slice.to_range());
+                if !values[range.clone()].iter().any(is_redactable) {
+                    return Ok(false);
+                }
+
+                for v in values[range].iter_mut() {
+                    redact_value(v);
+                }
+                let new_range = arena.alloc_values(values.into_iter());
+                *slice = SliceRange::new(new_range.start as u32..new_range.end as u32);
+            }
+            InnerListOp::InsertText {
+                slice,
+                unicode_start,
+                unicode_len,
+                ..
+            } => {
+                let text = std::str::from_utf8(slice.as_bytes()).unwrap();
+                if text
+                    .chars()
+                    .skip(range.start)
+                    .take(range.len())
+                    .all(|c| c == '\u{FFFD}')
+                {
+                    return Ok(false);
+                }
+
+                let text: String = text
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| if range.contains(&i) { '\u{FFFD}' } else { c })
+                    .collect();
+                let (new_slice, result) = arena.alloc_str_with_slice(&text);
+                *slice = new_slice;
+                *unicode_start = result.start as u32;
+                *unicode_len = (result.end - result.start) as u32;
+            }
+            InnerListOp::Set { value, .. } => {
+                if !is_redactable(value) {
+                    return Ok(false);
+                }
+
+                redact_value(value);
+            }
+            InnerListOp::StyleStart { value, .. } => {
+                if value.is_null() {
+                    return Ok(false);
+                }
+
+                *value = LoroValue::Null;
+            }
+            InnerListOp::Delete(_) | InnerListOp::Move { .. } | InnerListOp::StyleEnd => {
+                // Delete, move and style end ops won't be changed
+                return Ok(false);
+            }
+        },
+        InnerContent::Map(map_set) => match map_set.value.as_mut() {
+            Some(value) if is_redactable(value) => redact_value(value),
+            _ => return Ok(false),
+        },
+        InnerContent::Tree(_) => {
+            // Creation of child container won't be changed
+            return Ok(false);
+        }
+        InnerContent::Future(future) => match future {
+            #[cfg(feature = "counter")]
+            FutureInnerContent::Counter(x) => {
+                if *x == 0. {
+                    return Ok(false);
+                }
+
+                *x = 0.;
+            }
+            FutureInnerContent::Unknown { .. } => {
+                return Err(RedactError::UnknownOperationType);
+            }
+        },
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,104 @@
+use fxhash::FxHashMap;
+use loro_common::{Counter, IdSpan};
+use rle::HasLength;
+
+use crate::{container::idx::ContainerIdx, OpLog, VersionVector};
+
+/// An index from containers to the op spans that modified them or their descendants.
+///
+/// It's the reverse of [`crate::LoroDoc::get_changed_containers_in`]. The index is built lazily
+/// on the first query and catches up with the [OpLog] on every following query, so it doesn't
+/// need to be notified by every code path that inserts changes.
+///
+/// An op span is indexed under its container and every ancestor of it. The parent of a
+/// container never changes after it's created, so a query only reads the spans of the target.
+#[derive(Debug, Default)]
+pub(crate) struct ContainerChangesIndex {
+    /// The version that has been indexed
+    vv: VersionVector,
+    spans: FxHashMap<ContainerIdx, Vec<IdSpan>>,
+    /// The cached ancestors of the indexed containers, including themselves
+    ancestors: FxHashMap<ContainerIdx, Vec<ContainerIdx>>,
+}
+
+impl ContainerChangesIndex {
+    pub(crate) fn new(start_vv: VersionVector) -> Self {
+        Self {
+            vv: start_vv,
+            spans: Default::default(),
+            ancestors: Default::default(),
+        }
+    }
+
+    /// Index all the ops that are in the oplog but not in the index yet
+    pub(crate) fn catch_up(&mut self, oplog: &OpLog) {
+        if &self.vv == oplog.vv() {
+            return;
+        }
+
+        for change in oplog.iter_changes_peer_by_peer(&self.vv, oplog.vv()) {
+            let start = self.vv.get(&change.id.peer).copied().unwrap_or(0);
+            for op in change.ops.iter() {
+                let end = op.counter + op.atom_len() as Counter;
+                if end <= start {
+                    continue;
+                }
+
+                let span = IdSpan::new(change.id.peer, op.counter.max(start), end);
+                let ancestors = self.ancestors.entry(op.container).or_insert_with(|| {
+                    let mut ans = Vec::new();
+                    oplog.arena.with_ancestors(op.container, |c, _| ans.push(c));
+                    ans
+                });
+                for &c in ancestors.iter() {
+                    push_span(self.spans.entry(c).or_default(), span);
+                }
+            }
+        }
+
+        self.vv = oplog.vv().clone();
+    }
+
+    /// Get the op spans that modified `target` or any of its descendants.
+    ///
+    /// The spans that are included by `since` are excluded.
+    pub(crate) fn spans_touching(
+        &self,
+        target: ContainerIdx,
+        since: Option<&VersionVector>,
+    ) -> Vec<IdSpan> {
+        let mut ans = Vec::new();
+        for span in self.spans.get(&target).into_iter().flatten() {
+            let start = since
+                .and_then(|vv| vv.get(&span.peer).copied())
+                .unwrap_or(0)
+                .max(span.counter.start);
+            if start < span.counter.end {
+                ans.push(IdSpan::new(span.peer, start, span.counter.end));
+            }
+        }
+
+        ans.sort_unstable_by_key(|span| (span.peer, span.counter.start));
+        let mut merged: Vec<IdSpan> = Vec::with_capacity(ans.len());
+        for span in ans {
+            push_span(&mut merged, span);
+        }
+
+        merged
+    }
+}
+
+fn push_span(spans: &mut Vec<IdSpan>, span: IdSpan) {
+    if let Some(last) = spans.last_mut() {
+        if last.peer == span.peer
+            && last.counter.end >= span.counter.start
+            && last.counter.start <= span.counter.start
+        {
+            last.counter.end = last.counter.end.max(span.counter.end);
+            return;
+        }
+    }
+
+    debug_assert!(span.atom_len() > 0);
+    spans.push(span);
+}
@@ -13,7 +13,7 @@ use enum_as_inner::EnumAsInner;
 use enum_dispatch::enum_dispatch;
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
-use loro_common::{ContainerID, LoroError, LoroResult};
+use loro_common::{ContainerID, LoroError, LoroResult, ID};
 use loro_delta::DeltaItem;
 use tracing::{info_span, instrument, warn};
 
@@ -1492,35 +1492,49 @@ impl DocState {
     }
 
     pub fn get_relative_position(&mut self, pos: &Cursor, use_event_index: bool) -> Option<usize> {
-        let idx = self.arena.register_container(&pos.container);
-        let state = self.store.get_container_mut(idx)?;
-        if let Some(id) = pos.id {
-            match state {
-                State::ListState(s) => s.get_index_of_id(id),
-                State::RichtextState(s) => s.get_text_index_of_id(id, use_event_index),
-                State::MovableListState(s) => s.get_index_of_id(id),
-                State::MapState(_) | State::TreeState(_) | State::UnknownState(_) => unreachable!(),
-                #[cfg(feature = "counter")]
-                State::CounterState(_) => unreachable!(),
-            }
-        } else {
-            if matches!(pos.side, crate::cursor::Side::Left) {
-                return Some(0);
-            }
+        self.get_relative_positions(&pos.container, &[pos], use_event_index)
+            .pop()
+            .unwrap()
+    }
 
-            match state {
-                State::ListState(s) => Some(s.len()),
-                State::RichtextState(s) => Some(if use_event_index {
+    /// Get the positions of the cursors of `container` in a single pass over its state.
+    pub(crate) fn get_relative_positions(
+        &mut self,
+        container: &ContainerID,
+        cursors: &[&Cursor],
+        use_event_index: bool,
+    ) -> Vec<Option<usize>> {
+        let idx = self.arena.register_container(container);
+        let Some(state) = self.store.get_container_mut(idx) else {
+            return vec![None; cursors.len()];
+        };
+
+        let ids: Vec<ID> = cursors.iter().filter_map(|c| c.id).collect();
+        let (positions, len) = match state {
+            State::ListState(s) => (s.get_indexes_of_ids(&ids), s.len()),
+            State::RichtextState(s) => (
+                s.get_text_indexes_of_ids(&ids, use_event_index),
+                if use_event_index {
                     s.len_event()
                 } else {
                     s.len_unicode()
-                }),
-                State::MovableListState(s) => Some(s.len()),
-                State::MapState(_) | State::TreeState(_) | State::UnknownState(_) => unreachable!(),
-                #[cfg(feature = "counter")]
-                State::CounterState(_) => unreachable!(),
-            }
-        }
+                },
+            ),
+            State::MovableListState(s) => (s.get_indexes_of_ids(&ids), s.len()),
+            State::MapState(_) | State::TreeState(_) | State::UnknownState(_) => unreachable!(),
+            #[cfg(feature = "counter")]
+            State::CounterState(_) => unreachable!(),
+        };
+
+        let mut positions = positions.into_iter();
+        cursors
+            .iter()
+            .map(|c| match c.id {
+                Some(_) => positions.next().unwrap(),
+                None if matches!(c.side, crate::cursor::Side::Left) => Some(0),
+                None => Some(len),
+            })
+            .collect()
     }
 
     pub fn get_value_by_path(&mut self, path: &[Index]) -> Option<LoroValue> {
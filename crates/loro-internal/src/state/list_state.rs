@@ -9,6 +9,7 @@ use crate::{
     arena::SharedArena,
     configure::Configure,
     container::{idx::ContainerIdx, list::list_op::ListOp, ContainerID},
+    cursor::IdLookup,
     encoding::{EncodeMode, StateSnapshotDecodeContext, StateSnapshotEncoder},
     event::{Diff, Index, InternalDiff, ListDiff},
     handler::ValueOrHandler,
@@ -348,13 +349,24 @@ impl ListState {
         }
     }
 
-    pub fn get_index_of_id(&self, id: ID) -> Option<usize> {
+    /// Get the indexes of the ids in a single pass over the list.
+    pub(crate) fn get_indexes_of_ids(&self, ids: &[ID]) -> Vec<Option<usize>> {
+        let lookup = IdLookup::new(ids);
+        let mut ans = vec![None; ids.len()];
+        let mut found = 0;
         for (i, elem) in self.iter_with_id().enumerate() {
-            if elem.id.id() == id {
-                return Some(i);
+            if found == ids.len() {
+                break;
             }
+
+            let id = elem.id.id();
+            lookup.find_in(id.peer, id.counter..id.counter + 1, |slot, _| {
+                ans[slot] = Some(i);
+                found += 1;
+            });
         }
-        None
+
+        ans
     }
 }
 
@@ -12,6 +12,7 @@ use crate::{
     arena::SharedArena,
     configure::Configure,
     container::{idx::ContainerIdx, list::list_op::ListOp},
+    cursor::IdLookup,
     delta::DeltaItem,
     diff_calc::DiffMode,
     encoding::{StateSnapshotDecodeContext, StateSnapshotEncoder},
@@ -942,16 +943,29 @@ impl MovableListState {
         self.iter().cloned().collect_vec()
     }
 
-    pub(crate) fn get_index_of_id(&self, id: ID) -> Option<usize> {
+    /// Get the user indexes of the ids in a single pass over the list.
+    pub(crate) fn get_indexes_of_ids(&self, ids: &[ID]) -> Vec<Option<usize>> {
+        let lookup = IdLookup::new(ids);
+        let mut ans = vec![None; ids.len()];
+        let mut found = 0;
         let mut user_index = 0;
         for item in self.list().iter() {
-            if item.id.peer == id.peer && item.id.counter == id.counter {
-                return Some(user_index);
+            if found == ids.len() {
+                break;
             }
 
+            lookup.find_in(
+                item.id.peer,
+                item.id.counter..item.id.counter + 1,
+                |slot, _| {
+                    ans[slot] = Some(user_index);
+                    found += 1;
+                },
+            );
             user_index += if item.pointed_by.is_some() { 1 } else { 0 };
         }
-        None
+
+        ans
     }
 
     fn get_value_inner(&self) -> Vec<LoroValue> {
@@ -12,7 +12,7 @@ use crate::{
     arena::SharedArena,
     container::{
         idx::ContainerIdx,
-        list::list_op,
+        list::list_op::{self, ListOp},
         richtext::{
             config::StyleConfigMap,
             richtext_state::{
@@ -21,11 +21,12 @@ use crate::{
             AnchorType, RichtextState as InnerState, StyleOp, Styles,
         },
     },
+    cursor::IdLookup,
     delta::{StyleMeta, StyleMetaItem},
     encoding::{EncodeMode, StateSnapshotDecodeContext, StateSnapshotEncoder},
     event::{Diff, Index, InternalDiff, TextDiff},
     handler::TextDelta,
-    op::{Op, RawOp},
+    op::{ListSlice, Op, RawOp, RawOpContent},
     txn::Transaction,
     utils::{lazy::LazyLoad, string_slice::StringSlice},
     DocState,
@@ -116,7 +117,7 @@ impl RichtextState {
                         return;
                     }
                 }
-                RichtextStateChunk::Style { .. } => {}
+                RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => {}
             }
         }
     }
@@ -168,6 +169,10 @@ impl RichtextState {
                             pos.entity_index += t.unicode_len() as usize;
                             pos.event_index += t.event_len() as usize;
                         }
+                        RichtextStateChunk::Embed { .. } => {
+                            pos.entity_index += 1;
+                            pos.event_index += 1;
+                        }
                         RichtextStateChunk::Style { .. } => {
                             pos.entity_index += 1;
                         }
@@ -206,7 +211,12 @@ impl RichtextState {
         None
     }
 
-    pub fn get_text_index_of_id(&self, id: ID, use_event_index: bool) -> Option<usize> {
+    /// Get the indexes of the ids in a single pass over the text.
+    pub(crate) fn get_text_indexes_of_ids(
+        &self,
+        ids: &[ID],
+        use_event_index: bool,
+    ) -> Vec<Option<usize>> {
         let iter: &mut dyn Iterator<Item = &RichtextStateChunk>;
         let mut a;
         let mut b;
@@ -221,26 +231,31 @@ impl RichtextState {
             }
         }
 
+        let lookup = IdLookup::new(ids);
+        let mut ans = vec![None; ids.len()];
+        let mut found = 0;
         let mut index = 0;
         for elem in iter {
+            if found == ids.len() {
+                break;
+            }
+
             let span = elem.get_id_span();
-            if span.contains(id) {
-                match elem {
-                    RichtextStateChunk::Text(t) => {
-                        if use_event_index {
-                            let event_offset = t.convert_unicode_offset_to_event_offset(
-                                (id.counter - span.counter.start) as usize,
-                            );
-                            return Some(index + event_offset);
-                        } else {
-                            return Some(index + (id.counter - span.counter.start) as usize);
+            lookup.find_in(
+                span.peer,
+                span.counter.start..span.counter.end,
+                |slot, offset| {
+                    let offset = match elem {
+                        RichtextStateChunk::Text(t) if use_event_index => {
+                            t.convert_unicode_offset_to_event_offset(offset)
                         }
-                    }
-                    RichtextStateChunk::Style { .. } => {
-                        return Some(index);
-                    }
-                }
-            }
+                        RichtextStateChunk::Text(_) => offset,
+                        RichtextStateChunk::Style { .. } | RichtextStateChunk::Embed { .. } => 0,
+                    };
+                    ans[slot] = Some(index + offset);
+                    found += 1;
+                },
+            );
 
             index += match elem {
                 RichtextStateChunk::Text(t) => {
@@ -251,23 +266,59 @@ impl RichtextState {
                     }
                 }
                 RichtextStateChunk::Style { .. } => 0,
+                RichtextStateChunk::Embed { .. } => 1,
             };
         }
 
-        None
+        ans
     }
 
     pub(crate) fn get_delta(&mut self) -> Vec<TextDelta> {
         let mut delta = Vec::new();
         // TODO: merge last
         for span in self.state.get_mut().iter() {
-            delta.push(TextDelta::Insert {
-                insert: span.text.as_str().to_string(),
-                attributes: span.attributes.to_option_map(),
-            })
+            match span.text.as_embed() {
+                Some(embed) => delta.push(TextDelta::InsertEmbed {
+                    insert: embed.clone(),
+                    attributes: span.attributes.to_option_map(),
+                }),
+                None => delta.push(TextDelta::Insert {
+                    insert: span.text.as_str().to_string(),
+                    attributes: span.attributes.to_option_map(),
+                }),
+            }
         }
         delta
     }
+
+    fn iter_chunks_with_event_index(&self, f: &mut dyn FnMut(usize, &RichtextStateChunk) -> bool) {
+        let iter: &mut dyn Iterator<Item = &RichtextStateChunk>;
+        let mut a;
+        let mut b;
+        match &self.state {
+            LazyLoad::Src(s) => {
+                a = Some(s.elements.iter());
+                iter = &mut *a.as_mut().unwrap();
+            }
+            LazyLoad::Dst(s) => {
+                b = Some(s.iter_chunk());
+                iter = &mut *b.as_mut().unwrap();
+            }
+        }
+
+        let mut event_index = 0;
+        for elem in iter {
+            if !f(event_index, elem) {
+                return;
+            }
+
+            event_index += match elem {
+                RichtextStateChunk::Text(t) => t.event_len() as usize,
+                RichtextStateChunk::Style { .. } => 0,
+                RichtextStateChunk::Embed { .. } => 1,
+            };
+        }
+    }
 }
 
 impl Clone for RichtextState {
@@ -371,7 +422,8 @@ impl ContainerState for RichtextState {
                             {
                                 entity_len_sum += entity_len;
                                 match chunk {
-                                    RichtextStateChunk::Text(_) => {
+                                    RichtextStateChunk::Text(_)
+                                    | RichtextStateChunk::Embed { .. } => {
                                         let mut style_meta: StyleMeta = styles.into();
                                         for key in deleted_style_keys.iter() {
                                             if !style_meta.contains_key(key) {
@@ -419,6 +471,23 @@ impl ContainerState for RichtextState {
                                     insert_styles,
                                 );
                             }
+                            RichtextStateChunk::Embed { value, id } => {
+                                let (pos, styles) =
+                                    self.state.get_mut().insert_elem_at_entity_index(
+                                        entity_index,
+                                        RichtextStateChunk::new_embed(value.clone(), *id),
+                                    );
+                                let insert_styles = styles.clone().into();
+
+                                if pos > event_index {
+                                    ans.push_retain(pos - event_index, Default::default());
+                                }
+                                event_index = pos + 1;
+                                ans.push_insert(
+                                    StringSlice::new_embed(value.clone()),
+                                    insert_styles,
+                                );
+                            }
                             RichtextStateChunk::Style { anchor_type, style } => {
                                 let (new_event_index, _) =
                                     self.state.get_mut().insert_elem_at_entity_index(
@@ -511,11 +580,10 @@ impl ContainerState for RichtextState {
                     if value.rle_len() > 0 {
                         // Insertions
                         match value {
-                            RichtextStateChunk::Text(s) => {
-                                self.state.get_mut().insert_elem_at_entity_index(
-                                    entity_index,
-                                    RichtextStateChunk::Text(s.clone()),
-                                );
+                            RichtextStateChunk::Text(_) | RichtextStateChunk::Embed { .. } => {
+                                self.state
+                                    .get_mut()
+                                    .insert_elem_at_entity_index(entity_index, value.clone());
                             }
                             RichtextStateChunk::Style { style, anchor_type } => {
                                 self.state.get_mut().insert_elem_at_entity_index(
@@ -546,7 +614,8 @@ impl ContainerState for RichtextState {
                                                         start_entity_index +=
                                                             t.unicode_len() as usize;
                                                     }
-                                                    RichtextStateChunk::Style { .. } => {
+                                                    RichtextStateChunk::Style { .. }
+                                                    | RichtextStateChunk::Embed { .. } => {
                                                         start_entity_index += 1;
                                                     }
                                                 }
@@ -575,8 +644,24 @@ impl ContainerState for RichtextState {
         self.update_version();
         match &op.content {
             crate::op::InnerContent::List(l) => match l {
-                list_op::InnerListOp::Insert { slice: _, pos: _ } => {
-                    unreachable!()
+                list_op::InnerListOp::Insert { slice: _, pos } => {
+                    let RawOpContent::List(ListOp::Insert {
+                        slice: ListSlice::RawData(values),
+                        ..
+                    }) = &r_op.content
+                    else {
+                        unreachable!()
+                    };
+
+                    for (i, value) in values.iter().enumerate() {
+                        self.state.get_mut().insert_elem_at_entity_index(
+                            *pos + i,
+                            RichtextStateChunk::new_embed(
+                                value.clone(),
+                                r_op.id_full().inc(i as i32),
+                            ),
+                        );
+                    }
                 }
                 list_op::InnerListOp::InsertText {
                     slice,
@@ -656,18 +741,31 @@ impl ContainerState for RichtextState {
     }
 
     #[doc = r" Get the index of the child container"]
-    #[allow(unused)]
     fn get_child_index(&self, id: &ContainerID) -> Option<Index> {
-        None
+        let mut ans = None;
+        self.iter_chunks_with_event_index(&mut |index, elem| {
+            if matches!(elem.as_embed(), Some(LoroValue::Container(c)) if c == id) {
+                ans = Some(Index::Seq(index));
+                return false;
+            }
+
+            true
+        });
+        ans
     }
 
-    #[allow(unused)]
     fn get_child_containers(&self) -> Vec<ContainerID> {
-        Vec::new()
+        let mut ans = Vec::new();
+        self.iter_raw(&mut |elem| {
+            if let Some(LoroValue::Container(c)) = elem.as_embed() {
+                ans.push(c.clone());
+            }
+        });
+        ans
     }
 
-    fn contains_child(&self, _id: &ContainerID) -> bool {
-        false
+    fn contains_child(&self, id: &ContainerID) -> bool {
+        self.get_child_index(id).is_some()
     }
 
     #[doc = " Get a list of ops that can be used to restore the state to the current state"]
@@ -706,6 +804,12 @@ impl ContainerState for RichtextState {
                 list_op::InnerListOp::InsertText { slice, .. } => {
                     RichtextStateChunk::new_text(slice.clone(), id)
                 }
+                list_op::InnerListOp::Insert { slice, .. } => {
+                    // An embed op is encoded one value at a time because each embed is a chunk
+                    let values = ctx.oplog.arena.get_values(slice.to_range());
+                    debug_assert_eq!(values.len(), 1);
+                    RichtextStateChunk::new_embed(values.into_iter().next().unwrap(), id)
+                }
                 list_op::InnerListOp::StyleStart {
                     key, value, info, ..
                 } => {
@@ -838,6 +942,44 @@ impl RichtextState {
             .get_text_entity_ranges(pos, len, PosType::Event)
     }
 
+    #[inline]
+    pub(crate) fn get_styles_at(&mut self, pos: usize, pos_type: PosType) -> Option<StyleMeta> {
+        self.state.get_mut().get_styles_at(pos, pos_type)
+    }
+
+    #[inline]
+    pub(crate) fn next_newline(&mut self, pos: usize) -> Option<usize> {
+        self.state.get_mut().next_newline(pos)
+    }
+
+    #[inline]
+    pub(crate) fn get_style_ranges(&mut self, pos_type: PosType) -> Vec<(Range<usize>, StyleMeta)> {
+        self.state.get_mut().get_style_ranges(pos_type)
+    }
+
+    #[inline]
+    pub(crate) fn len(&mut self, pos_type: PosType) -> usize {
+        self.state.get_mut().len(pos_type)
+    }
+
+    #[cfg(feature = "line-index")]
+    #[inline]
+    pub(crate) fn line_count(&mut self) -> usize {
+        self.state.get_mut().line_count()
+    }
+
+    #[cfg(feature = "line-index")]
+    #[inline]
+    pub(crate) fn line_start(&mut self, line: usize, pos_type: PosType) -> Option<usize> {
+        self.state.get_mut().line_start(line, pos_type)
+    }
+
+    #[cfg(feature = "line-index")]
+    #[inline]
+    pub(crate) fn line_of(&mut self, pos: usize, pos_type: PosType) -> Option<usize> {
+        self.state.get_mut().line_of(pos, pos_type)
+    }
+
     #[inline]
     pub fn get_richtext_value(&mut self) -> LoroValue {
         self.state.get_mut().get_richtext_value()
@@ -869,6 +1011,12 @@ impl RichtextState {
         self.state.get_mut().index_to_event_index(index, pos_type)
     }
 
+    pub(crate) fn event_index_to_index(&mut self, event_index: usize, pos_type: PosType) -> usize {
+        self.state
+            .get_mut()
+            .event_index_to_index(event_index, pos_type)
+    }
+
     pub(crate) fn event_index_to_unicode_index(&mut self, event_index: usize) -> usize {
         self.state
             .get_mut()
@@ -960,6 +1108,7 @@ mod snapshot {
         /// positive for text
         /// 0 for mark start
         /// -1 for mark end
+        /// -2 for embed
         #[columnar(strategy = "DeltaRle")]
         len: i32,
     }
@@ -978,6 +1127,8 @@ mod snapshot {
         spans: Vec<EncodedTextSpan>,
         keys: Vec<InternalString>,
         marks: Vec<EncodedMark>,
+        #[columnar(optional, index = 0)]
+        embeds: Vec<LoroValue>,
     }
 
     impl FastStateSnapshot for RichtextState {
@@ -989,17 +1140,19 @@ mod snapshot {
         ///    - peer_idx: Index of the peer ID in a value register (delta-RLE encoded)
         ///    - counter: Operation counter (delta-RLE encoded)
         ///    - lamport_sub_counter: Lamport timestamp - counter (delta-RLE encoded)
-        ///    - len: Length of text chunk or marker type (-1 for end, 0 for start, positive for text)
+        ///    - len: Length of text chunk or marker type (-2 for embed, -1 for end, 0 for start, positive for text)
         /// 3. A list of unique style keys as InternalString.
         /// 4. A series of EncodedMark structs for style information:
         ///    - key_idx: Index of the style key in the keys list
         ///    - value: The style value
         ///    - info: Additional style information as a byte
+        /// 5. The values of the embeds in order
         fn encode_snapshot_fast<W: std::io::prelude::Write>(&mut self, mut w: W) {
             let value = self.get_value().into_string().unwrap();
             postcard::to_io(&*value, &mut w).unwrap();
             let mut spans = Vec::new();
             let mut marks = Vec::new();
+            let mut embeds = Vec::new();
 
             let mut peers: ValueRegister<PeerID> = ValueRegister::new();
             let iter: &mut dyn Iterator<Item = &RichtextStateChunk>;
@@ -1055,6 +1208,15 @@ mod snapshot {
                             })
                         }
                     },
+                    RichtextStateChunk::Embed { value, id } => {
+                        spans.push(EncodedTextSpan {
+                            peer_idx: peers.register(&id.peer),
+                            counter: id.counter,
+                            lamport_sub_counter: id.lamport as i32 - id.counter,
+                            len: -2,
+                        });
+                        embeds.push(value.clone());
+                    }
                 }
             }
 
@@ -1068,6 +1230,7 @@ mod snapshot {
                 spans,
                 keys: keys.unwrap_vec(),
                 marks,
+                embeds,
             })
             .unwrap();
             w.write_all(&bytes).unwrap();
@@ -1107,6 +1270,7 @@ mod snapshot {
             let keys = iters.keys;
             let span_iter = iters.spans.into_iter();
             let mut mark_iter = iters.marks.into_iter();
+            let mut embed_iter = iters.embeds.into_iter();
             let mut id_to_style = FxHashMap::default();
             for span in span_iter {
                 let EncodedTextSpan {
@@ -1144,6 +1308,7 @@ mod snapshot {
                         let style = id_to_style.remove(&id_full.id().inc(-1)).unwrap();
                         RichtextStateChunk::new_style(style, richtext::AnchorType::End)
                     }
+                    -2 => RichtextStateChunk::new_embed(embed_iter.next().unwrap(), id_full),
                     len => {
                         // Text
                         let (new, rest) = s.split_at_unicode_pos(len as usize);
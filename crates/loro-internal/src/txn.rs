@@ -7,7 +7,7 @@ use std::{
 
 use enum_as_inner::EnumAsInner;
 use generic_btree::rle::{HasLength as RleHasLength, Mergeable as GBSliceable};
-use loro_common::{ContainerType, IdLp, IdSpan, LoroResult};
+use loro_common::{ContainerType, IdLp, IdSpan, LoroMapValue, LoroResult};
 use loro_delta::{array_vec::ArrayVec, DeltaRopeBuilder};
 use rle::{HasLength, Mergable, RleVec};
 use smallvec::{smallvec, SmallVec};
@@ -27,6 +27,7 @@ use crate::{
     id::{Counter, PeerID, ID},
     op::{Op, RawOp, RawOpContent},
     span::HasIdSpan,
+    utils::string_slice::StringSlice,
     version::Frontiers,
     InternalString, LoroError, LoroValue,
 };
@@ -152,6 +153,7 @@ pub struct Transaction {
     on_commit: Option<OnCommitFn>,
     timestamp: Option<Timestamp>,
     msg: Option<Arc<str>>,
+    metadata: Option<LoroMapValue>,
     latest_timestamp: Timestamp,
 }
 
@@ -370,6 +372,7 @@ impl Transaction {
             finished: false,
             on_commit: None,
             msg: None,
+            metadata: None,
             latest_timestamp,
         }
     }
@@ -386,6 +389,10 @@ impl Transaction {
         self.msg = msg;
     }
 
+    pub fn set_metadata(&mut self, metadata: Option<LoroMapValue>) {
+        self.metadata = metadata;
+    }
+
     pub(crate) fn set_on_commit(&mut self, f: OnCommitFn) {
         self.on_commit = Some(f);
     }
@@ -424,6 +431,7 @@ impl Transaction {
                     .unwrap_or_else(|| oplog.get_timestamp_for_next_txn()),
             ),
             commit_msg: take(&mut self.msg),
+            commit_metadata: take(&mut self.metadata),
         };
 
         let diff = if state.is_recording() {
@@ -740,12 +748,18 @@ fn change_to_diff(
                     .retain(pos as usize, Default::default())
                     .build();
                 for op in ops.iter() {
-                    let InnerListOp::InsertText { slice, .. } = op.content.as_list().unwrap()
-                    else {
-                        unreachable!()
-                    };
-
-                    delta.push_insert(slice.clone().into(), styles.clone());
+                    match op.content.as_list().unwrap() {
+                        InnerListOp::InsertText { slice, .. } => {
+                            delta.push_insert(slice.clone().into(), styles.clone());
+                        }
+                        // Embeds
+                        InnerListOp::Insert { slice, .. } => {
+                            for v in arena.get_values(slice.to_range()) {
+                                delta.push_insert(StringSlice::new_embed(v), styles.clone());
+                            }
+                        }
+                        _ => unreachable!(),
+                    }
                 }
                 ans.push(TxnContainerDiff {
                     idx: op.container,
@@ -180,6 +180,15 @@ impl Query<RichtextTreeTrait> for EntityIndexQueryWithEventIndex {
                 self.event_index += s.event_len() as usize;
                 (left, false)
             }
+            RichtextStateChunk::Embed { .. } => {
+                if left <= 1 {
+                    self.event_index += left;
+                    return (left, true);
+                }
+
+                self.event_index += 1;
+                (left, false)
+            }
             RichtextStateChunk::Style { .. } => {
                 if left == 0 {
                     return (0, true);
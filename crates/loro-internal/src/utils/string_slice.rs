@@ -2,6 +2,7 @@ use std::{fmt::Debug, ops::Deref};
 
 use append_only_bytes::BytesSlice;
 use generic_btree::rle::{HasLength, Mergeable, Sliceable, TryInsert};
+use loro_common::LoroValue;
 use rle::Mergable;
 use serde::{Deserialize, Deserializer, Serialize};
 
@@ -19,7 +20,11 @@ pub struct StringSlice {
 
 impl PartialEq for StringSlice {
     fn eq(&self, other: &Self) -> bool {
-        self.as_str() == other.as_str()
+        match (&self.bytes, &other.bytes) {
+            (Variant::Embed(a), Variant::Embed(b)) => a == b,
+            (Variant::Embed(_), _) | (_, Variant::Embed(_)) => false,
+            _ => self.as_str() == other.as_str(),
+        }
     }
 }
 
@@ -29,6 +34,8 @@ impl Eq for StringSlice {}
 enum Variant {
     BytesSlice(BytesSlice),
     Owned(String),
+    /// An inline embed in the text. It has a length of 1 in every unit.
+    Embed(LoroValue),
 }
 
 impl From<String> for StringSlice {
@@ -55,6 +62,10 @@ impl From<&str> for StringSlice {
 
 impl Debug for StringSlice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Variant::Embed(v) = &self.bytes {
+            return f.debug_struct("StringSlice").field("embed", v).finish();
+        }
+
         f.debug_struct("StringSlice")
             .field("bytes", &self.as_str())
             .finish()
@@ -69,11 +80,31 @@ impl StringSlice {
         }
     }
 
+    pub fn new_embed(value: LoroValue) -> Self {
+        Self {
+            bytes: Variant::Embed(value),
+        }
+    }
+
+    /// Get the value of the embed, or `None` if this is a string.
+    pub fn as_embed(&self) -> Option<&LoroValue> {
+        match &self.bytes {
+            Variant::Embed(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_embed(&self) -> bool {
+        matches!(self.bytes, Variant::Embed(_))
+    }
+
+    /// The string content. It's empty for embeds.
     pub fn as_str(&self) -> &str {
         match &self.bytes {
             // SAFETY: `bytes` is always valid utf8
             Variant::BytesSlice(s) => unsafe { std::str::from_utf8_unchecked(s) },
             Variant::Owned(s) => s,
+            Variant::Embed(_) => "",
         }
     }
 
@@ -81,6 +112,7 @@ impl StringSlice {
         match &self.bytes {
             Variant::BytesSlice(s) => s.len(),
             Variant::Owned(s) => s.len(),
+            Variant::Embed(_) => 1,
         }
     }
 
@@ -88,24 +120,33 @@ impl StringSlice {
         match &self.bytes {
             Variant::BytesSlice(s) => s.deref(),
             Variant::Owned(s) => s.as_bytes(),
+            Variant::Embed(_) => &[],
         }
     }
 
     pub fn len_unicode(&self) -> usize {
+        if self.is_embed() {
+            return 1;
+        }
+
         count_unicode_chars(self.bytes())
     }
 
     pub fn len_utf16(&self) -> usize {
+        if self.is_embed() {
+            return 1;
+        }
+
         count_utf16_len(self.bytes())
     }
 
     pub fn is_empty(&self) -> bool {
-        self.bytes().is_empty()
+        !self.is_embed() && self.bytes().is_empty()
     }
 
     pub fn extend(&mut self, s: &str) {
         match &mut self.bytes {
-            Variant::BytesSlice(_) => {
+            Variant::BytesSlice(_) | Variant::Embed(_) => {
                 *self = Self {
                     bytes: Variant::Owned(format!("{}{}", self.as_str(), s)),
                 }
@@ -128,8 +169,10 @@ impl<'de> Deserialize<'de> for StringSlice {
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        Ok(s.into())
+        match LoroValue::deserialize(deserializer)? {
+            LoroValue::String(s) => Ok(s.to_string().into()),
+            v => Ok(Self::new_embed(v)),
+        }
     }
 }
 
@@ -138,7 +181,10 @@ impl Serialize for StringSlice {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.as_str())
+        match &self.bytes {
+            Variant::Embed(v) => v.serialize(serializer),
+            _ => serializer.serialize_str(self.as_str()),
+        }
     }
 }
 
@@ -149,7 +195,7 @@ impl DeltaValue for StringSlice {
                 Ok(_) => Ok(()),
                 Err(_) => Err(other),
             },
-            (Variant::Owned(s), _) => {
+            (Variant::Owned(s), Variant::BytesSlice(_) | Variant::Owned(_)) => {
                 s.push_str(other.as_str());
                 Ok(())
             }
@@ -158,6 +204,14 @@ impl DeltaValue for StringSlice {
     }
 
     fn take(&mut self, length: usize) -> Self {
+        if self.is_embed() {
+            if length == 0 {
+                return Self::from(String::new());
+            }
+
+            return std::mem::replace(self, Self::from(String::new()));
+        }
+
         let length = if cfg!(feature = "wasm") {
             utf16_to_utf8_index(self.as_str(), length).unwrap()
         } else {
@@ -180,13 +234,16 @@ impl DeltaValue for StringSlice {
                     bytes: Variant::Owned(other),
                 }
             }
+            Variant::Embed(_) => unreachable!(),
         }
     }
 
     /// Unicode length of the string
     /// Utf16 length when in WASM
     fn length(&self) -> usize {
-        if cfg!(feature = "wasm") {
+        if self.is_embed() {
+            1
+        } else if cfg!(feature = "wasm") {
             count_utf16_len(self.bytes())
         } else {
             count_unicode_chars(self.bytes())
@@ -196,7 +253,9 @@ impl DeltaValue for StringSlice {
 
 impl HasLength for StringSlice {
     fn rle_len(&self) -> usize {
-        if cfg!(feature = "wasm") {
+        if self.is_embed() {
+            1
+        } else if cfg!(feature = "wasm") {
             count_utf16_len(self.bytes())
         } else {
             count_unicode_chars(self.bytes())
@@ -209,8 +268,12 @@ impl TryInsert for StringSlice {
     where
         Self: Sized,
     {
+        if elem.is_embed() {
+            return Err(elem);
+        }
+
         match &mut self.bytes {
-            Variant::BytesSlice(_) => Err(elem),
+            Variant::BytesSlice(_) | Variant::Embed(_) => Err(elem),
             Variant::Owned(s) => {
                 if s.capacity() >= s.len() + elem.len_bytes() {
                     let pos = if cfg!(feature = "wasm") {
@@ -273,6 +336,14 @@ impl Mergeable for StringSlice {
 
 impl Sliceable for StringSlice {
     fn _slice(&self, range: std::ops::Range<usize>) -> Self {
+        if self.is_embed() {
+            if range.is_empty() {
+                return Self::from(String::new());
+            }
+
+            return self.clone();
+        }
+
         let range = if cfg!(feature = "wasm") {
             let start = utf16_to_utf8_index(self.as_str(), range.start).unwrap();
             let end = utf16_to_utf8_index(self.as_str(), range.end).unwrap();
@@ -286,12 +357,21 @@ impl Sliceable for StringSlice {
         let bytes = match &self.bytes {
             Variant::BytesSlice(s) => Variant::BytesSlice(s.slice_clone(range)),
             Variant::Owned(s) => Variant::Owned(s[range].to_string()),
+            Variant::Embed(_) => unreachable!(),
         };
 
         Self { bytes }
     }
 
     fn split(&mut self, pos: usize) -> Self {
+        if self.is_embed() {
+            if pos == 0 {
+                return std::mem::replace(self, Self::from(String::new()));
+            }
+
+            return Self::from(String::new());
+        }
+
         let pos = if cfg!(feature = "wasm") {
             utf16_to_utf8_index(self.as_str(), pos).unwrap()
         } else {
@@ -308,6 +388,7 @@ impl Sliceable for StringSlice {
                 let other = s.split_off(pos);
                 Variant::Owned(other)
             }
+            Variant::Embed(_) => unreachable!(),
         };
 
         Self { bytes }
@@ -9,6 +9,7 @@ use crate::{
 use generic_btree::rle::HasLength;
 use loro_common::ContainerType;
 pub use loro_common::LoroValue;
+use serde::Deserialize;
 
 // TODO: rename this trait
 pub trait ToJson {
@@ -91,7 +92,7 @@ impl ToJson for DeltaItem<StringSlice, StyleMeta> {
                 attributes: meta,
             }
         } else if map.contains_key("insert") {
-            let value = map["insert"].as_str().unwrap().to_string().into();
+            let value = StringSlice::deserialize(&map["insert"]).unwrap();
             let meta = if let Some(meta) = map.get("attributes") {
                 StyleMeta::from_json(meta.to_string().as_str())
             } else {
@@ -173,7 +174,7 @@ fn diff_item_from_json(v: serde_json::Value) -> TextDiffItem {
             attr: meta,
         }
     } else if map.contains_key("insert") {
-        let value = map["insert"].as_str().unwrap().to_string().into();
+        let value = StringSlice::deserialize(&map["insert"]).unwrap();
         let meta = if let Some(meta) = map.get("attributes") {
             StyleMeta::from_json(meta.to_string().as_str())
         } else {
@@ -532,6 +533,14 @@ pub mod wasm {
         utils::string_slice::StringSlice,
     };
 
+    /// An inline embed is converted to its value instead of a string
+    fn string_slice_to_js(value: &StringSlice) -> JsValue {
+        match value.as_embed() {
+            Some(v) => v.clone().into(),
+            None => JsValue::from_str(value.as_str()),
+        }
+    }
+
     impl From<Index> for JsValue {
         fn from(value: Index) -> Self {
             match value {
@@ -661,7 +670,7 @@ pub mod wasm {
                     js_sys::Reflect::set(
                         &obj,
                         &JsValue::from_str("insert"),
-                        &JsValue::from_str(value.as_str()),
+                        &string_slice_to_js(&value),
                     )
                     .unwrap();
                     if !meta.is_empty() {
@@ -738,7 +747,7 @@ pub mod wasm {
                     js_sys::Reflect::set(
                         &obj,
                         &JsValue::from_str("insert"),
-                        &JsValue::from_str(value.as_str()),
+                        &string_slice_to_js(value),
                     )
                     .unwrap();
                     if !attr.is_empty() {
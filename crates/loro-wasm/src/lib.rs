@@ -14,8 +14,8 @@ use loro_internal::{
     encoding::ImportBlobMetadata,
     event::Index,
     handler::{
-        Handler, ListHandler, MapHandler, TextDelta, TextHandler, TreeHandler, UpdateOptions,
-        ValueOrHandler,
+        DiffGranularity, Handler, ListHandler, MapHandler, TextDelta, TextHandler, TreeHandler,
+        UpdateOptions, ValueOrHandler,
     },
     id::{Counter, PeerID, TreeID, ID},
     json::JsonSchema,
@@ -154,7 +154,9 @@ extern "C" {
     pub type JsLoroTreeOrUndefined;
     #[wasm_bindgen(typescript_type = "[string, Value | Container]")]
     pub type MapEntry;
-    #[wasm_bindgen(typescript_type = "{[key: string]: { expand: 'before'|'after'|'none'|'both' }}")]
+    #[wasm_bindgen(
+        typescript_type = "{[key: string]: { expand: 'before'|'after'|'none'|'both' } | { block: true }}"
+    )]
     pub type JsTextStyles;
     #[wasm_bindgen(typescript_type = "Delta<string>[]")]
     pub type JsDelta;
@@ -416,6 +418,9 @@ impl LoroDoc {
     /// - `none`: the mark will not be expanded to include the inserted text at the boundaries
     /// - `both`: when inserting text either right before or right after the given range, the mark will be expanded to include the inserted text
     ///
+    /// Set `block: true` instead to make the style a block attribute, such as a heading level. It is
+    /// stored on the newline that ends each paragraph it's applied to.
+    ///
     /// @example
     /// ```ts
     /// const doc = new LoroDoc();
@@ -449,6 +454,14 @@ impl LoroDoc {
             if !value.is_object() {
                 return Err("Text style config format error".into());
             }
+            if Reflect::get(&value, &"block".into())?
+                .as_bool()
+                .unwrap_or(false)
+            {
+                style_config.insert_block(key.into());
+                continue;
+            }
+
             // read expand value from value
             let expand = Reflect::get(&value, &"expand".into()).expect("`expand` not specified");
             let expand_str = expand.as_string().unwrap();
@@ -1816,6 +1829,47 @@ struct MarkRange {
     end: usize,
 }
 
+fn js_to_update_options(options: JsValue) -> JsResult<UpdateOptions> {
+    if options.is_null() || options.is_undefined() {
+        return Ok(UpdateOptions::default());
+    }
+
+    let opts = match js_sys::Object::try_from(&options) {
+        Some(o) => o,
+        None => return Err(JsError::new("Invalid options").into()),
+    };
+    let get = |key: &str| js_sys::Reflect::get(opts, &key.into()).ok();
+    let granularity = match get("granularity").and_then(|v| v.as_string()).as_deref() {
+        None | Some("char") => DiffGranularity::Char,
+        Some("word") => DiffGranularity::Word,
+        Some(other) => {
+            return Err(JsError::new(&format!("Invalid granularity: {}", other)).into());
+        }
+    };
+    let mut ans = UpdateOptions::default()
+        .use_refined_diff(
+            get("useRefinedDiff")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+        )
+        .granularity(granularity)
+        .ignore_whitespace(
+            get("ignoreWhitespace")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        )
+        .semantic_cleanup(
+            get("semanticCleanup")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        );
+    if let Some(timeout_ms) = get("timeoutMs").and_then(|v| v.as_f64()) {
+        ans = ans.timeout_ms(timeout_ms);
+    }
+
+    Ok(ans)
+}
+
 #[wasm_bindgen]
 impl LoroText {
     /// Create a new detached LoroText (not attached to any LoroDoc).
@@ -1882,26 +1936,7 @@ impl LoroText {
     ///
     #[wasm_bindgen(skip_typescript)]
     pub fn update(&self, text: &str, options: JsValue) -> JsResult<()> {
-        let options = if options.is_null() || options.is_undefined() {
-            UpdateOptions {
-                timeout_ms: None,
-                use_refined_diff: true,
-            }
-        } else {
-            let opts = match js_sys::Object::try_from(&options) {
-                Some(o) => o,
-                None => return Err(JsError::new("Invalid options").into()),
-            };
-            UpdateOptions {
-                timeout_ms: js_sys::Reflect::get(opts, &"timeoutMs".into())
-                    .ok()
-                    .and_then(|v| v.as_f64()),
-                use_refined_diff: js_sys::Reflect::get(opts, &"useRefinedDiff".into())
-                    .ok()
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true),
-            }
-        };
+        let options = js_to_update_options(options)?;
         self.handler
             .update(text, options)
             .map_err(|_| JsError::new("Update timeout").into())
@@ -1912,26 +1947,7 @@ impl LoroText {
     /// It uses Myers' diff algorithm to compute the optimal difference.
     #[wasm_bindgen(js_name = "updateByLine", skip_typescript)]
     pub fn update_by_line(&self, text: &str, options: JsValue) -> JsResult<()> {
-        let options = if options.is_null() || options.is_undefined() {
-            UpdateOptions {
-                timeout_ms: None,
-                use_refined_diff: true,
-            }
-        } else {
-            let opts = match js_sys::Object::try_from(&options) {
-                Some(o) => o,
-                None => return Err(JsError::new("Invalid options").into()),
-            };
-            UpdateOptions {
-                timeout_ms: js_sys::Reflect::get(opts, &"timeoutMs".into())
-                    .ok()
-                    .and_then(|v| v.as_f64()),
-                use_refined_diff: js_sys::Reflect::get(opts, &"useRefinedDiff".into())
-                    .ok()
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true),
-            }
-        };
+        let options = js_to_update_options(options)?;
         self.handler
             .update_by_line(text, options)
             .map_err(|_| JsError::new("Update timeout").into())
@@ -5055,6 +5071,19 @@ export type JsonChange = {
 export interface TextUpdateOptions {
     timeoutMs?: number,
     useRefinedDiff?: boolean,
+    /**
+     * The unit that the texts are compared by. Defaults to `"char"`, which compares
+     * grapheme clusters. It's ignored by `updateByLine`.
+     */
+    granularity?: "char" | "word",
+    /**
+     * Treat the tokens that differ only in whitespace as equal. Defaults to false.
+     */
+    ignoreWhitespace?: boolean,
+    /**
+     * Merge the edits separated by short equalities into larger edits. Defaults to false.
+     */
+    semanticCleanup?: boolean,
 }
 
 export type ExportMode = {
@@ -2,16 +2,21 @@
 use enum_as_inner::EnumAsInner;
 use loro_internal::container::ContainerID;
 use loro_internal::delta::TreeDiff;
+use loro_internal::event::encoded::EncodedDiffEvent;
 use loro_internal::event::EventTriggerKind;
 use loro_internal::handler::{TextDelta, ValueOrHandler};
-use loro_internal::FxHashMap;
+use loro_internal::undo::DiffBatch;
 use loro_internal::{
     event::{Diff as DiffInner, Index},
     ContainerDiff as ContainerDiffInner, DiffEvent as DiffEventInner,
 };
+use loro_internal::{FxHashMap, LoroResult};
 use std::sync::Arc;
 
-use crate::ValueOrContainer;
+use crate::{LoroDoc, ValueOrContainer};
+
+/// Stable JSON and binary encodings of the diffs, see [`loro_internal::undo::DiffBatch::encode`].
+pub use loro_internal::event::encoded;
 
 /// A subscriber to the event.
 pub type Subscriber = Arc<dyn (for<'a> Fn(DiffEvent<'a>)) + Send + Sync>;
@@ -27,6 +32,22 @@ pub struct DiffEvent<'a> {
     pub current_target: Option<ContainerID>,
     /// The diffs of the event.
     pub events: Vec<ContainerDiff<'a>>,
+    inner: DiffEventInner<'a>,
+}
+
+impl DiffEvent<'_> {
+    /// Encode the event into an owned form that can be serialized as JSON with
+    /// [`EncodedDiffEvent::to_json`] or as bytes with [`EncodedDiffEvent::to_bytes`].
+    pub fn encode(&self) -> EncodedDiffEvent {
+        self.inner.encode()
+    }
+
+    /// Decode an encoded event into a [`DiffBatch`]. The child containers in the diffs are
+    /// bound to `doc`, and the batch can be applied with
+    /// [`loro_internal::LoroDoc::apply_diff`] on [`LoroDoc::inner`].
+    pub fn decode(encoded: &EncodedDiffEvent, doc: &LoroDoc) -> LoroResult<DiffBatch> {
+        Ok(DiffBatch::new(vec![encoded.decode(doc.inner())?]))
+    }
 }
 
 /// A diff of a container.
@@ -108,8 +129,9 @@ impl<'a> From<DiffEventInner<'a>> for DiffEvent<'a> {
         DiffEvent {
             triggered_by: value.event_meta.by,
             origin: &value.event_meta.origin,
-            current_target: value.current_target,
+            current_target: value.current_target.clone(),
             events: value.events.iter().map(|&diff| diff.into()).collect(),
+            inner: value,
         }
     }
 }
@@ -2,11 +2,13 @@
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
 use event::{DiffEvent, Subscriber};
-use fxhash::FxHashSet;
+use fxhash::{FxHashMap, FxHashSet};
 pub use loro_common::InternalString;
 pub use loro_internal::cursor::CannotFindRelativePosition;
 use loro_internal::cursor::Cursor;
 use loro_internal::cursor::PosQueryResult;
+use loro_internal::cursor::RangeCursor;
+use loro_internal::cursor::RangeQueryResult;
 use loro_internal::cursor::Side;
 pub use loro_internal::encoding::ImportStatus;
 use loro_internal::handler::HandlerTrait;
@@ -30,8 +32,8 @@ use std::ops::Range;
 use std::sync::Arc;
 use tracing::info;
 
-pub use loro_internal::diff::diff_impl::UpdateOptions;
 pub use loro_internal::diff::diff_impl::UpdateTimeoutError;
+pub use loro_internal::diff::diff_impl::{DiffGranularity, UpdateOptions};
 pub use loro_internal::subscription::LocalUpdateCallback;
 pub use loro_internal::subscription::PeerIdUpdateCallback;
 pub use loro_internal::ChangeMeta;
@@ -43,11 +45,18 @@ pub use loro_internal::configure::{StyleConfig, StyleConfigMap};
 pub use loro_internal::container::richtext::ExpandType;
 pub use loro_internal::container::{ContainerID, ContainerType, IntoContainerId};
 pub use loro_internal::cursor;
+pub use loro_internal::delta::{QuillAttributes, QuillDelta, QuillValue};
 pub use loro_internal::delta::{TreeDeltaItem, TreeDiff, TreeDiffItem, TreeExternalDiff};
 pub use loro_internal::encoding::ExportMode;
 pub use loro_internal::encoding::ImportBlobMetadata;
 pub use loro_internal::event::{EventTriggerKind, Index};
+#[cfg(feature = "line-index")]
+pub use loro_internal::handler::LineChange;
 pub use loro_internal::handler::TextDelta;
+pub use loro_internal::handler::TextPosUnit;
+pub use loro_internal::handler::{CustomMarkRender, MarkRender, TextFormat, TextRenderOptions};
+#[cfg(feature = "text-search")]
+pub use loro_internal::handler::{Regex, TextMatch};
 pub use loro_internal::json;
 pub use loro_internal::json::{
     FutureOp as JsonFutureOp, FutureOpWrapper as JsonFutureOpWrapper, JsonChange, JsonOp,
@@ -73,6 +82,7 @@ pub use loro_internal::{
 };
 pub use loro_kv_store as kv_store;
 
+pub use loro_internal::json_patch;
 #[cfg(feature = "jsonpath")]
 pub use loro_internal::jsonpath;
 #[cfg(feature = "jsonpath")]
@@ -261,6 +271,30 @@ impl LoroDoc {
         self.doc.checkout(frontiers)
     }
 
+    /// Get the frontiers of the document as of the given Unix timestamp (in seconds).
+    ///
+    /// It's the maximal causally consistent set of changes whose timestamps are less than
+    /// or equal to `timestamp`. A change is excluded if any of its dependencies is excluded,
+    /// so changes from a peer whose clock runs ahead can't pull in their descendants early.
+    ///
+    /// Timestamps are only recorded when `set_record_timestamp(true)` is enabled or when
+    /// they are set by `commit_with`. Changes without timestamps are treated as time 0.
+    ///
+    /// **NOTE:** This method will implicitly commit.
+    #[inline]
+    pub fn frontiers_at_timestamp(&self, timestamp: Timestamp) -> Frontiers {
+        self.doc.frontiers_at_timestamp(timestamp)
+    }
+
+    /// Checkout the `DocState` to the version as of the given Unix timestamp (in seconds).
+    ///
+    /// It's the same as `doc.checkout(&doc.frontiers_at_timestamp(timestamp))`.
+    /// The document becomes detached after this operation.
+    #[inline]
+    pub fn checkout_at_timestamp(&self, timestamp: Timestamp) -> LoroResult<()> {
+        self.doc.checkout_at_timestamp(timestamp)
+    }
+
     /// Checkout the `DocState` to the latest version.
     ///
     /// > The document becomes detached during a `checkout` operation.
@@ -445,6 +479,49 @@ impl LoroDoc {
         self.doc.export_json_updates(start_vv, end_vv)
     }
 
+    /// Export the json updates as NDJSON, one change per line.
+    ///
+    /// It streams the changes in causal order and writes them one by one instead of building the
+    /// whole [`JsonSchema`] in memory, so it can be used to pipe large histories into log tooling.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, VersionVector};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let mut ndjson = Vec::new();
+    /// doc.export_json_updates_ndjson(&VersionVector::default(), &doc.oplog_vv(), &mut ndjson)
+    ///     .unwrap();
+    ///
+    /// let new_doc = LoroDoc::new();
+    /// new_doc.import_json_updates_ndjson(ndjson.as_slice()).unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn export_json_updates_ndjson<W: std::io::Write>(
+        &self,
+        start_vv: &VersionVector,
+        end_vv: &VersionVector,
+        writer: W,
+    ) -> std::io::Result<()> {
+        self.doc
+            .export_json_updates_ndjson(start_vv, end_vv, writer)
+    }
+
+    /// Import the NDJSON updates exported by [`LoroDoc::export_json_updates_ndjson`].
+    ///
+    /// The input is read and imported in batches, so the whole input is never held in memory.
+    #[inline]
+    pub fn import_json_updates_ndjson<R: std::io::BufRead>(
+        &self,
+        reader: R,
+    ) -> Result<ImportStatus, LoroError> {
+        self.doc.import_json_updates_ndjson(reader)
+    }
+
     /// Export all the ops not included in the given `VersionVector`
     #[deprecated(
         since = "1.0.0",
@@ -702,6 +779,32 @@ impl LoroDoc {
         self.doc.get_by_str_path(path).map(ValueOrContainer::from)
     }
 
+    /// Update the root container to the target deep value with the smallest set of ops.
+    ///
+    /// Maps are diffed key by key, lists are diffed element by element, texts are updated
+    /// with [`LoroText::update`] and nested containers are reconciled recursively, so the
+    /// concurrent edits on the unchanged parts survive the merge.
+    ///
+    /// If the root container doesn't exist yet, a map, list or text is created according
+    /// to the type of the value. New maps and lists inside the value become containers.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ToJson, loro_value};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_deep_value("form", loro_value!({"name": "Alice", "tags": ["a", "b"]})).unwrap();
+    /// doc.set_deep_value("form", loro_value!({"name": "Alice B", "tags": ["b", "c"]})).unwrap();
+    /// assert_eq!(
+    ///     doc.get_deep_value().to_json_value(),
+    ///     serde_json::json!({"form": {"name": "Alice B", "tags": ["b", "c"]}})
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_deep_value(&self, root: &str, value: impl Into<LoroValue>) -> LoroResult<()> {
+        self.doc.set_deep_value(root, &value.into())
+    }
+
     /// Get the absolute position of the given cursor.
     ///
     /// # Example
@@ -728,6 +831,87 @@ impl LoroDoc {
         self.doc.query_pos(cursor)
     }
 
+    /// Get the absolute positions of many cursors at once, in the same order as `cursors`.
+    ///
+    /// It returns the same results as calling [`LoroDoc::get_cursor_pos`] on each cursor,
+    /// but it resolves the cursors of each container in one pass and shares the history
+    /// lookups of the cursors whose elements were deleted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let text = &doc.get_text("text");
+    /// text.insert(0, "01234").unwrap();
+    /// let a = text.get_cursor(1, Default::default()).unwrap();
+    /// let b = text.get_cursor(3, Default::default()).unwrap();
+    /// text.delete(0, 2).unwrap();
+    /// let ans = doc.get_cursor_pos_batch(&[a, b]);
+    /// assert_eq!(ans[0].as_ref().unwrap().current.pos, 0);
+    /// assert_eq!(ans[1].as_ref().unwrap().current.pos, 1);
+    /// ```
+    #[inline]
+    pub fn get_cursor_pos_batch(
+        &self,
+        cursors: &[Cursor],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        self.doc.query_pos_batch(cursors)
+    }
+
+    /// Get the current range of the given range cursor.
+    ///
+    /// The positions in a text are in `unit`, other containers ignore it. If all the content
+    /// of the range is deleted, it returns [`RangeQueryResult::Deleted`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{cursor::RangeQueryResult, LoroDoc, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// let range = text.get_range_cursor(6..11).unwrap();
+    /// text.insert(0, "😀 ").unwrap();
+    /// // The insertion at the boundary is not included
+    /// text.insert(13, "!").unwrap();
+    /// let RangeQueryResult::Range { range: current, .. } =
+    ///     doc.get_range_cursor_pos(&range, TextPosUnit::Utf16).unwrap()
+    /// else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(current, 9..14);
+    /// text.delete(8, 5).unwrap();
+    /// assert_eq!(
+    ///     doc.get_range_cursor_pos(&range, TextPosUnit::Unicode).unwrap(),
+    ///     RangeQueryResult::Deleted
+    /// );
+    /// ```
+    #[inline]
+    pub fn get_range_cursor_pos(
+        &self,
+        range: &RangeCursor,
+        unit: TextPosUnit,
+    ) -> Result<RangeQueryResult, CannotFindRelativePosition> {
+        self.doc.query_range(range, unit)
+    }
+
+    /// Get the cursors that cannot be resolved in the shallow snapshot exported from `frontiers`.
+    ///
+    /// A cursor becomes unresolvable when its target has been deleted and the history
+    /// needed to trace back its position is trimmed. It's useful to check the impact of
+    /// [`ExportMode::ShallowSnapshot`] before trimming the history. You can compute the
+    /// cut point by time with [`LoroDoc::frontiers_at_timestamp`].
+    #[inline]
+    pub fn unresolvable_cursors_in_shallow_snapshot(
+        &self,
+        frontiers: &Frontiers,
+        cursors: &[Cursor],
+    ) -> Result<Vec<Cursor>, LoroEncodeError> {
+        self.doc
+            .unresolvable_cursors_in_shallow_snapshot(frontiers, cursors)
+    }
+
     /// Get the inner LoroDoc ref.
     #[inline]
     pub fn inner(&self) -> &InnerLoroDoc {
@@ -768,6 +952,40 @@ impl LoroDoc {
         self.doc.export(mode)
     }
 
+    /// Redact the content of the ops in the given range from the history and the state.
+    ///
+    /// It follows the same rules as [`json::redact`]: the text is replaced with
+    /// U+FFFD, the inserted values are replaced with null, while the op ids, lengths and
+    /// lamports are kept. The exported updates and snapshots won't contain the redacted content.
+    ///
+    /// The redaction isn't carried by the exported updates. Every peer that should purge the
+    /// content calls this method with the same range, which the app sends through its own
+    /// channel. Subscribers receive the purged state as a checkout event.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, LoroDoc, VersionRange};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "my secret").unwrap();
+    /// let peer = LoroDoc::new();
+    /// peer.import(&doc.export(ExportMode::all_updates()).unwrap()).unwrap();
+    ///
+    /// let mut range = VersionRange::new();
+    /// range.insert(1, 3, 9);
+    /// doc.redact(&range).unwrap();
+    /// assert_eq!(doc.get_text("text").to_string(), "my ������");
+    /// // The peer applies the same redaction
+    /// peer.redact(&range).unwrap();
+    /// assert_eq!(peer.get_text("text").to_string(), "my ������");
+    /// ```
+    #[inline]
+    pub fn redact(&self, range: &VersionRange) -> Result<(), json::RedactError> {
+        self.doc.redact(range)
+    }
+
     /// Analyze the container info of the doc
     ///
     /// This is used for development and debugging. It can be slow.
@@ -823,6 +1041,85 @@ impl LoroDoc {
         })
     }
 
+    /// Get the [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902) JSON Patch that turns
+    /// the deep value at version `a` into the deep value at version `b`.
+    ///
+    /// The paths are JSON Pointers into the value returned by [`LoroDoc::get_deep_value`].
+    /// The current state of the doc is not changed.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, json_patch::JsonPatchOp};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let map = doc.get_map("map");
+    /// map.insert("a", 1).unwrap();
+    /// doc.commit();
+    /// let a = doc.state_frontiers();
+    /// map.insert("a", 2).unwrap();
+    /// doc.commit();
+    /// let b = doc.state_frontiers();
+    ///
+    /// let patch = doc.json_patch_between(&a, &b).unwrap();
+    /// assert_eq!(
+    ///     patch,
+    ///     vec![JsonPatchOp::Add { path: "/map/a".into(), value: serde_json::json!(2) }]
+    /// );
+    /// ```
+    #[inline]
+    pub fn json_patch_between(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> LoroResult<Vec<json_patch::JsonPatchOp>> {
+        self.doc.json_patch_between(a, b)
+    }
+
+    /// Apply an [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902) JSON Patch against
+    /// the value returned by [`LoroDoc::get_deep_value`].
+    ///
+    /// The patch is applied as minimal CRDT ops. JSON objects and arrays become map and list
+    /// containers, and a string replacing a text container is applied as text edits.
+    /// The patch is atomic: if any operation fails, the doc is left unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ToJson, json_patch::JsonPatchOp};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.apply_json_patch(&[
+    ///     JsonPatchOp::Replace { path: "/text".into(), value: json!("Hello world") },
+    ///     JsonPatchOp::Add { path: "/map".into(), value: json!({"list": [1, 2]}) },
+    /// ])
+    /// .unwrap();
+    /// assert_eq!(
+    ///     doc.get_deep_value().to_json_value(),
+    ///     json!({"text": "Hello world", "map": {"list": [1, 2]}})
+    /// );
+    /// ```
+    #[inline]
+    pub fn apply_json_patch(
+        &self,
+        patch: &[json_patch::JsonPatchOp],
+    ) -> Result<(), json_patch::JsonPatchError> {
+        self.doc.apply_json_patch(patch)
+    }
+
+    /// Apply an [RFC 7396](https://datatracker.ietf.org/doc/html/rfc7396) JSON Merge Patch
+    /// against the value returned by [`LoroDoc::get_deep_value`].
+    ///
+    /// Nested maps are merged in place, and the other values are applied with the same
+    /// rules as [`LoroDoc::apply_json_patch`].
+    #[inline]
+    pub fn apply_json_merge_patch(
+        &self,
+        patch: &serde_json::Value,
+    ) -> Result<(), json_patch::JsonPatchError> {
+        self.doc.apply_json_merge_patch(patch)
+    }
+
     /// Get the number of operations in the pending transaction.
     ///
     /// The pending transaction is the one that is not committed yet. It will be committed
@@ -867,6 +1164,36 @@ impl LoroDoc {
     pub fn get_changed_containers_in(&self, id: ID, len: usize) -> FxHashSet<ContainerID> {
         self.doc.get_changed_containers_in(id, len)
     }
+
+    /// Gets the changes that modified the given container or any of its descendants.
+    ///
+    /// **NOTE:** This method will implicitly commit.
+    ///
+    /// The returned changes are sorted by their Lamport timestamps. It's the reverse of
+    /// `doc.get_changed_containers_in()`, and can be used to build an activity feed for
+    /// a part of the document.
+    ///
+    /// The first call builds an index from containers to changes. The index is updated
+    /// incrementally on the following calls. Use `doc.free_container_changes_index()` to
+    /// release it.
+    ///
+    /// # Arguments
+    ///
+    /// * `container` - The container to query
+    /// * `since` - If provided, only the changes after this version are returned
+    pub fn changes_touching(
+        &self,
+        container: &ContainerID,
+        since: Option<&Frontiers>,
+    ) -> LoroResult<Vec<ChangeMeta>> {
+        self.doc.changes_touching(container, since)
+    }
+
+    /// Free the index that is built by `doc.changes_touching()`.
+    #[inline]
+    pub fn free_container_changes_index(&self) {
+        self.doc.free_container_changes_index()
+    }
 }
 
 /// It's used to prevent the user from implementing the trait directly.
@@ -1444,6 +1771,99 @@ impl LoroText {
         self.handler.insert_utf8(pos, s)
     }
 
+    /// Insert a string at the given unicode position with exactly the given styles.
+    ///
+    /// Unlike [`LoroText::insert`] followed by [`LoroText::mark`], the inserted text never
+    /// carries the styles it would inherit from its neighbors unless they are in `attrs`.
+    /// A `null` value removes the style. The styles are applied in the same transaction as
+    /// the insertion.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ToJson};
+    /// use fxhash::FxHashMap;
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// text.mark(0..5, "bold", true).unwrap();
+    /// let mut attrs = FxHashMap::default();
+    /// attrs.insert("italic".to_string(), true.into());
+    /// text.insert_with_marks(5, " world", &attrs).unwrap();
+    /// assert_eq!(
+    ///     text.to_delta().to_json_value(),
+    ///     json!([
+    ///         { "insert": "Hello", "attributes": { "bold": true } },
+    ///         { "insert": " world", "attributes": { "italic": true } },
+    ///     ])
+    /// );
+    /// ```
+    pub fn insert_with_marks(
+        &self,
+        pos: usize,
+        s: &str,
+        attrs: &FxHashMap<String, LoroValue>,
+    ) -> LoroResult<()> {
+        self.handler.insert_with_marks(pos, s, attrs)
+    }
+
+    /// Insert an inline embed, such as an image or a mention, at the given unicode position.
+    ///
+    /// An embed occupies a single position in the text. It can be styled by [`LoroText::mark`]
+    /// and shows up as an insert of the value in [`LoroText::to_delta`], but it is not part of
+    /// [`LoroText::to_string`].
+    ///
+    /// A string or null embed is rejected with [`LoroError::ArgErr`], because it couldn't be
+    /// told apart from text in the delta.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroValue, ToJson};
+    /// # use serde_json::json;
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "ab").unwrap();
+    /// let image: LoroValue = serde_json::from_value(json!({ "image": "a.png" })).unwrap();
+    /// text.insert_embed(1, image).unwrap();
+    /// assert_eq!(text.to_string(), "ab");
+    /// assert_eq!(text.len_unicode(), 3);
+    /// assert_eq!(
+    ///     text.to_delta().to_json_value(),
+    ///     json!([
+    ///         { "insert": "a" },
+    ///         { "insert": { "image": "a.png" } },
+    ///         { "insert": "b" },
+    ///     ])
+    /// );
+    /// ```
+    pub fn insert_embed(&self, pos: usize, v: impl Into<LoroValue>) -> LoroResult<()> {
+        self.handler.insert_embed(pos, v)
+    }
+
+    /// Insert a child container as an inline embed at the given unicode position.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroMap, LoroValue};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "ab").unwrap();
+    /// let map = text.insert_embed_container(1, LoroMap::new()).unwrap();
+    /// map.insert("src", "a.png").unwrap();
+    /// let delta = text.to_delta().into_list().unwrap();
+    /// let embed = delta[1].as_map().unwrap().get("insert").unwrap();
+    /// assert_eq!(embed, &LoroValue::Container(map.id()));
+    /// ```
+    pub fn insert_embed_container<C: ContainerTrait>(&self, pos: usize, child: C) -> LoroResult<C> {
+        Ok(C::from_handler(
+            self.handler
+                .insert_embed_container(pos, child.to_handler())?,
+        ))
+    }
+
     /// Delete a range of text at the given unicode position with unicode length.
     pub fn delete(&self, pos: usize, len: usize) -> LoroResult<()> {
         self.handler.delete_unicode(pos, len)
@@ -1454,6 +1874,37 @@ impl LoroText {
         self.handler.delete_utf8(pos, len)
     }
 
+    /// Insert a string at the given grapheme cluster position.
+    ///
+    /// Grapheme clusters are the characters perceived by users, such as an emoji with a skin
+    /// tone. The grapheme cluster methods scan the text, so they cost O(n).
+    pub fn insert_grapheme(&self, pos: usize, s: &str) -> LoroResult<()> {
+        self.handler.insert_grapheme(pos, s)
+    }
+
+    /// Delete `len` grapheme clusters at the given grapheme cluster position.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "👍🏽👨‍👩‍👧!").unwrap();
+    /// assert_eq!(text.len_grapheme(), 3);
+    /// text.delete_grapheme(1, 1).unwrap();
+    /// assert_eq!(text.to_string(), "👍🏽!");
+    /// ```
+    pub fn delete_grapheme(&self, pos: usize, len: usize) -> LoroResult<()> {
+        self.handler.delete_grapheme(pos, len)
+    }
+
+    /// Get a string slice at the given grapheme cluster range
+    pub fn slice_grapheme(&self, start_index: usize, end_index: usize) -> LoroResult<String> {
+        self.handler.slice_grapheme(start_index, end_index)
+    }
+
     /// Get a string slice at the given Unicode range
     pub fn slice(&self, start_index: usize, end_index: usize) -> LoroResult<String> {
         self.handler.slice(start_index, end_index)
@@ -1489,23 +1940,38 @@ impl LoroText {
         self.handler.len_utf16()
     }
 
+    /// Get the length of the text container in grapheme clusters.
+    pub fn len_grapheme(&self) -> usize {
+        self.handler.len_grapheme()
+    }
+
     /// Update the current text based on the provided text.
     ///
     /// It will calculate the minimal difference and apply it to the current text.
-    /// It uses Myers' diff algorithm to compute the optimal difference.
+    /// It uses Myers' diff algorithm to compute the optimal difference. The diff runs on
+    /// grapheme clusters, so it never splits a cluster like an emoji with a skin tone.
     ///
     /// This could take a long time for large texts (e.g. > 50_000 characters).
     /// In that case, you should use `updateByLine` instead.
     ///
+    /// [`UpdateOptions`] can switch the diff to words, ignore whitespace-only changes,
+    /// or merge tiny edits into whole-word edits.
+    ///
+    /// `text` is plain text, so the inline embeds are removed.
+    ///
     /// # Example
     /// ```rust
-    /// use loro::LoroDoc;
+    /// use loro::{DiffGranularity, LoroDoc, UpdateOptions};
     ///
     /// let doc = LoroDoc::new();
     /// let text = doc.get_text("text");
     /// text.insert(0, "Hello").unwrap();
     /// text.update("Hello World", Default::default()).unwrap();
     /// assert_eq!(text.to_string(), "Hello World");
+    ///
+    /// let options = UpdateOptions::default().granularity(DiffGranularity::Word);
+    /// text.update("Hi World", options).unwrap();
+    /// assert_eq!(text.to_string(), "Hi World");
     /// ```
     ///
     pub fn update(&self, text: &str, options: UpdateOptions) -> Result<(), UpdateTimeoutError> {
@@ -1515,6 +1981,8 @@ impl LoroText {
     /// Update the current text based on the provided text.
     ///
     /// This update calculation is line-based, which will be more efficient but less precise.
+    /// With [`UpdateOptions::ignore_whitespace`], re-indented lines are matched and only
+    /// their whitespace is edited.
     pub fn update_by_line(
         &self,
         text: &str,
@@ -1541,6 +2009,10 @@ impl LoroText {
     ///
     /// *You should make sure that a key is always associated with the same expand type.*
     ///
+    /// If `key` is registered by [`StyleConfigMap::insert_block`], the newlines that end the
+    /// paragraphs touched by the range are marked instead. Like [`LoroText::set_block_attr`], it
+    /// fails if the range reaches a last paragraph that doesn't end with a newline.
+    ///
     /// Note: this is not suitable for unmergeable annotations like comments.
     pub fn mark(
         &self,
@@ -1571,6 +2043,229 @@ impl LoroText {
         self.handler.unmark(range.start, range.end, key)
     }
 
+    /// Set a block attribute, such as a heading level, on the paragraph at the given unicode
+    /// position.
+    ///
+    /// The key must be registered by [`StyleConfigMap::insert_block`]. The attribute is stored
+    /// on the newline that ends the paragraph, like in [Quill](https://quilljs.com/docs/delta/),
+    /// so it shows up on that newline in [`LoroText::to_delta`] and in text events.
+    ///
+    /// It fails with [`LoroError::ArgErr`] if the paragraph doesn't end with a newline. The text
+    /// is never edited here, because peers appending the missing newline concurrently would
+    /// leave an extra empty paragraph. Keep a newline at the end of the text, as Quill does,
+    /// to style the last paragraph.
+    ///
+    /// Concurrent changes to different attributes of the same paragraph are all kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, StyleConfigMap, ToJson};
+    /// # use serde_json::json;
+    /// let doc = LoroDoc::new();
+    /// let mut styles = StyleConfigMap::default_rich_text_config();
+    /// styles.insert_block("header".into());
+    /// doc.config_text_style(styles);
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Title\nBody").unwrap();
+    /// text.set_block_attr(2, "header", 1).unwrap();
+    /// assert_eq!(
+    ///     text.to_delta().to_json_value(),
+    ///     json!([
+    ///         { "insert": "Title" },
+    ///         { "insert": "\n", "attributes": { "header": 1 } },
+    ///         { "insert": "Body" },
+    ///     ])
+    /// );
+    /// assert_eq!(text.get_block_attrs(0).get("header"), Some(&1.into()));
+    /// ```
+    pub fn set_block_attr(
+        &self,
+        pos: usize,
+        key: &str,
+        value: impl Into<LoroValue>,
+    ) -> LoroResult<()> {
+        self.handler.set_block_attr(pos, key, value.into())
+    }
+
+    /// Remove a block attribute from the paragraph at the given unicode position.
+    pub fn remove_block_attr(&self, pos: usize, key: &str) -> LoroResult<()> {
+        self.handler.remove_block_attr(pos, key)
+    }
+
+    /// Get the block attributes of the paragraph at the given unicode position.
+    pub fn get_block_attrs(&self, pos: usize) -> FxHashMap<String, LoroValue> {
+        self.handler.get_block_attrs(pos)
+    }
+
+    /// Get the styles of the character at `pos`, which is in the given unit.
+    ///
+    /// Styles removed by [`LoroText::unmark`] are not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroValue, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// text.mark(0..5, "bold", true).unwrap();
+    /// let styles = text.get_styles_at(4, TextPosUnit::Unicode).unwrap();
+    /// assert_eq!(styles.get("bold"), Some(&LoroValue::from(true)));
+    /// assert!(text.get_styles_at(5, TextPosUnit::Unicode).unwrap().is_empty());
+    /// ```
+    pub fn get_styles_at(
+        &self,
+        pos: usize,
+        unit: TextPosUnit,
+    ) -> LoroResult<FxHashMap<String, LoroValue>> {
+        self.handler.get_styles_at(pos, unit)
+    }
+
+    /// Get the ranges where the style `key` is active, in the given unit, with the value of
+    /// the style.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroValue, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// text.mark(0..2, "comment", "c1").unwrap();
+    /// text.mark(6..8, "comment", "c2").unwrap();
+    /// assert_eq!(
+    ///     text.ranges_with_style("comment", TextPosUnit::Unicode),
+    ///     vec![(0..2, LoroValue::from("c1")), (6..8, LoroValue::from("c2"))]
+    /// );
+    /// ```
+    pub fn ranges_with_style(
+        &self,
+        key: &str,
+        unit: TextPosUnit,
+    ) -> Vec<(Range<usize>, LoroValue)> {
+        self.handler.ranges_with_style(key, unit)
+    }
+
+    /// Split `range`, which is in the given unit, into spans that have the same styles.
+    ///
+    /// The spans cover the whole range, so unstyled text shows up as spans with empty styles.
+    pub fn style_spans(
+        &self,
+        range: Range<usize>,
+        unit: TextPosUnit,
+    ) -> LoroResult<Vec<(Range<usize>, FxHashMap<String, LoroValue>)>> {
+        self.handler.style_spans(range, unit)
+    }
+
+    /// Find all the non-overlapping matches of `regex`.
+    ///
+    /// Each match has its range in the given unit and stable cursors at both ends.
+    /// Inline embeds are matched as U+FFFC.
+    ///
+    /// The regex search needs the `text-search` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, Regex, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "😀 cat hat").unwrap();
+    /// let regex = Regex::new("[ch]at").unwrap();
+    /// let found = text.find_all(&regex, TextPosUnit::Utf16);
+    /// assert_eq!(found[0].range, 3..6);
+    /// assert_eq!(found[1].range, 7..10);
+    /// ```
+    #[cfg(feature = "text-search")]
+    pub fn find_all(&self, regex: &Regex, unit: TextPosUnit) -> Vec<TextMatch> {
+        self.handler.find_all(regex, unit)
+    }
+
+    /// Replace all the non-overlapping matches of `regex` with `replacement` in one transaction.
+    ///
+    /// `replacement` may refer to capture groups, like [`Regex::replace_all`]. Only the
+    /// characters that differ between a match and its replacement are edited, so the retained
+    /// characters keep their styles and the inserted ones take the styles of what they replace.
+    ///
+    /// Returns the number of replaced matches.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroValue, Regex, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "colour and flavour").unwrap();
+    /// text.mark(0..6, "bold", true).unwrap();
+    /// let regex = Regex::new("our").unwrap();
+    /// assert_eq!(text.replace_all(&regex, "or").unwrap(), 2);
+    /// assert_eq!(text.to_string(), "color and flavor");
+    /// assert_eq!(
+    ///     text.ranges_with_style("bold", TextPosUnit::Unicode),
+    ///     vec![(0..5, LoroValue::from(true))]
+    /// );
+    /// ```
+    #[cfg(feature = "text-search")]
+    pub fn replace_all(&self, regex: &Regex, replacement: &str) -> LoroResult<usize> {
+        self.handler.replace_all(regex, replacement)
+    }
+
+    /// The number of lines, which is the number of `\n` plus one.
+    ///
+    /// The line queries need the `line-index` feature, which keeps a line index in the text,
+    /// so they don't scan the whole text.
+    #[cfg(feature = "line-index")]
+    pub fn line_count(&self) -> usize {
+        self.handler.line_count()
+    }
+
+    /// Get the content of the line at the zero-based `line`, without the `\n`.
+    #[cfg(feature = "line-index")]
+    pub fn line(&self, line: usize) -> LoroResult<String> {
+        self.handler.line(line)
+    }
+
+    /// Convert a position in the given unit into a zero-based `(line, column)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, TextPosUnit};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "let a = 1;\nlet 😀 = 2;").unwrap();
+    /// assert_eq!(text.line_count(), 2);
+    /// assert_eq!(text.line(1).unwrap(), "let 😀 = 2;");
+    /// assert_eq!(text.pos_to_line_col(16, TextPosUnit::Unicode).unwrap(), (1, 5));
+    /// assert_eq!(text.pos_to_line_col(17, TextPosUnit::Utf16).unwrap(), (1, 6));
+    /// assert_eq!(text.line_col_to_pos(1, 6, TextPosUnit::Utf16).unwrap(), 17);
+    /// ```
+    #[cfg(feature = "line-index")]
+    pub fn pos_to_line_col(&self, pos: usize, unit: TextPosUnit) -> LoroResult<(usize, usize)> {
+        self.handler.pos_to_line_col(pos, unit)
+    }
+
+    /// Convert a zero-based `(line, column)` into a position in the given unit.
+    ///
+    /// The column can point to the end of the line, but not beyond.
+    #[cfg(feature = "line-index")]
+    pub fn line_col_to_pos(&self, line: usize, col: usize, unit: TextPosUnit) -> LoroResult<usize> {
+        self.handler.line_col_to_pos(line, col, unit)
+    }
+
+    /// Express the delta of a text event as a change of lines.
+    ///
+    /// `old_line_count` is the [`LoroText::line_count`] before the change. Call it inside the
+    /// event callback, before the text changes again. All the edits in the delta are merged
+    /// into one change, and style changes are ignored.
+    ///
+    /// Returns `None` if the content is unchanged.
+    #[cfg(feature = "line-index")]
+    pub fn line_change(&self, delta: &[TextDelta], old_line_count: usize) -> Option<LineChange> {
+        self.handler.line_change(delta, old_line_count)
+    }
+
     /// Get the text in [Delta](https://quilljs.com/docs/delta/) format.
     ///
     /// # Example
@@ -1602,12 +2297,133 @@ impl LoroText {
         self.handler.get_richtext_value()
     }
 
+    /// Get the text as a [QuillDelta], which can be serialized into the canonical
+    /// [Quill Delta](https://quilljs.com/docs/delta/) JSON.
+    ///
+    /// # Example
+    /// ```
+    /// # use loro::{LoroDoc, QuillDelta, ToJson};
+    /// # use serde_json::json;
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// let delta: QuillDelta = serde_json::from_value(json!({
+    ///     "ops": [{ "insert": "Hello", "attributes": { "bold": true } }, { "insert": " world" }]
+    /// }))
+    /// .unwrap();
+    /// text.apply_quill_delta(&delta).unwrap();
+    ///
+    /// let change: QuillDelta = serde_json::from_value(json!({
+    ///     "ops": [{ "retain": 4, "attributes": { "bold": null } }]
+    /// }))
+    /// .unwrap();
+    /// text.apply_quill_delta(&change).unwrap();
+    /// assert_eq!(
+    ///     text.to_quill_delta().to_json_value(),
+    ///     json!({
+    ///         "ops": [
+    ///             { "insert": "Hell" },
+    ///             { "insert": "o", "attributes": { "bold": true } },
+    ///             { "insert": " world" },
+    ///         ]
+    ///     })
+    /// );
+    /// ```
+    pub fn to_quill_delta(&self) -> QuillDelta {
+        self.handler.to_quill_delta()
+    }
+
+    /// Apply a [QuillDelta] to the text container.
+    ///
+    /// A `null` attribute on a retain removes the style from the range. Embeds are inserted
+    /// as inline embeds, see [`LoroText::insert_embed`].
+    pub fn apply_quill_delta(&self, delta: &QuillDelta) -> LoroResult<()> {
+        self.handler.apply_quill_delta(delta)
+    }
+
     /// Get the text content of the text container.
     #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         self.handler.to_string()
     }
 
+    /// Render the rich text as Markdown, with the style keys mapped by `options`.
+    ///
+    /// Inline embeds are left out of the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, TextRenderOptions};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// text.mark(0..7, "bold", true).unwrap();
+    /// text.mark(6..11, "italic", true).unwrap();
+    /// let options = TextRenderOptions::default();
+    /// assert_eq!(text.to_markdown(&options), "**Hello *w****orld*");
+    /// assert_eq!(
+    ///     text.to_html(&options),
+    ///     "<strong>Hello <em>w</em></strong><em>orld</em>"
+    /// );
+    /// ```
+    pub fn to_markdown(&self, options: &TextRenderOptions) -> String {
+        self.handler.to_markdown(options)
+    }
+
+    /// Render the rich text as an HTML fragment, with the style keys mapped by `options`.
+    pub fn to_html(&self, options: &TextRenderOptions) -> String {
+        self.handler.to_html(options)
+    }
+
+    /// Parse the inline Markdown and insert it at `pos` as text with marks.
+    ///
+    /// Emphasis, strong emphasis, strikethrough, code spans and links are mapped to the style
+    /// keys by `options`. The styles whose keys are not configured in the doc's
+    /// [`StyleConfigMap`] are dropped.
+    ///
+    /// Only the inline subset of CommonMark is parsed. Block syntax such as headings and lists
+    /// is kept as plain text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, TextRenderOptions, ToJson};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert_markdown(0, "Hello **[world](https://loro.dev)**", &TextRenderOptions::default())
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     text.to_delta().to_json_value(),
+    ///     json!([
+    ///         { "insert": "Hello " },
+    ///         { "insert": "world", "attributes": { "bold": true, "link": "https://loro.dev" } },
+    ///     ])
+    /// );
+    /// ```
+    pub fn insert_markdown(
+        &self,
+        pos: usize,
+        markdown: &str,
+        options: &TextRenderOptions,
+    ) -> LoroResult<()> {
+        self.handler.insert_markdown(pos, markdown, options)
+    }
+
+    /// Parse the inline Markdown and update the text and its marks to it with the minimal
+    /// set of ops, like [`LoroText::update`].
+    ///
+    /// The styles mapped by `options` that are not in the Markdown are removed.
+    pub fn update_by_markdown(
+        &self,
+        markdown: &str,
+        options: &TextRenderOptions,
+    ) -> LoroResult<()> {
+        self.handler.update_by_markdown(markdown, options)
+    }
+
     /// Get the cursor at the given position in the given Unicode position.
     ///
     /// Using "index" to denote cursor positions can be unstable, as positions may
@@ -1642,6 +2458,21 @@ impl LoroText {
         self.handler.get_cursor(pos, side)
     }
 
+    /// Get a stable range for the given unicode range, such as a selection or a comment anchor.
+    ///
+    /// The content inserted at the boundaries of the range is not included.
+    /// See [`LoroDoc::get_range_cursor_pos`].
+    pub fn get_range_cursor(&self, range: Range<usize>) -> Option<RangeCursor> {
+        self.handler.get_range_cursor(range.start, range.end)
+    }
+
+    /// Get the stable position at the given grapheme cluster position.
+    ///
+    /// See [`LoroText::get_cursor`].
+    pub fn get_cursor_grapheme(&self, pos: usize, side: Side) -> Option<Cursor> {
+        self.handler.get_cursor_grapheme(pos, side)
+    }
+
     /// Whether the text container is deleted.
     pub fn is_deleted(&self) -> bool {
         self.handler.is_deleted()
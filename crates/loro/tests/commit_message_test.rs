@@ -1,4 +1,4 @@
-use loro::{CommitOptions, LoroDoc, VersionVector, ID};
+use loro::{CommitOptions, LoroDoc, LoroValue, VersionVector, ID};
 
 #[test]
 fn test_commit_message() {
@@ -156,3 +156,72 @@ fn test_commit_message_json_updates() {
     let text2 = doc2.get_text("text");
     assert_eq!(text2.to_string(), "hello world");
 }
+
+#[test]
+fn test_commit_metadata() {
+    let doc1 = LoroDoc::new();
+    doc1.set_peer_id(1).unwrap();
+    let text1 = doc1.get_text("text");
+
+    text1.insert(0, "hello").unwrap();
+    doc1.commit_with(
+        CommitOptions::new()
+            .commit_msg("first edit")
+            .commit_metadata(vec![("author".to_string(), LoroValue::from("alice"))]),
+    );
+
+    text1.insert(5, " world").unwrap();
+    doc1.commit_with(
+        CommitOptions::new()
+            .commit_msg("first edit")
+            .commit_metadata(vec![("author".to_string(), LoroValue::from("bob"))]),
+    );
+
+    // Changes with different metadata should not be merged
+    let change1 = doc1.get_change(ID::new(1, 0)).unwrap();
+    let change2 = doc1.get_change(ID::new(1, 5)).unwrap();
+    assert_eq!(change1.len, 5);
+    assert_eq!(
+        change1.metadata.as_ref().unwrap().get("author"),
+        Some(&LoroValue::from("alice"))
+    );
+    assert_eq!(
+        change2.metadata.as_ref().unwrap().get("author"),
+        Some(&LoroValue::from("bob"))
+    );
+
+    let check = |doc: &LoroDoc| {
+        let change1 = doc.get_change(ID::new(1, 0)).unwrap();
+        let change2 = doc.get_change(ID::new(1, 5)).unwrap();
+        assert_eq!(
+            change1.metadata,
+            doc1.get_change(ID::new(1, 0)).unwrap().metadata
+        );
+        assert_eq!(
+            change2.metadata,
+            doc1.get_change(ID::new(1, 5)).unwrap().metadata
+        );
+        assert_eq!(doc.get_text("text").to_string(), "hello world");
+    };
+
+    let doc2 = LoroDoc::new();
+    doc2.import(&doc1.export(loro::ExportMode::all_updates()).unwrap())
+        .unwrap();
+    check(&doc2);
+
+    let doc3 = LoroDoc::new();
+    doc3.import(&doc1.export(loro::ExportMode::Snapshot).unwrap())
+        .unwrap();
+    check(&doc3);
+
+    let doc4 = LoroDoc::new();
+    doc4.import_json_updates(doc1.export_json_updates(&VersionVector::new(), &doc1.oplog_vv()))
+        .unwrap();
+    check(&doc4);
+
+    // Changes without metadata are unaffected
+    doc2.set_peer_id(2).unwrap();
+    doc2.get_text("text").insert(0, "!").unwrap();
+    doc2.commit();
+    assert!(doc2.get_change(ID::new(2, 0)).unwrap().metadata.is_none());
+}
@@ -42,6 +42,7 @@ fn allow_editing_on_detached_mode_when_detached_editing_is_enabled() {
                             loro::TextDelta::Delete { delete } => {
                                 s.replace_range(index..index + delete, "");
                             }
+                            loro::TextDelta::InsertEmbed { .. } => unreachable!(),
                         }
                     }
                 }
@@ -111,6 +112,7 @@ fn allow_editing_on_detached_mode_when_detached_editing_is_enabled() {
             deps: [],
             lamport: 0,
             msg: None,
+            metadata: None,
             ops: [
                 JsonOp {
                     content: Text(
@@ -132,6 +134,7 @@ fn allow_editing_on_detached_mode_when_detached_editing_is_enabled() {
             ],
             lamport: 5,
             msg: None,
+            metadata: None,
             ops: [
                 JsonOp {
                     content: Text(
@@ -154,6 +157,7 @@ fn allow_editing_on_detached_mode_when_detached_editing_is_enabled() {
             ],
             lamport: 12,
             msg: None,
+            metadata: None,
             ops: [
                 JsonOp {
                     content: Text(
@@ -196,6 +200,7 @@ fn allow_editing_on_detached_mode_when_detached_editing_is_enabled_2() {
                             loro::TextDelta::Delete { delete } => {
                                 s.replace_range(index..index + delete, "");
                             }
+                            loro::TextDelta::InsertEmbed { .. } => unreachable!(),
                         }
                     }
                 }
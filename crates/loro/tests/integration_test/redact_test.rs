@@ -1,5 +1,11 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use loro::event::Diff;
 use loro::json::redact;
-use loro::{LoroDoc, LoroList, LoroMovableList, LoroTree, LoroValue};
+use loro::{ExportMode, LoroDoc, LoroList, LoroMovableList, LoroTree, LoroValue, TextDelta};
 use loro_internal::version::VersionRange;
 
 #[test]
@@ -290,3 +296,118 @@ fn redact_should_keep_parent_child_relationship() {
     new_doc.import_json_updates(&redacted_json).unwrap();
     assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
 }
+
+#[test]
+fn redact_doc_in_place() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "Hello, secret world").unwrap();
+    let map = doc.get_map("map");
+    map.insert("password", "secret").unwrap();
+    doc.commit();
+
+    let peer = LoroDoc::new();
+    peer.set_peer_id(2).unwrap();
+    peer.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+
+    // "secret" in the text and the map value
+    let mut text_range = VersionRange::new();
+    text_range.insert(1, 7, 13);
+    let mut map_range = VersionRange::new();
+    map_range.insert(1, 19, 20);
+    doc.redact(&text_range).unwrap();
+    doc.redact(&map_range).unwrap();
+    assert_eq!(text.to_string(), "Hello, ������ world");
+    assert_eq!(
+        map.get("password").unwrap().into_value().unwrap(),
+        LoroValue::Null
+    );
+
+    let contains_secret = |bytes: &[u8]| bytes.windows(6).any(|w| w == b"secret");
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    let updates = doc.export(ExportMode::all_updates()).unwrap();
+    assert!(!contains_secret(&snapshot));
+    assert!(!contains_secret(&updates));
+    for bytes in [snapshot, updates] {
+        let new_doc = LoroDoc::new();
+        new_doc.import(&bytes).unwrap();
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    }
+
+    // The redacted doc can still sync with the peers
+    peer.get_text("text").insert(0, "!").unwrap();
+    peer.commit();
+    doc.import(&peer.export(ExportMode::updates(&doc.oplog_vv())).unwrap())
+        .unwrap();
+    assert_eq!(text.to_string(), "!Hello, ������ world");
+    peer.redact(&text_range).unwrap();
+    peer.redact(&map_range).unwrap();
+    peer.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(peer.get_deep_value(), doc.get_deep_value());
+}
+
+#[test]
+fn redaction_is_applied_by_each_peer() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.get_text("text").insert(0, "my secret").unwrap();
+    doc.commit();
+
+    let peer = LoroDoc::new();
+    peer.set_peer_id(2).unwrap();
+    peer.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    // A concurrent edit on the peer
+    peer.get_text("text").insert(0, "> ").unwrap();
+    peer.commit();
+
+    let mut range = VersionRange::new();
+    range.insert(1, 3, 9);
+    doc.redact(&range).unwrap();
+    assert_eq!(doc.get_text("text").to_string(), "my ������");
+
+    // Importing the redacted history doesn't purge anything on the peer
+    peer.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(peer.get_text("text").to_string(), "> my secret");
+    // A root map with any name is ordinary data
+    peer.get_map("loro_redactions").insert("x", "y").unwrap();
+    doc.import(&peer.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(doc.get_text("text").to_string(), "> my ������");
+
+    // The peer opts in by applying the same range, and its subscribers see the purge
+    let redacted = Arc::new(AtomicBool::new(false));
+    let redacted_clone = redacted.clone();
+    let _sub = peer.subscribe_root(Arc::new(move |e| {
+        for diff in e.events.iter() {
+            if let Diff::Text(delta) = &diff.diff {
+                let has_redacted_text = delta.iter().any(|d| {
+                    matches!(d, TextDelta::Insert { insert, .. } if insert.contains('\u{FFFD}'))
+                });
+                if has_redacted_text {
+                    redacted_clone.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }));
+    peer.redact(&range).unwrap();
+    assert!(redacted.load(Ordering::Relaxed));
+    assert_eq!(peer.get_text("text").to_string(), "> my ������");
+    let contains_secret = |bytes: &[u8]| bytes.windows(6).any(|w| w == b"secret");
+    assert!(!contains_secret(
+        &peer.export(ExportMode::Snapshot).unwrap()
+    ));
+    assert!(!contains_secret(
+        &peer.export(ExportMode::all_updates()).unwrap()
+    ));
+
+    // Redacting the same range again changes nothing
+    redacted.store(false, Ordering::Relaxed);
+    peer.redact(&range).unwrap();
+    assert!(!redacted.load(Ordering::Relaxed));
+    assert_eq!(peer.get_deep_value(), doc.get_deep_value());
+}
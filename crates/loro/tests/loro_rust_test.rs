@@ -11,8 +11,8 @@ use std::{
 
 use loro::{
     awareness::Awareness, loro_value, CommitOptions, ContainerID, ContainerTrait, ContainerType,
-    ExportMode, Frontiers, FrontiersNotIncluded, LoroDoc, LoroError, LoroList, LoroMap, LoroText,
-    ToJson,
+    DiffGranularity, ExportMode, Frontiers, FrontiersNotIncluded, LoroDoc, LoroError, LoroList,
+    LoroMap, LoroText, ToJson, UpdateOptions,
 };
 use loro_internal::{encoding::EncodedBlobMode, handler::TextDelta, id::ID, vv, LoroResult};
 use rand::{Rng, SeedableRng};
@@ -1810,6 +1810,7 @@ fn test_travel_change_ancestors() {
         id: 12@1,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [
                 11@1,
@@ -1823,6 +1824,7 @@ fn test_travel_change_ancestors() {
         id: 6@2,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [
                 10@1,
@@ -1836,6 +1838,7 @@ fn test_travel_change_ancestors() {
         id: 11@1,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [
                 10@1,
@@ -1849,6 +1852,7 @@ fn test_travel_change_ancestors() {
         id: 0@2,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [
                 4@1,
@@ -1861,6 +1865,7 @@ fn test_travel_change_ancestors() {
         id: 0@1,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [],
         ),
@@ -1884,6 +1889,7 @@ fn test_travel_change_ancestors() {
         id: 0@2,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [
                 4@1,
@@ -1896,6 +1902,7 @@ fn test_travel_change_ancestors() {
         id: 0@1,
         timestamp: 0,
         message: None,
+        metadata: None,
         deps: Frontiers(
             [],
         ),
@@ -2225,6 +2232,51 @@ fn get_changed_containers_in() {
     )
 }
 
+#[test]
+fn changes_touching_container() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(0).unwrap();
+    let map = doc.get_map("map");
+    let child = map.insert_container("text", LoroText::new()).unwrap();
+    doc.commit_with(CommitOptions::new().commit_msg("create"));
+    child.insert(0, "hello").unwrap();
+    doc.commit_with(CommitOptions::new().commit_msg("edit child"));
+    doc.get_text("other").insert(0, "world").unwrap();
+    doc.commit_with(CommitOptions::new().commit_msg("edit other"));
+    let v = doc.state_frontiers();
+    child.insert(5, "!").unwrap();
+    doc.commit_with(CommitOptions::new().commit_msg("edit child again"));
+
+    let changes = doc.changes_touching(&map.id(), None).unwrap();
+    let messages: Vec<&str> = changes.iter().map(|c| c.message()).collect();
+    assert_eq!(messages, vec!["create", "edit child", "edit child again"]);
+
+    let changes = doc.changes_touching(&child.id(), None).unwrap();
+    let messages: Vec<&str> = changes.iter().map(|c| c.message()).collect();
+    assert_eq!(messages, vec!["edit child", "edit child again"]);
+
+    let changes = doc.changes_touching(&map.id(), Some(&v)).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].message(), "edit child again");
+
+    // The index is maintained for the new changes and the imported changes
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(1).unwrap();
+    doc_b
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    doc_b.get_text(&child.id()).insert(0, "> ").unwrap();
+    doc_b.commit_with(CommitOptions::new().commit_msg("remote edit"));
+    doc.import(&doc_b.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let changes = doc.changes_touching(&map.id(), Some(&v)).unwrap();
+    let messages: Vec<&str> = changes.iter().map(|c| c.message()).collect();
+    assert_eq!(messages, vec!["edit child again", "remote edit"]);
+
+    doc.free_container_changes_index();
+    assert_eq!(doc.changes_touching(&map.id(), None).unwrap().len(), 4);
+}
+
 #[test]
 fn is_deleted() {
     let doc = LoroDoc::new();
@@ -2257,3 +2309,784 @@ fn change_count() {
     new_doc.import(&bytes.unwrap()).unwrap();
     assert_eq!(new_doc.len_changes(), n);
 }
+
+#[test]
+fn checkout_at_timestamp() {
+    let doc1 = LoroDoc::new();
+    doc1.set_peer_id(1).unwrap();
+    doc1.set_change_merge_interval(0);
+    let text = doc1.get_text("text");
+    text.insert(0, "a").unwrap();
+    doc1.commit_with(CommitOptions::new().timestamp(10));
+    text.insert(1, "b").unwrap();
+    doc1.commit_with(CommitOptions::new().timestamp(20));
+
+    // doc2's clock is ahead of doc1's
+    let doc2 = LoroDoc::new();
+    doc2.set_peer_id(2).unwrap();
+    doc2.import(&doc1.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    doc2.get_text("text").insert(2, "X").unwrap();
+    doc2.commit_with(CommitOptions::new().timestamp(100));
+
+    text.insert(2, "c").unwrap();
+    doc1.commit_with(CommitOptions::new().timestamp(30));
+    doc1.import(&doc2.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+
+    assert_eq!(doc1.frontiers_at_timestamp(5), Frontiers::default());
+    assert_eq!(
+        doc1.frontiers_at_timestamp(25),
+        Frontiers::from(ID::new(1, 1))
+    );
+    assert_eq!(
+        doc1.frontiers_at_timestamp(50),
+        Frontiers::from(ID::new(1, 2))
+    );
+    assert_eq!(
+        doc1.frontiers_to_vv(&doc1.frontiers_at_timestamp(100)),
+        Some(doc1.oplog_vv())
+    );
+
+    doc1.checkout_at_timestamp(50).unwrap();
+    assert_eq!(text.to_string(), "abc");
+    doc1.checkout_at_timestamp(20).unwrap();
+    assert_eq!(text.to_string(), "ab");
+    doc1.checkout_at_timestamp(i64::MAX).unwrap();
+    assert_eq!(text.len_unicode(), 4);
+    doc1.checkout_to_latest();
+
+    // A new change can't be earlier than the changes it depends on
+    doc1.get_text("text").insert(0, "d").unwrap();
+    doc1.commit_with(CommitOptions::new().timestamp(40));
+    assert_eq!(doc1.get_change(ID::new(1, 3)).unwrap().timestamp, 100);
+    assert_eq!(
+        doc1.frontiers_at_timestamp(50),
+        Frontiers::from(ID::new(1, 2))
+    );
+}
+
+#[test]
+fn export_squashed_snapshot() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_change_merge_interval(0);
+    let text = doc.get_text("text");
+    for (i, ts) in [10, 11, 12, 110].into_iter().enumerate() {
+        text.insert(i, "a").unwrap();
+        doc.commit_with(CommitOptions::new().timestamp(ts));
+    }
+    text.insert(4, "b").unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(111).commit_msg("fix"));
+    let cursor = text.get_cursor(1, Default::default()).unwrap();
+    assert_eq!(doc.len_changes(), 5);
+
+    let bytes = doc.export(ExportMode::squashed_snapshot(10)).unwrap();
+    assert!(bytes.len() < doc.export(ExportMode::Snapshot).unwrap().len());
+    let new_doc = LoroDoc::new();
+    new_doc.import(&bytes).unwrap();
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    assert_eq!(new_doc.oplog_vv(), doc.oplog_vv());
+    assert_eq!(new_doc.len_changes(), 3);
+    let change = new_doc.get_change(ID::new(1, 0)).unwrap();
+    assert_eq!(change.len, 3);
+    assert_eq!(change.timestamp, 10);
+    let change = new_doc.get_change(ID::new(1, 3)).unwrap();
+    assert_eq!(change.len, 1);
+    assert_eq!(change.timestamp, 110);
+    // Changes with different commit messages are not merged
+    let change = new_doc.get_change(ID::new(1, 4)).unwrap();
+    assert_eq!(change.len, 1);
+    assert_eq!(change.message(), "fix");
+    assert_eq!(new_doc.get_cursor_pos(&cursor).unwrap().current.pos, 1);
+
+    // The squashed history can still be synced with the original doc
+    doc.get_text("text").insert(0, "c").unwrap();
+    doc.commit();
+    new_doc
+        .import(
+            &doc.export(ExportMode::updates(&new_doc.oplog_vv()))
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+}
+
+#[test]
+fn export_shallow_since_duration() {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_change_merge_interval(0);
+    let text = doc.get_text("text");
+    text.insert(0, "hello").unwrap();
+    let cursor_e = text.get_cursor(1, Default::default()).unwrap();
+    let cursor_l = text.get_cursor(2, Default::default()).unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(100));
+    text.delete(0, 2).unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(200));
+    text.insert(3, "!").unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(300));
+    text.insert(4, " world").unwrap();
+    let cursor_w = text.get_cursor(5, Default::default()).unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(now));
+    text.delete(5, 1).unwrap();
+    doc.commit_with(CommitOptions::new().timestamp(now));
+    assert_eq!(text.to_string(), "llo! orld");
+
+    let one_day = std::time::Duration::from_secs(24 * 60 * 60);
+    let cut = doc.frontiers_at_timestamp(now - one_day.as_secs() as i64);
+    assert_eq!(cut, Frontiers::from(ID::new(1, 7)));
+    let cursors = [cursor_e.clone(), cursor_l.clone(), cursor_w.clone()];
+    assert_eq!(
+        doc.unresolvable_cursors_in_shallow_snapshot(&cut, &cursors)
+            .unwrap(),
+        vec![cursor_e.clone()]
+    );
+
+    let new_doc = LoroDoc::new();
+    new_doc
+        .import(&doc.export(ExportMode::shallow_since(one_day)).unwrap())
+        .unwrap();
+    assert!(new_doc.is_shallow());
+    assert_eq!(new_doc.shallow_since_frontiers(), cut);
+    assert_eq!(new_doc.get_text("text").to_string(), "llo! orld");
+    assert!(new_doc.get_cursor_pos(&cursor_e).is_err());
+    assert_eq!(new_doc.get_cursor_pos(&cursor_l).unwrap().current.pos, 0);
+    assert_eq!(new_doc.get_cursor_pos(&cursor_w).unwrap().current.pos, 5);
+}
+
+#[test]
+fn set_deep_value_keeps_concurrent_edits() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_deep_value(
+        "root",
+        loro_value!({
+            "title": "Hello",
+            "items": [{"name": "a"}, {"name": "b"}, {"name": "c"}],
+            "removed": 1
+        }),
+    )
+    .unwrap();
+    doc.commit();
+    let other = LoroDoc::new();
+    other.set_peer_id(2).unwrap();
+    other
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+
+    // Concurrent edits on the other peer
+    let root = other.get_map("root");
+    let item = root
+        .get("items")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_list()
+        .unwrap()
+        .get(2)
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_map()
+        .unwrap();
+    item.insert("done", true).unwrap();
+    other.commit();
+
+    let ops_before = doc.len_ops();
+    doc.set_deep_value(
+        "root",
+        loro_value!({
+            "title": "Hello world",
+            "items": [{"name": "a"}, {"name": "x"}, {"name": "c"}]
+        }),
+    )
+    .unwrap();
+    doc.commit();
+    // Set the title, set the name of the second item and delete a key
+    assert_eq!(doc.len_ops() - ops_before, 3);
+
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(
+        doc.get_deep_value().to_json_value(),
+        json!({
+            "root": {
+                "title": "Hello world",
+                "items": [{"name": "a"}, {"name": "x"}, {"name": "c", "done": true}]
+            }
+        })
+    );
+
+    assert!(doc.set_deep_value("root", 1).is_err());
+}
+
+#[test]
+fn text_inline_embeds() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "Hello world").unwrap();
+    text.insert_embed(5, loro_value!({"image": "a.png"}))
+        .unwrap();
+    let map = text.insert_embed_container(0, LoroMap::new()).unwrap();
+    map.insert("user", "alice").unwrap();
+    text.mark(5..8, "bold", true).unwrap();
+    doc.commit();
+
+    assert_eq!(text.to_string(), "Hello world");
+    assert_eq!(text.len_unicode(), 13);
+    // Replace the container id so the expectation doesn't depend on its json form
+    let expected = json!([
+        { "insert": map.id().to_string() },
+        { "insert": "Hell" },
+        { "insert": "o", "attributes": { "bold": true } },
+        { "insert": { "image": "a.png" }, "attributes": { "bold": true } },
+        { "insert": " ", "attributes": { "bold": true } },
+        { "insert": "world" },
+    ]);
+    let mut delta = text.to_delta().to_json_value();
+    // A string insert is always parsed as text. The container embed has to be tagged.
+    let parsed: Vec<TextDelta> = serde_json::from_value(delta.clone()).unwrap();
+    assert!(matches!(&parsed[0], TextDelta::Insert { .. }));
+    let tagged = json!([{ "embed": delta[0]["insert"].clone() }]);
+    let parsed: Vec<TextDelta> = serde_json::from_value(tagged.clone()).unwrap();
+    assert_eq!(
+        parsed[0],
+        TextDelta::InsertEmbed {
+            insert: loro::LoroValue::Container(map.id()),
+            attributes: None,
+        }
+    );
+    assert_eq!(
+        serde_json::to_value(&parsed).unwrap()[0]["embed"],
+        tagged[0]["embed"]
+    );
+    delta[0]["insert"] = json!(map.id().to_string());
+    assert_eq!(delta, expected);
+
+    // The embeds survive both updates and snapshots
+    for mode in [ExportMode::all_updates(), ExportMode::Snapshot] {
+        let other = LoroDoc::new();
+        other.import(&doc.export(mode).unwrap()).unwrap();
+        assert_eq!(other.get_text("text").to_delta(), text.to_delta());
+        assert_eq!(
+            other.get_deep_value().to_json_value(),
+            doc.get_deep_value().to_json_value()
+        );
+        let child = other.get_map(map.id());
+        assert_eq!(child.get_value().to_json_value(), json!({"user": "alice"}));
+    }
+
+    let other = LoroDoc::new();
+    other
+        .import(&doc.export(ExportMode::Snapshot).unwrap())
+        .unwrap();
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran2 = ran.clone();
+    let _g = other.subscribe(
+        &text.id(),
+        Arc::new(move |event| {
+            for event in event.events {
+                let delta = event.diff.as_text().unwrap();
+                assert_eq!(
+                    delta,
+                    &vec![
+                        TextDelta::Retain {
+                            retain: 2,
+                            attributes: None,
+                        },
+                        TextDelta::InsertEmbed {
+                            insert: loro_value!({"image": "cat.png"}),
+                            attributes: None,
+                        },
+                    ]
+                );
+                ran2.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }),
+    );
+    let v = doc.state_vv();
+    let image = loro_value!({"image": "cat.png"});
+    text.insert_embed(2, image).unwrap();
+    doc.commit();
+    other
+        .import(&doc.export(ExportMode::updates(&v)).unwrap())
+        .unwrap();
+    assert!(ran.load(std::sync::atomic::Ordering::Relaxed));
+
+    text.delete(0, 9).unwrap();
+    doc.commit();
+    assert_eq!(text.to_string(), "world");
+    assert_eq!(text.len_unicode(), 5);
+    assert!(text
+        .insert_embed(0, loro::LoroValue::Container(map.id()))
+        .is_err());
+    // A string or null embed would look like text in the delta
+    assert!(text.insert_embed(0, "cat.png").is_err());
+    assert!(text.insert_embed(0, loro::LoroValue::Null).is_err());
+}
+
+#[test]
+fn text_block_attributes() {
+    let new_doc = |peer| {
+        let doc = LoroDoc::new();
+        doc.set_peer_id(peer).unwrap();
+        let mut styles = loro::StyleConfigMap::default_rich_text_config();
+        styles.insert_block("header".into());
+        styles.insert_block("align".into());
+        doc.config_text_style(styles);
+        doc
+    };
+    let doc_a = new_doc(1);
+    let text_a = doc_a.get_text("text");
+    text_a.insert(0, "Title\nBody").unwrap();
+    // The last paragraph has no newline to store the attributes
+    assert!(text_a.set_block_attr(8, "align", "right").is_err());
+    assert!(text_a.mark(2..8, "header", 2).is_err());
+    assert_eq!(text_a.to_string(), "Title\nBody");
+    text_a.insert(10, "\n").unwrap();
+    doc_a.commit();
+    let doc_b = new_doc(2);
+    doc_b
+        .import(&doc_a.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let text_b = doc_b.get_text("text");
+
+    // Concurrent changes to different attributes of the same paragraph
+    text_a.set_block_attr(0, "header", 1).unwrap();
+    text_b.set_block_attr(3, "align", "center").unwrap();
+    // Concurrent changes to the last paragraph share its trailing newline
+    text_a.set_block_attr(8, "header", 3).unwrap();
+    text_b.set_block_attr(8, "align", "right").unwrap();
+    assert!(text_a.set_block_attr(0, "bold", true).is_err());
+    doc_a.commit();
+    doc_b.commit();
+    doc_a
+        .import(&doc_b.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    doc_b
+        .import(&doc_a.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let expected = json!([
+        { "insert": "Title" },
+        { "insert": "\n", "attributes": { "header": 1, "align": "center" } },
+        { "insert": "Body" },
+        { "insert": "\n", "attributes": { "header": 3, "align": "right" } },
+    ]);
+    assert_eq!(text_a.to_delta().to_json_value(), expected);
+    assert_eq!(text_b.to_delta().to_json_value(), expected);
+    assert_eq!(text_a.to_string(), "Title\nBody\n");
+    assert_eq!(text_a.get_block_attrs(2).len(), 2);
+    assert_eq!(text_a.get_block_attrs(5).len(), 2);
+    assert_eq!(
+        text_a.get_block_attrs(6).get("align"),
+        Some(&"right".into())
+    );
+
+    // Typing at the end of a paragraph doesn't expand the attributes
+    text_a.insert(5, "!").unwrap();
+    text_a.remove_block_attr(0, "align").unwrap();
+    // Marking a range with a block key styles every paragraph it touches
+    text_a.mark(2..8, "header", 2).unwrap();
+    doc_a.commit();
+    assert_eq!(
+        text_a.to_delta().to_json_value(),
+        json!([
+            { "insert": "Title!" },
+            { "insert": "\n", "attributes": { "header": 2 } },
+            { "insert": "Body" },
+            { "insert": "\n", "attributes": { "align": "right", "header": 2 } },
+        ])
+    );
+
+    let doc_c = new_doc(3);
+    doc_c
+        .import(&doc_a.export(ExportMode::Snapshot).unwrap())
+        .unwrap();
+    assert_eq!(doc_c.get_text("text").to_delta(), text_a.to_delta());
+
+    // Neither `mark` nor `set_block_attr` edits the text
+    let text_c = doc_c.get_text("text");
+    text_c.insert(text_c.len_unicode(), "Tail").unwrap();
+    assert!(text_c.mark(7..16, "align", "left").is_err());
+    assert!(text_c.set_block_attr(12, "align", "left").is_err());
+    assert_eq!(text_c.to_string(), "Title!\nBody\nTail");
+    assert_eq!(
+        text_c.get_block_attrs(7).get("align"),
+        Some(&"right".into())
+    );
+    text_c.unmark(0..16, "align").unwrap();
+    assert_eq!(text_c.to_string(), "Title!\nBody\nTail");
+    assert!(text_c.get_block_attrs(7).get("align").is_none());
+}
+
+#[cfg(feature = "text-search")]
+#[test]
+fn text_regex_replace_all() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "😀 TODO: a, TODO: b").unwrap();
+    text.mark(2..10, "bold", true).unwrap();
+    doc.commit();
+
+    let regex = loro::Regex::new(r"TODO: (\w)").unwrap();
+    let found = text.find_all(&regex, loro::TextPosUnit::Unicode);
+    assert_eq!(
+        found.iter().map(|m| m.range.clone()).collect::<Vec<_>>(),
+        vec![2..9, 11..18]
+    );
+    let found_utf16 = text.find_all(&regex, loro::TextPosUnit::Utf16);
+    assert_eq!(
+        found_utf16
+            .iter()
+            .map(|m| m.range.clone())
+            .collect::<Vec<_>>(),
+        vec![3..10, 12..19]
+    );
+
+    let changes = doc.len_changes();
+    assert_eq!(text.replace_all(&regex, "TODO! $1").unwrap(), 2);
+    doc.commit();
+    assert_eq!(doc.len_changes(), changes + 1);
+    assert_eq!(text.to_string(), "😀 TODO! a, TODO! b");
+    // Only the colons are replaced, and the new chars take their styles
+    assert_eq!(
+        text.ranges_with_style("bold", loro::TextPosUnit::Unicode),
+        vec![(2..10, true.into())]
+    );
+
+    let start = found[1].start.as_ref().unwrap();
+    let end = found[1].end.as_ref().unwrap();
+    assert_eq!(doc.get_cursor_pos(start).unwrap().current.pos, 11);
+    assert_eq!(doc.get_cursor_pos(end).unwrap().current.pos, 18);
+}
+
+#[cfg(feature = "line-index")]
+#[test]
+fn text_line_index_and_line_changes() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    let lines = Arc::new(std::sync::Mutex::new(vec![String::new()]));
+    let lines_clone = lines.clone();
+    let text_clone = text.clone();
+    let _g = doc.subscribe(
+        &text.id(),
+        Arc::new(move |x| {
+            for event in x.events {
+                let delta = event.diff.as_text().unwrap();
+                let mut lines = lines_clone.lock().unwrap();
+                let old_line_count = lines.len();
+                let change = text_clone.line_change(delta, old_line_count).unwrap();
+                lines.splice(change.old_lines, change.new_lines);
+            }
+        }),
+    );
+
+    text.insert(0, "fn main() {\n}").unwrap();
+    doc.commit();
+    text.insert(12, "    println!(\"😀\");\n").unwrap();
+    doc.commit();
+    assert_eq!(text.line_count(), 3);
+    assert_eq!(text.line(1).unwrap(), "    println!(\"😀\");");
+    assert_eq!(
+        text.pos_to_line_col(27, loro::TextPosUnit::Unicode)
+            .unwrap(),
+        (1, 15)
+    );
+    assert_eq!(
+        text.line_col_to_pos(1, 17, loro::TextPosUnit::Utf16)
+            .unwrap(),
+        29
+    );
+
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2).unwrap();
+    doc_b
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let text_b = doc_b.get_text("text");
+    text_b.delete(11, 21).unwrap();
+    text_b.insert(0, "// entry\n").unwrap();
+    doc_b.commit();
+    doc.import(&doc_b.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+
+    assert_eq!(text.to_string(), "// entry\nfn main() {}");
+    assert_eq!(
+        *lines.lock().unwrap(),
+        text.to_string().split('\n').collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn text_grapheme_positions() {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "hi 👋🏻 👨‍👩‍👧").unwrap();
+    assert_eq!(text.len_grapheme(), 6);
+    assert_eq!(text.slice_grapheme(3, 4).unwrap(), "👋🏻");
+    let cursor = text.get_cursor_grapheme(5, Default::default()).unwrap();
+    text.insert_grapheme(5, "→").unwrap();
+    assert_eq!(text.to_string(), "hi 👋🏻 →👨‍👩‍👧");
+    assert_eq!(doc.get_cursor_pos(&cursor).unwrap().current.pos, 7);
+
+    // Only the last member of the family is changed, but the whole cluster is replaced
+    text.update("hi 👋🏻 →👨‍👩‍👦", Default::default()).unwrap();
+    assert_eq!(text.to_string(), "hi 👋🏻 →👨‍👩‍👦");
+    assert_eq!(text.len_grapheme(), 7);
+    assert!(doc.get_cursor_pos(&cursor).unwrap().update.is_some());
+
+    text.delete_grapheme(3, 1).unwrap();
+    assert_eq!(text.to_string(), "hi  →👨‍👩‍👦");
+}
+
+#[test]
+fn text_update_by_words_and_semantic_cleanup() {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "fn add(a, b) {\n    a + b\n}").unwrap();
+    // On "b" of "a + b"
+    let cursor = text.get_cursor(23, Default::default()).unwrap();
+
+    // Re-indent the body: the lines only differ in whitespace
+    let options = UpdateOptions::default().ignore_whitespace(true);
+    text.update_by_line("fn add(a, b) {\n  a + b\n}", options)
+        .unwrap();
+    assert_eq!(text.to_string(), "fn add(a, b) {\n  a + b\n}");
+    let pos = doc.get_cursor_pos(&cursor).unwrap();
+    assert!(pos.update.is_none());
+    assert_eq!(pos.current.pos, 21);
+
+    // Rename a parameter word by word
+    let options = UpdateOptions::default().granularity(DiffGranularity::Word);
+    text.update("fn add(x, b) {\n  x + b\n}", options).unwrap();
+    assert_eq!(text.to_string(), "fn add(x, b) {\n  x + b\n}");
+    let pos = doc.get_cursor_pos(&cursor).unwrap();
+    assert!(pos.update.is_none());
+    assert_eq!(pos.current.pos, 21);
+
+    // "pin" -> "tie": a char diff keeps the "i", the semantic cleanup replaces the word
+    for (semantic_cleanup, kept) in [(false, true), (true, false)] {
+        let doc = LoroDoc::new();
+        let text = doc.get_text("text");
+        text.insert(0, "a pin here").unwrap();
+        let cursor = text.get_cursor(3, Default::default()).unwrap();
+        let options = UpdateOptions::default().semantic_cleanup(semantic_cleanup);
+        text.update("a tie here", options).unwrap();
+        assert_eq!(text.to_string(), "a tie here");
+        assert_eq!(doc.get_cursor_pos(&cursor).unwrap().update.is_none(), kept);
+    }
+}
+
+#[test]
+fn text_insert_with_marks() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "Hello").unwrap();
+    text.mark(0..5, "bold", true).unwrap();
+
+    // The inserted text would inherit the bold style, but it's removed
+    let mut attrs = fxhash::FxHashMap::default();
+    attrs.insert("italic".to_string(), true.into());
+    text.insert_with_marks(5, "!", &attrs).unwrap();
+    // Keep the inherited style explicitly
+    let mut attrs = fxhash::FxHashMap::default();
+    attrs.insert("bold".to_string(), true.into());
+    text.insert_with_marks(0, ">", &attrs).unwrap();
+    // Remove the inherited style with null
+    let mut attrs = fxhash::FxHashMap::default();
+    attrs.insert("bold".to_string(), loro::LoroValue::Null);
+    text.insert_with_marks(3, "_", &attrs).unwrap();
+    assert_eq!(
+        text.to_delta().to_json_value(),
+        json!([
+            { "insert": ">He", "attributes": { "bold": true } },
+            { "insert": "_" },
+            { "insert": "llo", "attributes": { "bold": true } },
+            { "insert": "!", "attributes": { "italic": true } },
+        ])
+    );
+
+    // A paste through `apply_delta` replaces the styles the same way
+    text.apply_delta(&[
+        TextDelta::Retain {
+            retain: 8,
+            attributes: None,
+        },
+        TextDelta::Insert {
+            insert: " ok".into(),
+            attributes: Some(
+                [("underline".to_string(), true.into())]
+                    .into_iter()
+                    .collect(),
+            ),
+        },
+    ])
+    .unwrap();
+    doc.commit();
+    assert_eq!(
+        text.to_delta().to_json_value(),
+        json!([
+            { "insert": ">He", "attributes": { "bold": true } },
+            { "insert": "_" },
+            { "insert": "llo", "attributes": { "bold": true } },
+            { "insert": "!", "attributes": { "italic": true } },
+            { "insert": " ok", "attributes": { "underline": true } },
+        ])
+    );
+
+    let doc_b = LoroDoc::new();
+    doc_b
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(doc_b.get_text("text").to_delta(), text.to_delta());
+}
+
+#[test]
+fn text_range_cursor() {
+    use loro::cursor::{RangeCursor, RangeQueryResult};
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "The quick fox").unwrap();
+    let range = text.get_range_cursor(4..9).unwrap();
+    let caret = text.get_range_cursor(10..10).unwrap();
+    assert!(!range.is_collapsed());
+    assert!(caret.is_collapsed());
+    let range = RangeCursor::decode(&range.encode()).unwrap();
+    doc.commit();
+
+    // Concurrent insertions at both boundaries are not included
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2).unwrap();
+    doc_b
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    doc_b.get_text("text").insert(4, "very ").unwrap();
+    text.insert(9, "ly").unwrap();
+    doc.import(&doc_b.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(text.to_string(), "The very quickly fox");
+    let query = |range: &RangeCursor| {
+        doc.get_range_cursor_pos(range, loro::TextPosUnit::Unicode)
+            .unwrap()
+    };
+    assert_eq!(
+        query(&range),
+        RangeQueryResult::Range {
+            range: 9..14,
+            update: None
+        }
+    );
+    assert_eq!(
+        query(&caret),
+        RangeQueryResult::Range {
+            range: 17..17,
+            update: None
+        }
+    );
+
+    // The deleted start is traced back in the history
+    text.delete(9, 1).unwrap();
+    let RangeQueryResult::Range {
+        range: current,
+        update,
+    } = query(&range)
+    else {
+        panic!("the range is not deleted");
+    };
+    assert_eq!(current, 9..13);
+    let update = update.unwrap();
+    assert_eq!(
+        query(&update),
+        RangeQueryResult::Range {
+            range: 9..13,
+            update: None
+        }
+    );
+
+    // The range collapses when all of its content is deleted
+    text.delete(9, 4).unwrap();
+    assert_eq!(text.to_string(), "The very ly fox");
+    assert_eq!(query(&range), RangeQueryResult::Deleted);
+    assert_eq!(query(&update), RangeQueryResult::Deleted);
+    assert_eq!(
+        query(&caret),
+        RangeQueryResult::Range {
+            range: 12..12,
+            update: None
+        }
+    );
+}
+
+#[test]
+fn cursor_pos_batch() {
+    use loro::cursor::Side;
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    let list = doc.get_list("list");
+    text.insert(0, "Hello world").unwrap();
+    for i in 0..5 {
+        list.push(i).unwrap();
+    }
+
+    let mut cursors = Vec::new();
+    for pos in [0, 3, 6, 8, 11] {
+        cursors.push(text.get_cursor(pos, Side::Left).unwrap());
+        cursors.push(list.get_cursor(pos.min(5), Side::Right).unwrap());
+    }
+    doc.commit();
+
+    text.delete(2, 5).unwrap();
+    text.insert(0, "Oh, ").unwrap();
+    list.delete(1, 2).unwrap();
+    doc.commit();
+    text.delete(4, 2).unwrap();
+    list.delete(0, 1).unwrap();
+
+    let batch = doc.get_cursor_pos_batch(&cursors);
+    assert_eq!(batch.len(), cursors.len());
+    for (cursor, ans) in cursors.iter().zip(batch) {
+        assert_eq!(ans.ok(), doc.get_cursor_pos(cursor).ok());
+    }
+
+    assert!(doc.get_cursor_pos_batch(&[]).is_empty());
+}
+
+#[test]
+fn encode_diff_event() {
+    use loro::event::{encoded::EncodedDiffEvent, DiffEvent};
+
+    let doc = LoroDoc::new();
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |e| {
+        events_clone.try_lock().unwrap().push(e.encode().to_bytes());
+    }));
+    doc.get_map("map").insert("key", 1).unwrap();
+    doc.get_list("list").push("a").unwrap();
+    doc.commit();
+
+    let events = events.try_lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let encoded = EncodedDiffEvent::from_bytes(&events[0]).unwrap();
+    let new_doc = LoroDoc::new();
+    let diff = DiffEvent::decode(&encoded, &new_doc).unwrap();
+    new_doc
+        .inner()
+        .apply_diff(diff, &mut Default::default(), true)
+        .unwrap();
+    new_doc.commit();
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+}